@@ -1,21 +1,84 @@
 #![windows_subsystem = "windows"]
+#![deny(clippy::unwrap_used)]
 
 use eframe::egui;
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
+use std::cell::Cell;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
 use std::path::{Path, PathBuf};
-use std::sync::{mpsc, Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex, OnceLock};
 use std::thread;
-use std::time::UNIX_EPOCH;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+#[cfg(target_os = "windows")]
 use windows::core::PCWSTR;
-use windows::Win32::Foundation::{LPARAM, WPARAM};
-use windows::Win32::Graphics::Gdi::{AddFontResourceW, RemoveFontResourceW};
-use windows::Win32::UI::WindowsAndMessaging::{SendMessageW, HWND_BROADCAST, WM_FONTCHANGE};
+#[cfg(target_os = "windows")]
+use windows::Win32::Foundation::HWND;
+#[cfg(target_os = "windows")]
+use windows::Win32::Foundation::{
+    CloseHandle, GetLastError, SetLastError, ERROR_SUCCESS, LPARAM, WPARAM,
+};
+#[cfg(target_os = "windows")]
+use windows::Win32::Globalization::GetUserDefaultLCID;
+#[cfg(target_os = "windows")]
+use windows::Win32::Graphics::Gdi::{
+    AddFontResourceExW, AddFontResourceW, CreateBitmap, DeleteObject, EnumFontFamiliesExW, GetDC,
+    ReleaseDC, RemoveFontResourceExW, RemoveFontResourceW, DEFAULT_CHARSET, ENUMLOGFONTEXW,
+    FR_PRIVATE, LOGFONTW, TEXTMETRICW,
+};
+#[cfg(target_os = "windows")]
+use windows::Win32::System::Com::{
+    CoCreateInstance, CoInitializeEx, CoTaskMemFree, IPersistFile, CLSCTX_INPROC_SERVER,
+    COINIT_APARTMENTTHREADED, STGM_READ,
+};
+#[cfg(target_os = "windows")]
+use windows::Win32::System::Diagnostics::ToolHelp::{
+    CreateToolhelp32Snapshot, Process32FirstW, Process32NextW, PROCESSENTRY32W, TH32CS_SNAPPROCESS,
+};
+#[cfg(target_os = "windows")]
+use windows::Win32::System::Registry::{
+    RegCloseKey, RegEnumValueW, RegOpenKeyExW, HKEY, HKEY_CURRENT_USER, HKEY_LOCAL_MACHINE,
+    KEY_READ,
+};
+#[cfg(target_os = "windows")]
+use windows::Win32::System::Threading::{
+    GetCurrentProcess, GetGuiResources, GR_GDIOBJECTS, GR_GDIOBJECTS_PEAK,
+};
+#[cfg(target_os = "windows")]
+use windows::Win32::UI::Shell::{
+    FOLDERID_Fonts, FOLDERID_LocalAppData, IShellLinkW, ITaskbarList3, SHGetKnownFolderPath,
+    ShellLink, TaskbarList, KF_FLAG_DEFAULT, TBPF_INDETERMINATE, TBPF_NOPROGRESS, TBPF_NORMAL,
+};
+#[cfg(target_os = "windows")]
+use windows::Win32::UI::WindowsAndMessaging::{
+    CreateIconIndirect, DestroyIcon, SendMessageW, HICON, HWND_BROADCAST, ICONINFO, WM_FONTCHANGE,
+};
 
 #[derive(Default)]
 struct AppState {
-    loaded: HashSet<String>,
+    /// 以规范化路径为键，同一文件不同大小写或分隔符的路径只登记一次
+    loaded: HashMap<PathKey, LoadedFont>,
+    /// 上次会话未正常退出时遗留的字体面数，这些字体直到注销前仍占用 GDI 资源
+    residual_faces: usize,
+}
+
+impl AppState {
+    /// 本程序当前注册的字体面总数（AddFontResourceW 返回值之和）。
+    fn registered_faces(&self) -> usize {
+        self.residual_faces + self.loaded.values().map(|font| font.faces).sum::<usize>()
+    }
+}
+
+#[derive(Clone, Default)]
+struct LoadedFont {
+    /// 注册时使用的原始路径，卸载时原样传给 `RemoveFontResourceW`
+    path: String,
+    names: Vec<String>,
+    families: Vec<String>,
+    faces: usize,
 }
 
 #[derive(Clone, Serialize)]
@@ -24,37 +87,1229 @@ struct ProcessResult {
     failed: usize,
     missing: usize,
     duplicates: usize,
+    /// 系统已安装而跳过加载的字体
+    installed: usize,
+    /// 匹配文件与系统已安装字体同名的字体，无论是否加载
+    system_conflicts: usize,
+    /// 字幕用到了粗体/斜体，但匹配的文件缺少对应变体的字体
+    missing_variants: usize,
     subs: usize,
+    /// 为空或无法读取的字幕，不计入 `subs`
+    empty_subs: usize,
     fonts: usize,
-    logs: Vec<String>,
+    /// 解析不出字体名的字体文件，计入 `fonts`
+    invalid_fonts: usize,
+    logs: LogLines,
+    entries: Vec<FontEntry>,
+    /// 成功解析的字幕各自的字体覆盖率
+    subtitles: Vec<SubtitleReport>,
+    cache: CacheStats,
+    /// 本次索引的字体文件按类型的构成
+    types: FontTypeStats,
+    /// 只处理了字幕，没有任何可索引的字体文件（包括之前各次处理索引的）
+    subs_only: bool,
+    /// 字体名规则改写过的名称 `(原名, 改写后)`，字幕和字体两侧的都在内，见 [`NameRules`]
+    rule_hits: Vec<(String, String)>,
+    #[serde(skip)]
+    batch: LoadBatch,
+    /// 本次处理用到的字体索引，已合并之前各次处理的索引，见 [`FontIndex::merge`]
+    #[serde(skip)]
+    index: Option<Arc<FontIndex>>,
+}
+
+/// 只处理了字幕、没有字体文件时摘要中的提示，见 [`ProcessResult::subs_only`]。
+const SUBS_ONLY_HINT: &str = "提示: 请同时提供字体文件以完成加载";
+
+/// 单个字幕的字体覆盖情况。
+#[derive(Clone, Serialize)]
+struct SubtitleReport {
+    path: String,
+    /// 字幕引用的字体数
+    required: usize,
+    /// 其中已加载、已在别处加载或系统已安装的字体数
+    available: usize,
+    coverage_pct: f32,
+}
+
+/// 一次加载对 [`AppState`] 的改动，用于撤销。
+#[derive(Clone, Default)]
+struct LoadBatch {
+    /// 本批次新注册的文件
+    loaded_paths: Vec<String>,
+    /// 追加到已加载文件上的字体名 `(路径, 字体名)`
+    added_names: Vec<(String, String)>,
+}
+
+/// 待处理列表中的一项，类型在加入时确定。
+#[derive(Clone)]
+struct PendingEntry {
+    path: String,
+    kind: PathKind,
+}
+
+impl PendingEntry {
+    fn new(path: String) -> Self {
+        let kind = PathKind::of(Path::new(&path));
+        Self { path, kind }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum PathKind {
+    /// 目录，处理时递归遍历
+    Dir,
+    Font,
+    Sub,
+    /// 不认识的文件，如压缩包、快捷方式或其他文件
+    Other,
+}
+
+impl PathKind {
+    fn of(path: &Path) -> Self {
+        if path.is_dir() {
+            PathKind::Dir
+        } else if is_font_file(path) {
+            PathKind::Font
+        } else if is_sub_file(path) {
+            PathKind::Sub
+        } else {
+            PathKind::Other
+        }
+    }
+
+    fn icon(self) -> &'static str {
+        match self {
+            PathKind::Dir => "📁",
+            PathKind::Font => "🅰",
+            PathKind::Sub => "📄",
+            PathKind::Other => "❓",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            PathKind::Dir => "目录",
+            PathKind::Font => "字体文件",
+            PathKind::Sub => "字幕文件",
+            PathKind::Other => "其他文件",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize)]
+enum EntryStatus {
+    Loaded,
+    Duplicate,
+    Failed,
+    Missing,
+    /// 系统已安装，无需加载
+    System,
+    /// 与系统字体同名而未加载
+    Conflict,
+    /// 严格样式模式下，字幕需要的粗体/斜体变体没有文件提供（常规字形照常加载）
+    MissingVariant,
+}
+
+#[derive(Clone, Serialize)]
+struct FontEntry {
+    name: String,
+    path: Option<String>,
+    status: EntryStatus,
+    /// 引用该字体的字幕文件
+    sources: Vec<String>,
+    /// 匹配到的字体文件的各语言家族名
+    families: Vec<String>,
+    /// 同样匹配该字体名、但未被选用的其他文件
+    alternatives: Vec<String>,
+    /// 匹配文件中与系统已安装字体同名的家族名
+    conflicts: Vec<String>,
+    /// 字幕旁有字体目录、却选用了该目录树之外的文件时，记录被绕过的字体目录
+    local_dir: Option<String>,
+    /// 字幕用到、但匹配的文件都不提供的字形变体，如 "粗体"
+    missing_variants: Vec<String>,
+}
+
+/// 整理缓存的结果。
+struct CacheCompaction {
+    /// 文件已不存在或已改动而删除的条目
+    removed: usize,
+    kept: usize,
+    /// 整理前后缓存文件的字节数
+    before: u64,
+    after: u64,
 }
 
 #[derive(Clone, Serialize)]
 struct UnloadResult {
     count: usize,
+    /// 卸载后仍能枚举到的家族名（已排除系统安装的字体）
+    still_present: Vec<String>,
 }
 
 #[derive(Serialize, Deserialize, Default)]
 struct CacheFile {
     entries: HashMap<String, CacheEntry>,
+    /// 旧版把套用字体名规则后的名称写入缓存，并在这里记下规则的指纹；不为 0 时缓存的
+    /// 名称不是原名，整个缓存不可用。现在缓存只保存原名，规则在建立索引时套用
+    #[serde(default)]
+    name_rules: u64,
 }
 
 #[derive(Serialize, Deserialize)]
 struct CacheEntry {
     modified: u64,
     names: Vec<String>,
+    /// 各语言的主家族名，旧版缓存没有该字段
+    #[serde(default)]
+    families: Vec<String>,
+    /// 包含的字形变体位，见 [`variant_bit`]；旧版缓存为 0
+    #[serde(default)]
+    variants: u8,
+    /// 解析不出字体名时的原因；文件未变化前不再重新解析
+    #[serde(default)]
+    invalid: Option<String>,
+    /// 只作为 PostScript 名出现的名称，见 [`FontNames::ps_names`]；旧版缓存为 `None`
+    #[serde(default)]
+    ps_names: Option<Vec<String>>,
+    /// 写入条目的时间（Unix 秒），用于 [`Config::cache_ttl_days`]；旧版缓存为 0，
+    /// 设置了有效期时视为已过期
+    #[serde(default)]
+    cached_at: u64,
+    /// 是否带有 Graphite 的 `SILf` 表，见 [`FontNames::graphite`]；旧版缓存为 `None`
+    #[serde(default)]
+    graphite_capable: Option<bool>,
+    /// 是否只有 CFF/CFF2 轮廓，见 [`FontNames::cff`]；旧版缓存为 `None`
+    #[serde(default)]
+    cff_outlines: Option<bool>,
+}
+
+/// 从 `name` 表解析出的字体名。
+#[derive(Default)]
+struct FontNames {
+    /// 家族名与全名，用于匹配字幕中的字体名；系统区域的名称在前，其次美式英语
+    names: Vec<String>,
+    /// 各语言的主家族名 (ID 1)，系统区域的在前，其次英文，用于界面显示
+    families: Vec<String>,
+    /// 文件（TTC 为全部成员）包含的字形变体位，见 [`variant_bit`]
+    variants: u8,
+    /// 文件无法读取或没有可用字体名时的原因
+    invalid: Option<String>,
+    /// `names` 中只作为 PostScript 名 (ID 6) 出现、与家族名和全名都不同的名称
+    ps_names: Vec<String>,
+    /// 文件（TTC 为任一成员）带有 `SILf` 表，即 Graphite 字体。GDI 照常加载，但只有
+    /// 支持 Graphite 的渲染器才会用到其中的复杂文字排版规则
+    graphite: bool,
+    /// 文件（TTC 为任一成员）带有 `CFF ` 或 `CFF2` 表而没有 `glyf` 表，部分 Windows
+    /// 版本的 GDI 无法注册或注册后没有可用字形
+    cff: bool,
+}
+
+/// 字体文件的版本信息，用于区分同名的不同文件，见 [`read_font_meta`]。
+#[derive(Clone, Default)]
+struct FontMeta {
+    /// `name` 表的版本字符串 (ID 5)
+    version: Option<String>,
+    /// `name` 表的厂商名 (ID 8)
+    manufacturer: Option<String>,
+    /// `head.fontRevision`，16.16 定点数
+    revision: Option<f64>,
+}
+
+impl FontMeta {
+    /// 形如 "Version 2.004 · Adobe · 修订 2.004"，都没有时为空字符串。
+    fn summary(&self) -> String {
+        let mut parts: Vec<String> = Vec::new();
+        parts.extend(self.version.clone());
+        parts.extend(self.manufacturer.clone());
+        if let Some(revision) = self.revision {
+            parts.push(format!("修订 {:.3}", revision));
+        }
+        parts.join(" · ")
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct RestoreManifest {
+    version: u32,
+    fonts: Vec<ManifestFont>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct ManifestFont {
+    path: String,
+    #[serde(default)]
+    names: Vec<String>,
+}
+
+struct ScanOutput {
+    sub_files: Vec<PathBuf>,
+    font_files: Vec<PathBuf>,
+    required_fonts: HashSet<String>,
+    /// 字体名到引用它的字幕文件
+    font_sources: HashMap<String, Vec<String>>,
+    unsupported_subs: Vec<String>,
+    empty_subs: Vec<String>,
+    /// 样式中带有旧式 `*` 前缀的字体名（已去掉前缀）
+    starred_fonts: HashSet<String>,
+    /// 字幕中各字体用到的字形变体位
+    required_variants: HashMap<String, u8>,
+    /// 看起来使用复杂文字的字幕，见 [`AssFonts::complex_script`]
+    complex_subs: HashSet<String>,
+    index: FontIndex,
+    /// 按字体目录约定自动包含的目录及其新增的字体文件数
+    font_dirs: Vec<(PathBuf, usize)>,
+    /// 输入中的快捷方式及其目标，无法解析时为 `None`
+    shortcuts: Vec<(PathBuf, Option<PathBuf>)>,
+    /// 被合并或去重的重复输入与文件数，见 [`collect_files`]
+    redundant: usize,
+    /// 因 [`LoadOptions::excluded`] 跳过的字体文件数
+    excluded: usize,
+    /// 字幕和字体两侧被 [`LoadOptions::name_rules`] 改写过的名称
+    rule_hits: BTreeSet<(String, String)>,
+    /// 读取缓存时因超过 [`LoadOptions::cache_ttl_days`] 丢弃的条目数
+    cache_expired: usize,
+}
+
+/// [`build_font_index`] 的结果。
+#[derive(Default)]
+struct FontIndex {
+    /// 字体名的查找键 -> 文件路径列表
+    names: HashMap<NameKey, Vec<PathBuf>>,
+    /// 保留原始大小写的字体名 -> 文件路径列表
+    exact: HashMap<String, Vec<PathBuf>>,
+    /// 文件 -> 各语言家族名
+    families: HashMap<PathBuf, Vec<String>>,
+    /// 文件 -> 包含的字形变体位
+    variants: HashMap<PathBuf, u8>,
+    /// 文件 -> 只作为 PostScript 名 (ID 6) 出现、与家族名和全名都不同的名称
+    ps_names: HashMap<PathBuf, Vec<String>>,
+    /// 带有 `SILf` 表的 Graphite 字体文件
+    graphite: HashSet<PathBuf>,
+    /// 只有 CFF/CFF2 轮廓的字体文件
+    cff: HashSet<PathBuf>,
+    /// `names` 中所有查找键，排好序供 [`prefix_lookup`] 二分查找
+    sorted_keys: Vec<String>,
+    /// 解析不出字体名的文件及原因，包括从缓存得知的
+    invalid: Vec<(PathBuf, String)>,
+    /// 建立索引时被字体名规则改写过的名称 `(原名, 改写后)`
+    rule_hits: BTreeSet<(String, String)>,
+    cache_stats: CacheStats,
+    types: FontTypeStats,
+    source: FontSource,
+}
+
+impl FontIndex {
+    /// 把 `other` 中仍然存在、且不在 `excluded` 中的文件并入本索引，已有的路径排在
+    /// 前面。返回新增的文件数。
+    fn merge(&mut self, other: &FontIndex, excluded: &HashSet<PathKey>) -> usize {
+        let added: HashSet<&PathBuf> = other
+            .families
+            .keys()
+            .filter(|path| {
+                !self.families.contains_key(*path)
+                    && path.exists()
+                    && !excluded.contains(&PathKey::new(&path.to_string_lossy()))
+            })
+            .collect();
+        merge_name_map(&mut self.names, &other.names, &added);
+        merge_name_map(&mut self.exact, &other.exact, &added);
+        for path in &added {
+            let path = (*path).clone();
+            if let Some(families) = other.families.get(&path) {
+                self.families.insert(path.clone(), families.clone());
+            }
+            if let Some(bits) = other.variants.get(&path) {
+                self.variants.insert(path.clone(), *bits);
+            }
+            if other.graphite.contains(&path) {
+                self.graphite.insert(path.clone());
+            }
+            if other.cff.contains(&path) {
+                self.cff.insert(path.clone());
+            }
+            if let Some(ps_names) = other.ps_names.get(&path) {
+                self.ps_names.insert(path, ps_names.clone());
+            }
+        }
+        self.sorted_keys = sorted_name_keys(&self.names);
+        added.len()
+    }
+}
+
+/// 取出 `names` 的全部查找键并排序。
+fn sorted_name_keys(names: &HashMap<NameKey, Vec<PathBuf>>) -> Vec<String> {
+    let mut keys: Vec<String> = names.keys().map(|key| key.as_str().to_string()).collect();
+    keys.sort_unstable();
+    keys
+}
+
+/// 在排好序的 `sorted_keys` 中二分查找所有以 `query` 开头的键。`query` 应已经过
+/// [`NameKey::new`] 折叠，空查询不匹配任何键。
+///
+/// # Example
+///
+/// ```ignore
+/// let keys = vec!["arial".to_string(), "arial narrow".to_string(), "consolas".to_string()];
+/// assert_eq!(prefix_lookup("arial n", &keys), vec!["arial narrow"]);
+/// ```
+fn prefix_lookup(query: &str, sorted_keys: &[String]) -> Vec<String> {
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let start = sorted_keys.partition_point(|key| key.as_str() < query);
+    sorted_keys[start..]
+        .iter()
+        .take_while(|key| key.starts_with(query))
+        .cloned()
+        .collect()
+}
+
+/// 把 `other` 中属于 `added` 的路径追加到 `map` 的同名条目后。
+fn merge_name_map<K: Clone + Eq + std::hash::Hash>(
+    map: &mut HashMap<K, Vec<PathBuf>>,
+    other: &HashMap<K, Vec<PathBuf>>,
+    added: &HashSet<&PathBuf>,
+) {
+    for (name, paths) in other {
+        let new: Vec<PathBuf> = paths
+            .iter()
+            .filter(|path| added.contains(path))
+            .cloned()
+            .collect();
+        if !new.is_empty() {
+            map.entry(name.clone()).or_default().extend(new);
+        }
+    }
+}
+
+/// 已加载字体表的键，由 [`normalize_path_key`] 生成：不区分大小写，统一使用 `\\`
+/// 分隔。
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+struct PathKey(String);
+
+impl PathKey {
+    fn new(path: &str) -> Self {
+        PathKey(normalize_path_key(path))
+    }
+}
+
+/// 不区分大小写查找字体名时使用的键。字幕一侧和字体文件一侧的名称都只通过
+/// [`NameKey::new`] 生成键，新的规范化规则只需加在这里，两侧就同时生效。
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+struct NameKey(String);
+
+impl NameKey {
+    /// 去掉不可见字符（NUL、零宽字符、BOM）和首尾空白，全角 ASCII 字符和全角空格
+    /// 折叠为半角，再转为小写。
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// assert_eq!(NameKey::new(" Ｍｓ Gothic\u{200B}"), NameKey::new("ms gothic"));
+    /// ```
+    fn new(name: &str) -> Self {
+        let folded: String = name
+            .chars()
+            .filter_map(|c| match c {
+                '\0' | '\u{200B}'..='\u{200D}' | '\u{2060}' | '\u{FEFF}' => None,
+                '\u{3000}' => Some(' '),
+                '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0),
+                _ => Some(c),
+            })
+            .collect();
+        Self(folded.trim().to_lowercase())
+    }
+
+    fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// 索引中字体文件的来源。
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+enum FontSource {
+    /// 用户拖入或选择的文件
+    #[default]
+    User,
+    /// 注册表登记的系统字体，已由系统加载，不再通过 GDI 注册
+    System,
+}
+
+/// 一次建立索引时各类型字体文件的数量，按扩展名区分，不含无效字体。
+#[derive(Clone, Copy, Default, Serialize)]
+struct FontTypeStats {
+    ttf: usize,
+    otf: usize,
+    ttc: usize,
+    /// TTC 文件头部声明的成员字体总数
+    ttc_faces: usize,
+}
+
+impl FontTypeStats {
+    fn count(&mut self, path: &Path) {
+        let ext = path
+            .extension()
+            .and_then(|v| v.to_str())
+            .map(|v| v.to_lowercase());
+        match ext.as_deref() {
+            Some("ttf") => self.ttf += 1,
+            Some("otf") => self.otf += 1,
+            Some("ttc") => {
+                self.ttc += 1;
+                self.ttc_faces += ttc_member_count(path).unwrap_or(0);
+            }
+            _ => {}
+        }
+    }
+
+    /// 形如 "TTF 120 / OTF 30 / TTC 4 (含 18 个字体)"，没有字体文件时返回 `None`。
+    fn summary(&self) -> Option<String> {
+        if self.ttf + self.otf + self.ttc == 0 {
+            return None;
+        }
+        let mut text = format!("TTF {} / OTF {} / TTC {}", self.ttf, self.otf, self.ttc);
+        if self.ttc > 0 {
+            text.push_str(&format!(" (含 {} 个字体)", self.ttc_faces));
+        }
+        Some(text)
+    }
+}
+
+/// 一次建立索引时缓存的使用情况，不使用缓存时全为 0。
+#[derive(Clone, Copy, Default, Serialize)]
+struct CacheStats {
+    /// 修改时间一致、直接复用的条目
+    hits: usize,
+    /// 有条目但已失效、重新解析的文件
+    stale: usize,
+    /// 缓存中没有、首次解析的文件
+    added: usize,
+    /// 缓存记录为无效字体且未变化、直接跳过的文件，不计入 `hits`
+    invalid: usize,
+}
+
+impl CacheStats {
+    fn add(&mut self, other: CacheStats) {
+        self.hits += other.hits;
+        self.stale += other.stale;
+        self.added += other.added;
+        self.invalid += other.invalid;
+    }
+
+    /// 形如 "缓存命中 3200 / 新解析 40 (失效 2) / 已知无效 5"，没有用到缓存时返回
+    /// `None`。
+    fn summary(&self) -> Option<String> {
+        let parsed = self.stale + self.added;
+        if self.hits + parsed + self.invalid == 0 {
+            return None;
+        }
+        let mut text = format!("缓存命中 {} / 新解析 {}", self.hits, parsed);
+        if self.stale > 0 {
+            text.push_str(&format!(" (失效 {})", self.stale));
+        }
+        if self.invalid > 0 {
+            text.push_str(&format!(" / 已知无效 {}", self.invalid));
+        }
+        Some(text)
+    }
+}
+
+/// 单个 ASS 字幕中引用的字体。
+#[derive(Default, Debug, PartialEq)]
+struct AssFonts {
+    fonts: HashSet<String>,
+    /// 样式 Fontname 带 `*` 前缀的字体，同时也在 `fonts` 中
+    starred: HashSet<String>,
+    /// 各字体在样式定义与正文中实际用到的字形变体位
+    variants: HashMap<String, u8>,
+    /// `[Script Info]` 的 `Language` 属于复杂文字语言，或正文中出现阿拉伯、叙利亚、
+    /// 它拿、缅甸、高棉、老挝文字，见 [`is_complex_script_language`]
+    complex_script: bool,
+}
+
+/// `Style:` 行中与字形变体有关的设置。
+#[derive(Clone)]
+struct StyleDef {
+    font: String,
+    bold: bool,
+    italic: bool,
+}
+
+/// 结果页右键"添加别名"对话框的状态。
+struct AliasEdit {
+    /// 字幕中的字体名
+    name: String,
+    /// 实际查找的字体名，留空表示删除别名
+    target: String,
+}
+
+/// "按字体名强制清理"对话框的状态。
+#[derive(Default)]
+struct CleanByName {
+    name: String,
+    /// 查找到的候选文件及是否勾选，`None` 表示尚未查找
+    candidates: Option<Vec<(String, bool)>>,
+}
+
+/// "用 Everything 查找缺失字体"对话框的状态。
+struct EverythingSearch {
+    missing: Vec<FontEntry>,
+    /// 搜到的候选文件，`None` 表示仍在搜索
+    hits: Option<Result<Vec<EverythingHit>, String>>,
+}
+
+/// Everything 按文件名搜到的一个候选文件，用户勾选后才会加载。
+struct EverythingHit {
+    /// 据以搜到该文件的缺失字体名
+    font: String,
+    path: String,
+    checked: bool,
+}
+
+/// "单字体匹配测试"对话框的状态。
+#[derive(Default)]
+struct MatchTest {
+    sub: Option<PathBuf>,
+    font: Option<PathBuf>,
+    /// 打开对话框时的字体名规则
+    rules: NameRules,
+    /// 两个文件都选好后的结果
+    report: Option<Result<MatchReport, String>>,
+}
+
+/// 一个字体文件能否满足一个字幕的字体需求，见 [`match_font_to_sub`]。
+struct MatchReport {
+    /// 字幕需要的字体名，按名称排序
+    rows: Vec<MatchRow>,
+    /// 字体文件中用于匹配的全部名称
+    font_names: Vec<String>,
+    families: Vec<String>,
+    ps_names: Vec<String>,
+    /// 字体文件提供的字形变体位
+    variants: u8,
+}
+
+struct MatchRow {
+    name: String,
+    starred: bool,
+    outcome: MatchOutcome,
+    /// 字幕用到、但该文件不提供的变体，仅在匹配成功时检查
+    missing_variants: Vec<&'static str>,
+}
+
+enum MatchOutcome {
+    /// 与字体中的某个名称完全一致
+    Exact(String),
+    /// 只在忽略大小写、全角和零宽字符后一致
+    Normalized(String),
+    /// 只匹配到 PostScript 名
+    PostScript(String),
+    None,
+}
+
+#[derive(Clone)]
+struct DiffPreview {
+    missing: Vec<String>,
+    unused: Vec<String>,
+}
+
+/// 字幕所需字体在纯净系统上的覆盖情况，见 [`system_coverage_worker`]。
+#[derive(Clone)]
+struct SystemCoverage {
+    /// 系统已安装、无需打包的字体 `(字幕字体名, 系统字体名)`
+    covered: Vec<(String, String)>,
+    /// 需要随字幕打包的字体及拖入文件中匹配到的文件，找不到时为 `None`
+    bundle: Vec<(String, Option<String>)>,
+}
+
+enum Job {
+    Process {
+        paths: Vec<String>,
+        use_cache: bool,
+        /// 加载完成后用播放器打开的文件，见 [`PlayerSession`]
+        launch: Option<PathBuf>,
+    },
+    Preview {
+        paths: Vec<String>,
+        use_cache: bool,
+    },
+    /// 检查字幕所需字体有哪些已由系统安装、哪些需要打包
+    SystemCoverage {
+        paths: Vec<String>,
+        use_cache: bool,
+    },
+    Restore(Vec<ManifestFont>),
+    ResolveMissing {
+        folder: PathBuf,
+        missing: Vec<FontEntry>,
+        use_cache: bool,
+    },
+    /// 只在用户确认的文件中查找并加载缺失的字体
+    ResolveMissingFiles {
+        files: Vec<PathBuf>,
+        missing: Vec<FontEntry>,
+        use_cache: bool,
+    },
+    /// 通过 Everything 按文件名搜索缺失字体的候选文件
+    EverythingSearch {
+        names: Vec<String>,
+        port: u16,
+    },
+    Unload(Option<Vec<String>>),
+    Undo(LoadBatch),
+    Clean(PathBuf),
+    /// 查找解析出的字体名与给定名称一致的文件，供按字体名清理
+    FindFontFiles {
+        name: String,
+        /// 除缓存和会话索引外另行扫描的目录
+        dirs: Vec<PathBuf>,
+        use_cache: bool,
+    },
+    /// 强制卸载指定文件的全部注册
+    CleanFiles(Vec<String>),
+    /// 把字体文件打包为 ZIP
+    Pack {
+        dest: PathBuf,
+        /// 字体文件路径及其匹配名
+        fonts: Vec<(String, Vec<String>)>,
+        subs: Vec<String>,
+    },
+    /// 索引注册表登记的系统字体
+    IndexSystem {
+        use_cache: bool,
+    },
+    /// 删除缓存中已失效的条目
+    CompactCache,
+    /// 下载拖入的字幕和字体链接到临时目录，完成后加入待处理列表
+    Download(Vec<String>),
+}
+
+impl Job {
+    fn label(&self) -> String {
+        match self {
+            Job::Process {
+                launch: Some(file), ..
+            } => format!("用播放器打开 {}", file.to_string_lossy()),
+            Job::Process { paths, .. } => format!("加载 {} 个路径", paths.len()),
+            Job::Preview { paths, .. } => format!("预览差异 {} 个路径", paths.len()),
+            Job::SystemCoverage { paths, .. } => format!("系统字体覆盖检查 {} 个路径", paths.len()),
+            Job::Restore(fonts) => format!("恢复 {} 个字体", fonts.len()),
+            Job::ResolveMissing { folder, .. } => {
+                format!("查找缺失字体 {}", folder.to_string_lossy())
+            }
+            Job::ResolveMissingFiles { files, .. } => {
+                format!("从 {} 个文件中查找缺失字体", files.len())
+            }
+            Job::EverythingSearch { names, .. } => {
+                format!("用 Everything 查找 {} 个缺失字体", names.len())
+            }
+            Job::Unload(None) => "卸载全部字体".to_string(),
+            Job::Unload(Some(paths)) => format!("卸载 {} 个字体", paths.len()),
+            Job::Undo(batch) => format!("撤销加载 {} 个字体", batch.loaded_paths.len()),
+            Job::Clean(folder) => format!("强力清理 {}", folder.to_string_lossy()),
+            Job::FindFontFiles { name, .. } => format!("查找字体文件 {}", name),
+            Job::CleanFiles(paths) => format!("强力清理 {} 个文件", paths.len()),
+            Job::Pack { fonts, .. } => format!("打包 {} 个字体", fonts.len()),
+            Job::IndexSystem { .. } => "加载系统字体库".to_string(),
+            Job::CompactCache => "整理缓存".to_string(),
+            Job::Download(urls) => format!("下载 {} 个链接", urls.len()),
+        }
+    }
+}
+
+/// 文件对话框的选择结果回到界面线程后的去向，见 [`FontLoaderApp::open_dialog`]。
+enum DialogPurpose {
+    AddFiles,
+    AddFolder,
+    CleanFolder,
+    PickPlayer,
+    PickUiFont,
+    PickStructuredLog,
+    PickAliasMap,
+    /// 先选播放器，选好后接着选要打开的文件
+    PickPlayerThenOpen,
+    OpenWithPlayer,
+    ExportManifest,
+    ImportManifest,
+    ExportConfig,
+    ImportConfig,
+    FindMissing(Vec<FontEntry>),
+    ExportMissingReport(Vec<FontEntry>),
+    ExportHtmlReport,
+    MatchTestSub,
+    MatchTestFont,
+    Pack {
+        fonts: Vec<(String, Vec<String>)>,
+        subs: Vec<String>,
+    },
+}
+
+#[derive(Clone, Copy)]
+enum DialogAction {
+    PickFile,
+    PickFiles,
+    PickFolder,
+    SaveFile,
+}
+
+/// 对话框线程发回的选择，取消时 `paths` 为空。
+struct DialogResult {
+    purpose: DialogPurpose,
+    paths: Vec<PathBuf>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ProgressPhase {
+    Index,
+    Load,
+    Download,
 }
 
 enum WorkerResult {
+    Progress(ProgressPhase, usize, usize),
     Process(Result<ProcessResult, String>),
+    Preview(Result<DiffPreview, String>),
+    SystemCoverage(Result<SystemCoverage, String>),
+    Resolve(Result<ProcessResult, String>),
+    /// 每个候选文件 `(缺失字体名, 路径)`
+    EverythingSearch(Result<Vec<(String, String)>, String>),
     Unload(Result<UnloadResult, String>),
+    Undo(Result<UnloadResult, String>),
     Clean(Result<UnloadResult, String>),
+    FindFontFiles(Result<Vec<String>, String>),
+    /// 每个文件及释放的引用数
+    CleanFiles(Result<Vec<(String, usize)>, String>),
+    Pack(Result<PathBuf, String>),
+    SystemIndex(Result<FontIndex, String>),
+    CompactCache(Result<CacheCompaction, String>),
+    /// 每个链接及保存的临时文件
+    Download(Vec<(String, Result<DownloadedFile, String>)>),
 }
 
-#[derive(PartialEq, Eq, Clone, Copy)]
+#[derive(PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
 enum Tab {
     Operate,
+    Results,
+    Loaded,
     Logs,
+    Settings,
+}
+
+enum RowAction {
+    OpenFolder(String),
+    CopyNames(Vec<String>),
+    CopyPaths(Vec<String>),
+    Unload(Vec<String>),
+    /// 为字体名设置别名，打开 [`AliasEdit`] 对话框
+    AddAlias(String),
+    /// 把文件加入 [`Config::excluded_fonts`]
+    Exclude(Vec<String>),
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+enum Theme {
+    Dark,
+    Light,
+    System,
+    HighContrast,
+}
+
+impl Theme {
+    const ALL: [Theme; 4] = [
+        Theme::Dark,
+        Theme::Light,
+        Theme::System,
+        Theme::HighContrast,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            Theme::Dark => "暗色",
+            Theme::Light => "亮色",
+            Theme::System => "跟随系统",
+            Theme::HighContrast => "高对比度",
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct Config {
+    theme: Theme,
+    /// 注册字体面数达到该值时显示警告
+    face_warn_threshold: usize,
+    always_on_top: bool,
+    /// 卸载后检查字体是否真的从系统中移除
+    verify_unload: bool,
+    /// 字体的注册方式；`Private` 只有本程序可见，用于检查匹配而不影响其他程序
+    load_mode: LoadMode,
+    /// 取消当前任务时一并清空排队的任务，否则继续执行下一个
+    cancel_clears_queue: bool,
+    /// 优先匹配大小写完全一致的字体名
+    case_sensitive: bool,
+    /// 不加载与系统已安装字体同名的字体文件
+    skip_system_conflicts: bool,
+    /// 字幕需要的粗体/斜体变体缺失时不算作成功加载
+    strict_style: bool,
+    /// 发布包中存放字体的子目录名，处理时总是一并扫描（不区分大小写）
+    font_folders: Vec<String>,
+    /// 字体文件被占用等暂时性错误时的重试次数
+    load_retries: usize,
+    /// 每条日志最多列出的候选文件或冲突字体数，其余只给出数量
+    max_logged_candidates: usize,
+    /// 在结果中显示匹配文件的版本、厂商和修订号
+    show_font_meta: bool,
+    /// 检测到播放器进程启动时自动加载其打开的目录
+    auto_load: bool,
+    /// 自动加载关注的进程名（不区分大小写）
+    auto_load_processes: Vec<String>,
+    /// 播放器程序路径，为空时首次使用时询问
+    player_path: String,
+    /// 播放器参数模板，按空格分隔，`%f` 替换为要打开的文件
+    player_args: String,
+    /// 界面字体文件，为空时按 [`UI_FONT_CANDIDATES`] 自动选择
+    ui_font_path: String,
+    /// 在 127.0.0.1 上提供 HTTP 控制接口，见 [`serve_http`]
+    http_enabled: bool,
+    http_port: u16,
+    /// 非空时请求必须带上同值的 `X-Fontloader-Token` 头
+    http_token: String,
+    /// 下载从浏览器拖入的字幕和字体链接，关闭时拖入的链接只记录后忽略
+    download_dropped_urls: bool,
+    /// 可以通过 Everything (voidtools) 的 HTTP 服务器按文件名查找缺失字体
+    everything_enabled: bool,
+    everything_port: u16,
+    /// 启动时显示的标签页，退出时记为当时所在的标签页
+    initial_tab: Tab,
+    /// 有缺失字体时监视输入目录，放入能补上的字体文件后自动重新查找
+    auto_retry_missing: bool,
+    /// 每批次自动重新查找的次数上限
+    auto_retry_limit: usize,
+    /// 打包字体时跳过 `standard_fonts` 中的字体和与系统已安装字体同名的字体
+    pack_skip_standard: bool,
+    /// Windows 自带的常用字体名（不区分大小写），打包时可以跳过
+    standard_fonts: Vec<String>,
+    /// 字体名改写规则，字幕和字体两侧的名称都按顺序套用，见 [`NameRules`]
+    name_rules: Vec<NameRule>,
+    /// 字体别名：字幕中的字体名 -> 实际查找的字体名，见 [`LoadOptions::aliases`]
+    font_aliases: BTreeMap<String, String>,
+    /// 不参与匹配的字体文件，见 [`LoadOptions::excluded`]
+    excluded_fonts: Vec<String>,
+    /// 字体名缓存条目的有效天数，过期的条目读取缓存时丢弃；0 表示不过期
+    cache_ttl_days: u64,
+    /// 配置文件在程序外被修改时自动重载
+    watch_config: bool,
+    /// 非空时每条日志产生后立即以 JSON 对象追加到该文件，每行一条 (JSON Lines)，
+    /// 见 [`LogEntry`]
+    structured_log_path: Option<PathBuf>,
+    /// 扫描输入目录时进入子目录的层数上限，`None` 不限，见 [`collect_files`]
+    max_depth: Option<usize>,
+    /// 别名表 JSON 文件，每次处理时读入并与 `font_aliases` 合并，见 [`load_alias_map`]
+    alias_map_path: Option<PathBuf>,
+}
+
+/// Windows 各版本都自带的常用字体，中英文名各列一次。
+const DEFAULT_STANDARD_FONTS: [&str; 30] = [
+    "Arial",
+    "Times New Roman",
+    "Courier New",
+    "Verdana",
+    "Tahoma",
+    "Georgia",
+    "Trebuchet MS",
+    "Comic Sans MS",
+    "Impact",
+    "Segoe UI",
+    "Calibri",
+    "Cambria",
+    "Consolas",
+    "Microsoft YaHei",
+    "微软雅黑",
+    "SimSun",
+    "宋体",
+    "NSimSun",
+    "新宋体",
+    "SimHei",
+    "黑体",
+    "KaiTi",
+    "楷体",
+    "FangSong",
+    "仿宋",
+    "Microsoft JhengHei",
+    "微软正黑体",
+    "MS Gothic",
+    "Meiryo",
+    "Malgun Gothic",
+];
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            theme: Theme::Dark,
+            face_warn_threshold: 1000,
+            always_on_top: false,
+            verify_unload: false,
+            load_mode: LoadMode::Public,
+            cancel_clears_queue: false,
+            case_sensitive: false,
+            skip_system_conflicts: false,
+            strict_style: false,
+            font_folders: ["Fonts", "字体", "attachments"].map(String::from).to_vec(),
+            load_retries: 3,
+            max_logged_candidates: 5,
+            show_font_meta: false,
+            auto_load: false,
+            auto_load_processes: ["mpv.exe", "PotPlayerMini64.exe", "PotPlayerMini.exe"]
+                .map(String::from)
+                .to_vec(),
+            player_path: String::new(),
+            player_args: "%f".to_string(),
+            ui_font_path: String::new(),
+            http_enabled: false,
+            http_port: 27123,
+            http_token: String::new(),
+            download_dropped_urls: false,
+            everything_enabled: false,
+            everything_port: 80,
+            initial_tab: Tab::Operate,
+            auto_retry_missing: false,
+            auto_retry_limit: 5,
+            pack_skip_standard: false,
+            standard_fonts: DEFAULT_STANDARD_FONTS.map(String::from).to_vec(),
+            name_rules: Vec::new(),
+            font_aliases: BTreeMap::new(),
+            excluded_fonts: Vec::new(),
+            cache_ttl_days: 0,
+            watch_config: false,
+            structured_log_path: None,
+            max_depth: None,
+            alias_map_path: None,
+        }
+    }
+}
+
+/// 结构化日志中的一条，由日志行按开头的 `[标签]` 拆分而来。
+#[derive(Serialize)]
+struct LogEntry {
+    /// 标签，`[X]`、`[i]` 等符号标签换成 `error`、`info` 等单词；没有标签时为 `info`
+    level: String,
+    message: String,
+    /// 该行写入时距程序启动的毫秒数，见 [`log_clock_ms`]
+    elapsed_ms: u64,
+}
+
+impl LogEntry {
+    fn from_line(line: &str, elapsed_ms: u64) -> Self {
+        let (tag, message) = line
+            .strip_prefix('[')
+            .and_then(|rest| rest.split_once(']'))
+            .map_or(("i", line), |(tag, message)| (tag, message.trim_start()));
+        let level = match tag {
+            "X" => "error",
+            "i" => "info",
+            "??" => "missing",
+            "^" => "duplicate",
+            tag => tag,
+        };
+        Self {
+            level: level.to_lowercase(),
+            message: message.to_string(),
+            elapsed_ms,
+        }
+    }
+}
+
+/// 自程序启动起的毫秒数，日志行写入时用它记下时间。
+fn log_clock_ms() -> u64 {
+    static STARTED: OnceLock<Instant> = OnceLock::new();
+    STARTED.get_or_init(Instant::now).elapsed().as_millis() as u64
+}
+
+/// 日志行及各行写入时的时间。工作线程产生的行合并到界面日志后仍保留原来的时间，
+/// 写入结构化日志时用作 [`LogEntry::elapsed_ms`]。序列化时只输出文本。
+#[derive(Clone, Default)]
+struct LogLines {
+    lines: Vec<String>,
+    /// 与 `lines` 一一对应，见 [`log_clock_ms`]
+    times: Vec<u64>,
+}
+
+impl LogLines {
+    fn push(&mut self, line: String) {
+        self.lines.push(line);
+        self.times.push(log_clock_ms());
+    }
+
+    /// 移入 `other` 的全部行，保留各行原来的时间。
+    fn append(&mut self, other: &mut LogLines) {
+        self.lines.append(&mut other.lines);
+        self.times.append(&mut other.times);
+    }
+
+    fn times(&self) -> &[u64] {
+        &self.times
+    }
+}
+
+impl std::ops::Deref for LogLines {
+    type Target = [String];
+
+    fn deref(&self) -> &[String] {
+        &self.lines
+    }
+}
+
+impl<'a> IntoIterator for &'a LogLines {
+    type Item = &'a String;
+    type IntoIter = std::slice::Iter<'a, String>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.lines.iter()
+    }
+}
+
+impl Extend<String> for LogLines {
+    fn extend<I: IntoIterator<Item = String>>(&mut self, lines: I) {
+        for line in lines {
+            self.push(line);
+        }
+    }
+}
+
+impl FromIterator<String> for LogLines {
+    fn from_iter<I: IntoIterator<Item = String>>(lines: I) -> Self {
+        let mut logs = Self::default();
+        logs.extend(lines);
+        logs
+    }
+}
+
+impl Serialize for LogLines {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(&self.lines)
+    }
+}
+
+/// 把 `lines` 转成 [`LogEntry`] 逐行追加到 `path`，文件不存在时创建。`times` 与
+/// `lines` 一一对应。
+fn append_structured_log(path: &Path, lines: &[String], times: &[u64]) -> Result<(), String> {
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| e.to_string())?;
+    let mut writer = std::io::BufWriter::new(file);
+    for (line, &elapsed_ms) in lines.iter().zip(times) {
+        serde_json::to_writer(&mut writer, &LogEntry::from_line(line, elapsed_ms))
+            .map_err(|e| e.to_string())?;
+        writer.write_all(b"\n").map_err(|e| e.to_string())?;
+    }
+    writer.flush().map_err(|e| e.to_string())
+}
+
+/// 增量均值，只保存和与样本数。
+#[derive(Default)]
+struct RunningMean {
+    sum: f64,
+    count: u64,
+}
+
+impl RunningMean {
+    fn push(&mut self, value: f64) {
+        self.sum += value;
+        self.count += 1;
+    }
+
+    fn mean(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.sum / self.count as f64)
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct SessionState {
+    registered_faces: usize,
+    /// 退出时为仍在运行的播放器保留、未卸载的字体文件
+    kept_fonts: Vec<KeptFont>,
+    /// 退出时工作线程没能及时停下、来不及卸载的字体文件，下次启动时卸载
+    orphaned_fonts: Vec<KeptFont>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct KeptFont {
+    path: String,
+    faces: usize,
+}
+
+/// [`Config::cache_ttl_days`] 的上限
+const MAX_CACHE_TTL_DAYS: u64 = 3650;
+
+/// 任务进行中关闭窗口时，最多等工作线程这么久再退出
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 对配置文件的监视，丢弃即停止监视。
+struct ConfigWatch {
+    rx: mpsc::Receiver<()>,
+    _watcher: notify::RecommendedWatcher,
+    /// 最近一次改动的时间，重载后清空
+    changed_at: Option<Instant>,
+}
+
+/// 配置文件停止变化这么久后才重载，编辑器保存时可能分几次写入
+const CONFIG_WATCH_SETTLE: Duration = Duration::from_millis(500);
+
+/// 加载后仍有缺失字体时对输入目录的监视，丢弃即停止监视。
+struct MissingWatch {
+    rx: mpsc::Receiver<MissingFontAppeared>,
+    _watcher: notify::RecommendedWatcher,
+    /// 出现了新字体、等待重新查找的监视目录
+    pending: Vec<PathBuf>,
+    last_event: Instant,
+    /// 已自动重新查找的次数
+    retries: usize,
+}
+
+/// 监视目录中出现了提供某个缺失字体的文件。
+struct MissingFontAppeared {
+    /// 文件所在的监视目录
+    dir: PathBuf,
+    file: PathBuf,
+    name: String,
+}
+
+/// 最后一次监视到新字体文件后，等这么久没有新文件再重新查找，以免文件还没复制完
+const MISSING_WATCH_SETTLE: Duration = Duration::from_secs(1);
+
+/// 导出配置的格式版本。[`Config`] 增删字段不需要改动，缺少的字段导入时取默认值；
+/// 字段含义变化、旧文件需要转换时递增。
+const CONFIG_BUNDLE_VERSION: u32 = 1;
+
+/// "导出配置"写出的文件，只含设置，不含缓存和会话状态。
+#[derive(Serialize)]
+struct ConfigBundle<'a> {
+    version: u32,
+    config: &'a Config,
+}
+
+/// 等待确认的配置导入。
+struct ConfigImport {
+    path: PathBuf,
+    config: Config,
+    /// 与当前设置不同的项 `(设置项, 当前值, 导入值)`
+    changes: Vec<(String, String, String)>,
+    /// 导入的设置中在本机不存在的路径
+    missing_paths: Vec<String>,
+}
+
+/// 一次"用播放器打开"：播放器退出后卸载该会话加载的字体。
+struct PlayerSession {
+    id: u64,
+    file: PathBuf,
+    pid: u32,
+    /// 本会话新注册的文件，播放器退出时卸载（仍被其他会话使用的除外）
+    owned: Vec<String>,
+    /// 本会话用到的全部文件，包括加载前已注册的
+    used: Vec<String>,
 }
 
 #[derive(PartialEq, Eq, Clone, Copy)]
@@ -66,22 +1321,175 @@ enum Mode {
 struct FontLoaderApp {
     tab: Tab,
     mode: Mode,
-    logs: Vec<String>,
+    logs: LogLines,
     state: Arc<Mutex<AppState>>,
     busy: bool,
     worker_rx: Option<mpsc::Receiver<WorkerResult>>,
     last_summary: Option<ProcessResult>,
-    dark_mode: bool,
-    pending_paths: Vec<String>,
+    config: Config,
+    pending_paths: Vec<PendingEntry>,
+    loaded_view: BTreeMap<String, LoadedFont>,
+    loaded_filter: String,
+    apply_to_filtered: bool,
+    registered_faces: usize,
+    session_success_rate: RunningMean,
+    selected_results: HashSet<usize>,
+    selected_loaded: HashSet<String>,
+    diff_preview: Option<DiffPreview>,
+    system_coverage: Option<SystemCoverage>,
+    clean_by_name: Option<CleanByName>,
+    alias_edit: Option<AliasEdit>,
+    match_test: Option<MatchTest>,
+    everything_search: Option<EverythingSearch>,
+    /// 结果页显示过的字体文件版本信息，读取失败时为 `None`
+    font_meta: HashMap<String, Option<FontMeta>>,
+    cancel: Arc<AtomicBool>,
+    progress: Option<(ProgressPhase, usize, usize)>,
+    taskbar: TaskbarProgress,
+    repaint: RepaintSignal,
+    job_queue: VecDeque<Job>,
+    /// 最近一次完成的加载批次，可撤销
+    undo_batch: Option<LoadBatch>,
+    jobs_done: usize,
+    /// 处理过的输入目录，已加载列表据此显示相对路径
+    processed_dirs: Vec<PathBuf>,
+    /// 当前工作线程，退出时等待其结束后再清理
+    worker: Option<thread::JoinHandle<()>>,
+    /// 从注册表导入的系统字体索引，匹配到的字体视为已安装而不加载
+    system_index: Option<Arc<FontIndex>>,
+    /// 系统字体库的显示行 `(字体名, 路径)`，按字体名排序
+    system_library: Vec<(String, String)>,
+    /// 显示"重置所有设置"的确认对话框
+    confirm_reset: bool,
+    config_import: Option<ConfigImport>,
+    /// 打开中的文件对话框发回选择的通道，同一时间只允许一个对话框
+    dialog_rx: Option<mpsc::Receiver<DialogResult>>,
+    /// 本进程的 GDI 对象数 `(当前, 峰值)`，读取不到时为 `None`
+    gdi_usage: Option<(u32, u32)>,
+    gdi_checked_at: Option<Instant>,
+    /// 设置页中字体目录名的编辑内容，逗号分隔
+    font_folders_text: String,
+    /// 设置页中标准字体名的编辑内容，逗号分隔
+    standard_fonts_text: String,
+    /// 设置页中字体名改写规则的编辑内容，每行一条
+    name_rules_text: String,
+    /// 由 [`Config::name_rules`] 编译，设置修改后更新
+    name_rules: NameRules,
+    /// 设置页中字体别名的编辑内容，每行一条
+    font_aliases_text: String,
+    /// 设置页中排除的字体文件的编辑内容，每行一个
+    excluded_fonts_text: String,
+    /// 设置页中自动加载进程名的编辑内容，逗号分隔
+    auto_load_text: String,
+    /// 操作页中手动输入的路径，每行一个
+    path_input: String,
+    /// 上次添加手动输入的路径时的错误，对应仍留在输入框中的行
+    path_input_error: Option<String>,
+    /// 进程监视线程发回新启动的播放器进程，未启用自动加载时为 `None`
+    process_rx: Option<mpsc::Receiver<StartedProcess>>,
+    /// 置位后进程监视线程退出
+    process_watch_stop: Arc<AtomicBool>,
+    /// HTTP 接口线程转来的请求，未启用时为 `None`
+    http_rx: Option<mpsc::Receiver<HttpRequest>>,
+    /// 置位后 HTTP 接口线程退出
+    http_stop: Arc<AtomicBool>,
+    /// HTTP 接口线程，换端口前等它释放监听的端口
+    http_thread: Option<thread::JoinHandle<()>>,
+    /// 各目录上次自动加载的时间，用于节流
+    auto_loaded_at: HashMap<PathBuf, Instant>,
+    /// 正在执行的加载任务的输入目录，完成后有缺失字体时据此开始监视
+    pending_watch_dirs: Option<Vec<PathBuf>>,
+    missing_watch: Option<MissingWatch>,
+    config_watch: Option<ConfigWatch>,
+    /// `logs` 中已交给结构化日志的条数，之后的是新产生的
+    structured_log_written: usize,
+    /// 写入失败的结构化日志文件，换用其他文件前不再尝试
+    structured_log_failed: Option<PathBuf>,
+    /// 当前加载任务完成后要用播放器打开的文件
+    pending_launch: Option<PathBuf>,
+    /// 界面字体，渲染测试文字时在其基础上追加被测字体
+    font_defs: egui::FontDefinitions,
+    /// 已加载页的测试文字
+    sample_text: String,
+    /// 正在预览的字体家族，对应 `FontFamily::Name`
+    sample_family: Option<String>,
+    /// 本次会话各次处理累积的字体索引，后续处理可匹配之前拖入的字体
+    session_font_index: Arc<FontIndex>,
+    /// 仍在运行的播放器会话
+    players: Vec<PlayerSession>,
+    next_player_id: u64,
+    /// 等待播放器退出的线程发回会话 ID
+    player_tx: mpsc::Sender<u64>,
+    player_rx: mpsc::Receiver<u64>,
+    /// 有播放器在运行时关闭窗口，先询问是否保留其字体
+    confirm_quit: bool,
+    /// 用户已确认退出；`keep_player_fonts` 为真时退出时不卸载播放器用到的字体
+    quit_confirmed: bool,
+    keep_player_fonts: bool,
+    /// 任务进行中关闭窗口时开始停止的时间，任务结束或超时后再关闭
+    stopping: Option<Instant>,
+    /// 当前任务注册成功的字体，工作线程持有状态锁时退出也能知道注册了哪些
+    registrations: Arc<Mutex<Vec<KeptFont>>>,
 }
 
 impl FontLoaderApp {
     fn new(cc: &eframe::CreationContext<'_>) -> Self {
-        setup_custom_fonts(&cc.egui_ctx);
-        cc.egui_ctx.set_visuals(egui::Visuals::dark());
+        let config = load_config();
+        let (font_defs, font_logs) = setup_custom_fonts(&cc.egui_ctx, &config.ui_font_path);
+        let font_folders_text = config.font_folders.join(", ");
+        let standard_fonts_text = config.standard_fonts.join(", ");
+        let name_rules_text = format_name_rules(&config.name_rules);
+        let font_aliases_text = format_font_aliases(&config.font_aliases);
+        let excluded_fonts_text = config.excluded_fonts.join("\n");
+        let name_rules = NameRules::new(&config.name_rules);
+        let auto_load_text = config.auto_load_processes.join(", ");
+        apply_theme(&cc.egui_ctx, config.theme);
+        apply_window_level(&cc.egui_ctx, config.always_on_top);
+        let session = load_session();
+        let mut logs: LogLines = font_logs.into_iter().collect();
+        if session.registered_faces > 0 {
+            logs.push(format!(
+                "[i] 上次会话未正常退出，可能仍有 {} 个字体面处于注册状态",
+                session.registered_faces
+            ));
+        }
+        // 上次为播放器保留的字体仍处于注册状态，列入已加载以便之后卸载
+        let (loaded, released) = restore_kept_fonts(&session.kept_fonts, &|path| {
+            remove_font_resource(path, LoadMode::Public)
+        });
+        if released > 0 {
+            broadcast_font_change();
+            logs.push(format!(
+                "[i] 上次保留的字体中有 {} 个文件以不同大小写重复注册，已释放多余的注册",
+                released
+            ));
+        }
+        if !loaded.is_empty() {
+            logs.push(format!(
+                "[i] 上次退出时为播放器保留了 {} 个字体文件，已列入已加载",
+                loaded.len()
+            ));
+        }
+        let (unloaded, failed) = unload_orphaned_fonts(&session.orphaned_fonts, &|path| {
+            remove_font_resource(path, LoadMode::Public)
+        });
+        if unloaded > 0 {
+            broadcast_font_change();
+            logs.push(format!(
+                "[i] 已卸载上次退出时未能及时卸载的 {} 个字体文件",
+                unloaded
+            ));
+        }
+        if !failed.is_empty() {
+            logs.push(format!(
+                "[X] 上次退出时未能及时卸载的字体中有 {} 个文件仍无法卸载: {}",
+                failed.len(),
+                failed.join(", ")
+            ));
+        }
+        let (player_tx, player_rx) = mpsc::channel();
 
-        let mut style = (*cc.egui_ctx.style()).clone();
-        style.text_styles = [
+        let text_styles: BTreeMap<egui::TextStyle, egui::FontId> = [
             (
                 egui::TextStyle::Heading,
                 egui::FontId::new(28.0, egui::FontFamily::Proportional),
@@ -104,26 +1512,177 @@ impl FontLoaderApp {
             ),
         ]
         .into();
-        cc.egui_ctx.set_style(style);
+        // 亮/暗两套样式都要设置字号，切换主题时才不会回到默认大小
+        cc.egui_ctx
+            .all_styles_mut(|style| style.text_styles = text_styles.clone());
 
-        Self {
-            tab: Tab::Operate,
+        let mut app = Self {
+            tab: config.initial_tab,
             mode: Mode::NoResidue,
-            logs: Vec::new(),
-            state: Arc::new(Mutex::new(AppState::default())),
+            logs,
+            state: Arc::new(Mutex::new(AppState {
+                loaded,
+                residual_faces: session.registered_faces,
+            })),
             busy: false,
             worker_rx: None,
             last_summary: None,
-            dark_mode: true,
+            config,
             pending_paths: Vec::new(),
-        }
+            loaded_view: BTreeMap::new(),
+            loaded_filter: String::new(),
+            apply_to_filtered: false,
+            registered_faces: session.registered_faces,
+            session_success_rate: RunningMean::default(),
+            selected_results: HashSet::new(),
+            selected_loaded: HashSet::new(),
+            diff_preview: None,
+            system_coverage: None,
+            clean_by_name: None,
+            alias_edit: None,
+            match_test: None,
+            everything_search: None,
+            font_meta: HashMap::new(),
+            cancel: Arc::new(AtomicBool::new(false)),
+            progress: None,
+            taskbar: TaskbarProgress::default(),
+            repaint: RepaintSignal(cc.egui_ctx.clone()),
+            job_queue: VecDeque::new(),
+            undo_batch: None,
+            jobs_done: 0,
+            processed_dirs: Vec::new(),
+            worker: None,
+            system_index: None,
+            system_library: Vec::new(),
+            confirm_reset: false,
+            config_import: None,
+            dialog_rx: None,
+            gdi_usage: None,
+            gdi_checked_at: None,
+            font_folders_text,
+            standard_fonts_text,
+            name_rules_text,
+            name_rules,
+            font_aliases_text,
+            excluded_fonts_text,
+            auto_load_text,
+            path_input: String::new(),
+            path_input_error: None,
+            process_rx: None,
+            process_watch_stop: Arc::new(AtomicBool::new(false)),
+            http_rx: None,
+            http_stop: Arc::new(AtomicBool::new(false)),
+            http_thread: None,
+            auto_loaded_at: HashMap::new(),
+            pending_watch_dirs: None,
+            missing_watch: None,
+            config_watch: None,
+            structured_log_written: 0,
+            structured_log_failed: None,
+            pending_launch: None,
+            font_defs,
+            sample_text: "汉字 ABC 123 テスト".to_string(),
+            sample_family: None,
+            session_font_index: Arc::default(),
+            players: Vec::new(),
+            next_player_id: 0,
+            player_tx,
+            player_rx,
+            confirm_quit: false,
+            stopping: None,
+            registrations: Arc::new(Mutex::new(Vec::new())),
+            quit_confirmed: false,
+            keep_player_fonts: false,
+        };
+        app.restart_process_watcher();
+        app.restart_http_server();
+        app.restart_config_watch();
+        app
     }
 
-    fn append_logs(&mut self, items: impl IntoIterator<Item = String>) {
-        for item in items {
-            self.logs.push(item);
-        }
-    }
+    /// 把上次之后新增的日志追加到 [`Config::structured_log_path`]，每帧结束时调用。
+    fn flush_structured_log(&mut self) {
+        let start = self.structured_log_written.min(self.logs.len());
+        self.structured_log_written = self.logs.len();
+        let Some(path) = self.config.structured_log_path.clone() else {
+            return;
+        };
+        if start == self.logs.len() || self.structured_log_failed.as_ref() == Some(&path) {
+            return;
+        }
+        let times = &self.logs.times()[start..];
+        if let Err(err) = append_structured_log(&path, &self.logs[start..], times) {
+            self.logs.push(format!(
+                "[X] 写入结构化日志失败，已停止写入: {}: {}",
+                path.to_string_lossy(),
+                err
+            ));
+            self.structured_log_failed = Some(path);
+        }
+    }
+
+    fn set_structured_log(&mut self, path: Option<PathBuf>) {
+        match &path {
+            Some(path) => self
+                .logs
+                .push(format!("[i] 结构化日志写入: {}", path.to_string_lossy())),
+            None => self.logs.push("[i] 已停止写入结构化日志".to_string()),
+        }
+        self.config.structured_log_path = path;
+        self.structured_log_failed = None;
+        if let Err(err) = save_config(&self.config) {
+            self.logs.push(format!("[X] 保存设置失败: {}", err));
+        }
+    }
+
+    /// 按 [`Config::name_rules`] 重新编译规则。之前各次处理累积的索引是按旧规则建立的，
+    /// 一并丢弃。
+    fn set_name_rules(&mut self) {
+        self.name_rules = NameRules::new(&self.config.name_rules);
+        self.session_font_index = Arc::default();
+    }
+
+    fn set_alias_map(&mut self, path: Option<PathBuf>) {
+        match &path {
+            Some(path) => self
+                .logs
+                .push(format!("[i] 别名表: {}", path.to_string_lossy())),
+            None => self.logs.push("[i] 已停用别名表文件".to_string()),
+        }
+        self.config.alias_map_path = path;
+        if let Err(err) = save_config(&self.config) {
+            self.logs.push(format!("[X] 保存设置失败: {}", err));
+        }
+    }
+
+    fn append_logs(&mut self, mut items: LogLines) {
+        self.logs.append(&mut items);
+    }
+
+    /// 下载指向字幕或字体文件的 HTTP/HTTPS 链接，其余链接记录后忽略。未启用
+    /// [`Config::download_dropped_urls`] 时全部忽略。
+    fn handle_dropped_urls(&mut self, uris: Vec<String>) {
+        if !self.config.download_dropped_urls {
+            for uri in uris {
+                self.logs
+                    .push(format!("[i] 忽略拖入的链接（可在设置中启用下载）: {}", uri));
+            }
+            return;
+        }
+        let mut urls = Vec::new();
+        for uri in uris {
+            if is_download_url(&uri) {
+                self.logs.push(format!("[download] Downloading: {}", uri));
+                urls.push(uri);
+            } else {
+                self.logs
+                    .push(format!("[i] 忽略不是字幕或字体文件的链接: {}", uri));
+            }
+        }
+        if !urls.is_empty() {
+            self.submit_job(Job::Download(urls));
+        }
+    }
 
     fn enqueue_paths(&mut self, paths: Vec<PathBuf>) {
         let paths: Vec<String> = paths
@@ -135,844 +1694,9564 @@ impl FontLoaderApp {
         }
         let mut added = 0;
         for path in paths {
-            if !self.pending_paths.contains(&path) {
-                self.pending_paths.push(path);
-                added += 1;
+            if self.pending_paths.iter().any(|p| p.path == path) {
+                continue;
+            }
+            if let Some(ancestor) = self
+                .pending_paths
+                .iter()
+                .find(|p| p.kind == PathKind::Dir && is_descendant_path(&path, &p.path))
+                .map(|p| &p.path)
+            {
+                self.logs
+                    .push(format!("[i] 已包含在待处理目录中: {} ⊂ {}", path, ancestor));
+                continue;
+            }
+            if Path::new(&path).is_dir() {
+                let before = self.pending_paths.len();
+                self.pending_paths
+                    .retain(|p| !is_descendant_path(&p.path, &path));
+                let collapsed = before - self.pending_paths.len();
+                if collapsed > 0 {
+                    self.logs
+                        .push(format!("[i] 合并 {} 个子路径到: {}", collapsed, path));
+                }
             }
+            self.pending_paths.push(PendingEntry::new(path));
+            added += 1;
         }
         if added > 0 {
             self.logs.push(format!("[i] 已加入待处理: {}", added));
         }
     }
 
-    fn handle_process_pending(&mut self) {
-        if self.busy {
-            self.logs.push("[i] 正在处理，请稍候".to_string());
-            return;
+    /// 把输入框中的路径加入待处理，去掉首尾空白和资源管理器“复制文件地址”带的引号。
+    /// 不存在的路径留在输入框中并记下错误，其余清空。
+    fn handle_add_typed_paths(&mut self) {
+        let mut valid = Vec::new();
+        let mut rejected = Vec::new();
+        for line in self.path_input.lines() {
+            let line = line.trim().trim_matches('"').trim();
+            if line.is_empty() {
+                continue;
+            }
+            if Path::new(line).exists() {
+                valid.push(PathBuf::from(line));
+            } else {
+                rejected.push(line.to_string());
+            }
         }
+        self.path_input_error = (!rejected.is_empty())
+            .then(|| format!("{} 个路径不存在，已保留在输入框中", rejected.len()));
+        for path in &rejected {
+            self.logs.push(format!("[X] 路径不存在: {}", path));
+        }
+        self.path_input = rejected.join("\n");
+        self.enqueue_paths(valid);
+    }
+
+    fn pending_path_strings(&self) -> Vec<String> {
+        self.pending_paths.iter().map(|p| p.path.clone()).collect()
+    }
+
+    fn handle_process_pending(&mut self) {
         if self.pending_paths.is_empty() {
             self.logs.push("[i] 没有待处理的路径".to_string());
             return;
         }
-        let paths = std::mem::take(&mut self.pending_paths);
+        let paths = self.pending_path_strings();
+        self.pending_paths.clear();
         let use_cache = self.mode == Mode::Normal;
-        let state = self.state.clone();
-        let (tx, rx) = mpsc::channel();
-        self.worker_rx = Some(rx);
-        self.busy = true;
-        thread::spawn(move || {
-            let result = process_drop_worker(paths, use_cache, state);
-            let _ = tx.send(WorkerResult::Process(result));
+        self.submit_job(Job::Process {
+            paths,
+            use_cache,
+            launch: None,
         });
     }
 
-    fn handle_unload(&mut self) {
-        if self.busy {
-            self.logs.push("[i] 正在处理，请稍候".to_string());
+    fn handle_preview_diff(&mut self) {
+        if self.pending_paths.is_empty() {
+            self.logs.push("[i] 没有待处理的路径".to_string());
             return;
         }
-        let state = self.state.clone();
-        let (tx, rx) = mpsc::channel();
-        self.worker_rx = Some(rx);
-        self.busy = true;
-        thread::spawn(move || {
-            let result = unload_fonts_worker(state);
-            let _ = tx.send(WorkerResult::Unload(result));
-        });
+        let paths = self.pending_path_strings();
+        let use_cache = self.mode == Mode::Normal;
+        self.submit_job(Job::Preview { paths, use_cache });
     }
 
-    fn handle_clean(&mut self, folder: PathBuf) {
-        if self.busy {
-            self.logs.push("[i] 正在处理，请稍候".to_string());
+    fn handle_system_coverage(&mut self) {
+        if self.pending_paths.is_empty() {
+            self.logs.push("[i] 没有待处理的路径".to_string());
             return;
         }
-        let folder_str = folder.to_string_lossy().to_string();
-        self.logs
-            .push(format!("[i] 正在强力清理目录: {}", folder_str));
-        let (tx, rx) = mpsc::channel();
-        self.worker_rx = Some(rx);
-        self.busy = true;
-        thread::spawn(move || {
-            let result = clean_folder_worker(folder);
-            let _ = tx.send(WorkerResult::Clean(result));
-        });
+        let paths = self.pending_path_strings();
+        let use_cache = self.mode == Mode::Normal;
+        self.submit_job(Job::SystemCoverage { paths, use_cache });
     }
 
-    fn poll_worker(&mut self) {
-        let Some(rx) = self.worker_rx.take() else {
-            return;
-        };
-        let mut finished = false;
-        while let Ok(msg) = rx.try_recv() {
-            match msg {
-                WorkerResult::Process(result) => {
-                    self.busy = false;
-                    finished = true;
-                    match result {
-                        Ok(res) => {
-                            let summary = format!(
-                                "完成: 字幕{} 字体{} 已载入{} 失败{} 缺失{} 重复{}",
-                                res.subs, res.fonts, res.loaded, res.failed, res.missing, res.duplicates
-                            );
-                            self.append_logs(res.logs.clone());
-                            self.logs.push(summary);
-                            self.last_summary = Some(res);
-                        }
-                        Err(err) => {
-                            self.logs.push(format!("[X] {}", err));
-                        }
-                    }
-                }
-                WorkerResult::Unload(result) => {
-                    self.busy = false;
-                    finished = true;
-                    match result {
-                        Ok(res) => {
-                            self.logs.push(format!("卸载完成: {}", res.count));
-                            self.last_summary = Some(ProcessResult {
-                                loaded: 0,
-                                failed: 0,
-                                missing: 0,
-                                duplicates: 0,
-                                subs: 0,
-                                fonts: 0,
-                                logs: Vec::new(),
-                            });
-                        }
-                        Err(err) => {
-                            self.logs.push(format!("[X] {}", err));
-                        }
-                    }
-                }
-                WorkerResult::Clean(result) => {
-                    self.busy = false;
-                    finished = true;
-                    match result {
-                        Ok(res) => {
-                            self.logs.push(format!("强力清理完成，释放了 {} 个字体引用", res.count));
-                        }
-                        Err(err) => {
-                            self.logs.push(format!("[X] {}", err));
-                        }
-                    }
-                }
-            }
-        }
-        if finished {
-            self.worker_rx = None;
-        } else {
-            self.worker_rx = Some(rx);
-        }
+    fn reset_cancel(&mut self) -> Arc<AtomicBool> {
+        self.cancel.store(false, Ordering::Relaxed);
+        self.progress = None;
+        self.cancel.clone()
     }
-}
 
-impl eframe::App for FontLoaderApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        self.poll_worker();
-        let dropped = ctx.input(|i| i.raw.dropped_files.clone());
-        if !dropped.is_empty() {
-            let paths: Vec<PathBuf> = dropped.into_iter().filter_map(|f| f.path).collect();
-            if !paths.is_empty() {
-                self.enqueue_paths(paths);
-            }
+    fn handle_cancel(&mut self) {
+        if self.busy && !self.cancel.swap(true, Ordering::Relaxed) {
+            self.logs.push("[i] 正在取消...".to_string());
         }
+    }
 
-        egui::TopBottomPanel::top("top_bar").show(ctx, |ui| {
-            ui.horizontal(|ui| {
-                let operate = ui.selectable_label(self.tab == Tab::Operate, "操作");
-                if operate.clicked() {
-                    self.tab = Tab::Operate;
-                }
-                let logs = ui.selectable_label(self.tab == Tab::Logs, "日志");
-                if logs.clicked() {
-                    self.tab = Tab::Logs;
-                }
-                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                    let mut dark = self.dark_mode;
-                    if ui.checkbox(&mut dark, "暗色").changed() {
-                        self.dark_mode = dark;
-                        if dark {
-                            ctx.set_visuals(egui::Visuals::dark());
-                        } else {
-                            ctx.set_visuals(egui::Visuals::light());
-                        }
-                    }
-                });
-            });
-        });
+    fn handle_unload(&mut self) {
+        self.submit_job(Job::Unload(None));
+    }
 
-        egui::CentralPanel::default().show(ctx, |ui| match self.tab {
-            Tab::Operate => {
-                ui.vertical(|ui| {
-                    ui.label("将字幕/字体文件或文件夹拖入窗口，加入待处理后再点击开始处理");
-                    ui.add_space(4.0);
-                    
-                    let available_width = ui.available_width();
-                    let spacing = ui.spacing().item_spacing.x;
-                    let row_height = 35.0;
-
-                    // 第一行：选文件，选文件夹
-                    ui.horizontal(|ui| {
-                        let btn_w = (available_width - spacing) / 2.0;
-                        if ui.add_sized([btn_w, row_height], egui::Button::new("选文件")).clicked() {
-                            if let Some(files) = rfd::FileDialog::new().pick_files() {
-                                self.enqueue_paths(files);
-                            }
-                        }
-                        if ui.add_sized([btn_w, row_height], egui::Button::new("选文件夹")).clicked() {
-                            if let Some(folder) = rfd::FileDialog::new().pick_folder() {
-                                self.enqueue_paths(vec![folder]);
-                            }
-                        }
-                    });
+    fn handle_unload_paths(&mut self, paths: Vec<String>) {
+        self.submit_job(Job::Unload(Some(paths)));
+    }
 
-                    ui.add_space(4.0);
+    fn handle_clean(&mut self, folder: PathBuf) {
+        self.submit_job(Job::Clean(folder));
+    }
 
-                    // 第二行：开始处理（加载），卸载
-                    ui.horizontal(|ui| {
-                        let btn_w = (available_width - spacing) / 2.0;
-                        if ui.add_sized([btn_w, row_height], egui::Button::new("加载字体")).clicked() {
-                            self.handle_process_pending();
-                        }
-                        if ui.add_sized([btn_w, row_height], egui::Button::new("卸载已加载字体")).clicked() {
-                            self.handle_unload();
-                        }
-                    });
+    /// 选择视频或字幕，加载所需字体后用播放器打开。视频取同目录下文件名以视频名开头
+    /// 的字幕，没有时处理整个目录。
+    fn handle_open_with_player(&mut self) {
+        if self.config.player_path.is_empty() {
+            self.open_dialog(
+                player_dialog(),
+                DialogAction::PickFile,
+                DialogPurpose::PickPlayerThenOpen,
+            );
+            return;
+        }
+        self.open_dialog(
+            rfd::FileDialog::new().add_filter("视频/字幕", &PLAYER_FILE_EXTENSIONS),
+            DialogAction::PickFile,
+            DialogPurpose::OpenWithPlayer,
+        );
+    }
 
-                    ui.add_space(4.0);
+    fn open_with_player(&mut self, file: PathBuf) {
+        let paths = if is_sub_file(&file) {
+            vec![file.to_string_lossy().to_string()]
+        } else {
+            companion_subtitles(&file)
+        };
+        let use_cache = self.mode == Mode::Normal;
+        self.submit_job(Job::Process {
+            paths,
+            use_cache,
+            launch: Some(file),
+        });
+    }
 
-                    // 第三行：强制清理
-                    if ui
-                        .add_sized([available_width, row_height], egui::Button::new("⚠强制清理目录残留"))
-                        .on_hover_text("选择一个文件夹，尝试强制卸载其中所有字体文件的系统占用（无论是否由本程序加载）")
-                        .clicked()
-                    {
-                        if let Some(folder) = rfd::FileDialog::new().pick_folder() {
-                            self.handle_clean(folder);
-                        }
-                    }
+    /// 把选好的播放器程序保存到设置。
+    fn set_player(&mut self, path: PathBuf) {
+        self.config.player_path = path.to_string_lossy().to_string();
+        if let Err(err) = save_config(&self.config) {
+            self.logs.push(format!("[X] 保存设置失败: {}", err));
+        }
+    }
 
-                    ui.add_space(8.0);
+    /// 检查并改用用户选择的界面字体，不可用时保留原设置。
+    fn set_ui_font(&mut self, path: PathBuf) {
+        if let Err(err) = read_ui_font(&path) {
+            self.logs.push(format!(
+                "[X] 不能用作界面字体: {}: {}",
+                path.to_string_lossy(),
+                err
+            ));
+            return;
+        }
+        self.config.ui_font_path = path.to_string_lossy().to_string();
+        if let Err(err) = save_config(&self.config) {
+            self.logs.push(format!("[X] 保存设置失败: {}", err));
+        }
+        self.apply_ui_font();
+    }
 
-                    ui.horizontal(|ui| {
-                        let mut mode = self.mode;
-                        ui.label("模式:");
-                        if ui.radio_value(&mut mode, Mode::NoResidue, "无残留").clicked() {
-                            self.mode = Mode::NoResidue;
-                        }
-                        if ui.radio_value(&mut mode, Mode::Normal, "普通").clicked() {
-                            self.mode = Mode::Normal;
-                        }
-                    });
+    /// 按设置重建界面字体。正在显示的测试文字所用字体随之移除。
+    fn apply_ui_font(&mut self) {
+        let ctx = self.repaint.0.clone();
+        let (font_defs, logs) = setup_custom_fonts(&ctx, &self.config.ui_font_path);
+        self.font_defs = font_defs;
+        self.sample_family = None;
+        self.logs.extend(logs);
+    }
 
-                    ui.label(format!("待处理路径: {}", self.pending_paths.len()));
-                    if let Some(summary) = &self.last_summary {
-                        ui.label(format!(
-                            "摘要: 字幕{} 字体{} 已载入{} 失败{} 缺失{} 重复{}",
-                            summary.subs,
-                            summary.fonts,
-                            summary.loaded,
-                            summary.failed,
-                            summary.missing,
-                            summary.duplicates
-                        ));
-                    }
+    /// 在辅助线程上打开文件对话框，界面照常刷新；选择结果由 [`Self::poll_dialog`]
+    /// 取回。已有对话框打开时忽略新的请求。
+    fn open_dialog(
+        &mut self,
+        dialog: rfd::FileDialog,
+        action: DialogAction,
+        purpose: DialogPurpose,
+    ) {
+        if self.dialog_rx.is_some() {
+            self.logs
+                .push("[i] 已有文件对话框打开，请先完成选择".to_string());
+            return;
+        }
+        let (tx, rx) = mpsc::channel();
+        self.dialog_rx = Some(rx);
+        let repaint = self.repaint.clone();
+        thread::spawn(move || {
+            let paths = match action {
+                DialogAction::PickFile => dialog.pick_file().into_iter().collect(),
+                DialogAction::PickFiles => dialog.pick_files().unwrap_or_default(),
+                DialogAction::PickFolder => dialog.pick_folder().into_iter().collect(),
+                DialogAction::SaveFile => dialog.save_file().into_iter().collect(),
+            };
+            let _ = tx.send(DialogResult { purpose, paths });
+            repaint.request();
+        });
+    }
 
-                    if self.busy {
-                        ui.label("处理中...");
-                    }
-                });
+    fn poll_dialog(&mut self) {
+        let Some(rx) = &self.dialog_rx else {
+            return;
+        };
+        let result = match rx.try_recv() {
+            Ok(result) => result,
+            Err(mpsc::TryRecvError::Empty) => return,
+            Err(mpsc::TryRecvError::Disconnected) => {
+                self.dialog_rx = None;
+                return;
             }
-            Tab::Logs => {
-                egui::ScrollArea::vertical().auto_shrink([false; 2]).show(ui, |ui| {
-                    for line in &self.logs {
-                        ui.label(line);
-                    }
-                });
+        };
+        self.dialog_rx = None;
+        let paths = result.paths;
+        let Some(first) = paths.first().cloned() else {
+            return;
+        };
+        match result.purpose {
+            DialogPurpose::AddFiles => self.enqueue_paths(paths),
+            DialogPurpose::AddFolder => self.enqueue_paths(vec![first]),
+            DialogPurpose::CleanFolder => self.handle_clean(first),
+            DialogPurpose::PickPlayer => self.set_player(first),
+            DialogPurpose::PickUiFont => self.set_ui_font(first),
+            DialogPurpose::PickStructuredLog => self.set_structured_log(Some(first)),
+            DialogPurpose::PickAliasMap => self.set_alias_map(Some(first)),
+            DialogPurpose::PickPlayerThenOpen => {
+                self.set_player(first);
+                self.handle_open_with_player();
             }
-        });
-    }
-    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
-        if let Ok(state) = self.state.lock() {
-            let mut count = 0;
-            for path in state.loaded.iter() {
-                if remove_font_resource(path) {
-                    count += 1;
+            DialogPurpose::OpenWithPlayer => self.open_with_player(first),
+            DialogPurpose::ExportManifest => self.export_manifest_to(&first),
+            DialogPurpose::ImportManifest => self.import_manifest_from(&first),
+            DialogPurpose::ExportConfig => self.export_config_to(&first),
+            DialogPurpose::ImportConfig => self.import_config_from(first),
+            DialogPurpose::FindMissing(missing) => self.submit_job(Job::ResolveMissing {
+                folder: first,
+                missing,
+                use_cache: self.mode == Mode::Normal,
+            }),
+            DialogPurpose::ExportHtmlReport => {
+                let Some(summary) = &self.last_summary else {
+                    return;
+                };
+                match export_html_report(&first, summary) {
+                    Ok(()) => self
+                        .logs
+                        .push(format!("[i] 已导出报告: {}", first.to_string_lossy())),
+                    Err(err) => self.logs.push(format!("[X] 导出报告失败: {}", err)),
                 }
             }
-            if count > 0 {
-                broadcast_font_change();
+            DialogPurpose::ExportMissingReport(missing) => {
+                let missing: Vec<&FontEntry> = missing.iter().collect();
+                match export_missing_report(&first, &missing) {
+                    Ok(()) => self.logs.push(format!(
+                        "[i] 已导出缺失字体报告: {}",
+                        first.to_string_lossy()
+                    )),
+                    Err(err) => self.logs.push(format!("[X] 导出报告失败: {}", err)),
+                }
+            }
+            DialogPurpose::MatchTestSub => {
+                if let Some(test) = &mut self.match_test {
+                    test.sub = Some(first);
+                    test.refresh();
+                }
             }
+            DialogPurpose::MatchTestFont => {
+                if let Some(test) = &mut self.match_test {
+                    test.font = Some(first);
+                    test.refresh();
+                }
+            }
+            DialogPurpose::Pack { fonts, subs } => self.submit_job(Job::Pack {
+                dest: first,
+                fonts,
+                subs,
+            }),
         }
     }
-}
 
-fn process_drop_worker(
-    paths: Vec<String>,
-    use_cache: bool,
-    state: Arc<Mutex<AppState>>,
-) -> Result<ProcessResult, String> {
-    let file_list = collect_files(&paths)?;
-    let mut sub_files = Vec::new();
-    let mut font_files = Vec::new();
-    for path in file_list {
-        if is_sub_file(&path) {
-            sub_files.push(path);
-        } else if is_font_file(&path) {
-            font_files.push(path);
+    /// 加载完成后启动播放器，并在后台线程中等待其退出。
+    fn start_player_session(&mut self, file: PathBuf, res: &ProcessResult) {
+        let args = player_command_args(&self.config.player_args, &file);
+        let mut child = match std::process::Command::new(&self.config.player_path)
+            .args(&args)
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(err) => {
+                self.logs.push(format!(
+                    "[X] 启动播放器失败: {}: {}",
+                    self.config.player_path, err
+                ));
+                return;
+            }
+        };
+        let id = self.next_player_id;
+        self.next_player_id += 1;
+        let used: Vec<String> = res
+            .entries
+            .iter()
+            .filter(|e| {
+                matches!(
+                    e.status,
+                    EntryStatus::Loaded | EntryStatus::Duplicate | EntryStatus::MissingVariant
+                )
+            })
+            .filter_map(|e| e.path.clone())
+            .collect();
+        let session = PlayerSession {
+            id,
+            file,
+            pid: child.id(),
+            owned: res.batch.loaded_paths.clone(),
+            used,
+        };
+        self.logs.push(format!(
+            "[i] 已启动播放器 (PID {}): {}，退出后卸载本次加载的 {} 个字体文件",
+            session.pid,
+            session.file.to_string_lossy(),
+            session.owned.len()
+        ));
+        self.players.push(session);
+        let tx = self.player_tx.clone();
+        let repaint = self.repaint.clone();
+        thread::spawn(move || {
+            let _ = child.wait();
+            let _ = tx.send(id);
+            repaint.request();
+        });
+    }
+
+    /// 处理已退出的播放器：卸载其会话加载的字体，仍被其他会话使用的转交给那个会话。
+    fn poll_players(&mut self) {
+        while let Ok(id) = self.player_rx.try_recv() {
+            let Some(pos) = self.players.iter().position(|p| p.id == id) else {
+                continue;
+            };
+            let session = self.players.remove(pos);
+            let mut unload = Vec::new();
+            for path in session.owned {
+                match self.players.iter_mut().find(|p| p.used.contains(&path)) {
+                    Some(other) => other.owned.push(path),
+                    None => unload.push(path),
+                }
+            }
+            self.logs.push(format!(
+                "[i] 播放器已退出 (PID {}): {}",
+                session.pid,
+                session.file.to_string_lossy()
+            ));
+            if !unload.is_empty() {
+                self.submit_job(Job::Unload(Some(unload)));
+            }
         }
     }
 
-    let mut required_fonts = HashSet::new();
-    let mut unsupported_subs = Vec::new();
-    for sub in &sub_files {
-        if is_ass_file(sub) {
-            if let Some(text) = read_text(sub) {
-                for font in parse_ass_fonts(&text) {
-                    required_fonts.insert(font);
+    /// 工作线程没能及时停下时，它仍持有状态锁，无法安全地卸载。把界面已知的已加载
+    /// 字体和本次任务注册的字体都记入会话状态：`keep` 中为播放器保留的照常保留，
+    /// 其余下次启动时卸载，见 [`outstanding_session`]。
+    fn save_outstanding_registrations(&self, keep: &HashSet<PathKey>) {
+        let mut kept_fonts: Vec<KeptFont> = self
+            .loaded_view
+            .iter()
+            .map(|(path, font)| KeptFont {
+                path: path.clone(),
+                faces: font.faces,
+            })
+            .collect();
+        if let Ok(registrations) = self.registrations.lock() {
+            for font in registrations.iter() {
+                let key = PathKey::new(&font.path);
+                if !kept_fonts
+                    .iter()
+                    .any(|kept| PathKey::new(&kept.path) == key)
+                {
+                    kept_fonts.push(KeptFont {
+                        path: font.path.clone(),
+                        faces: font.faces,
+                    });
                 }
             }
-        } else {
-            unsupported_subs.push(sub.to_string_lossy().to_string());
         }
+        let _ = save_session(&outstanding_session(kept_fonts, keep));
     }
 
-    let mut cache = if use_cache {
-        load_cache_file()
+    fn ui_stopping(&mut self, ctx: &egui::Context) {
+        let Some(started) = self.stopping else {
+            return;
+        };
+        egui::Modal::new(egui::Id::new("stopping")).show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui_spinner(ui);
+                ui.label("正在停止...");
+            });
+            let left = SHUTDOWN_TIMEOUT.saturating_sub(started.elapsed());
+            ui.weak(format!(
+                "等待当前任务结束后卸载字体，最多再等 {} 秒",
+                left.as_secs() + 1
+            ));
+        });
+        ctx.request_repaint_after(Duration::from_millis(100));
+    }
+
+    fn ui_confirm_quit(&mut self, ctx: &egui::Context) {
+        if !self.confirm_quit {
+            return;
+        }
+        let mut choice = None;
+        let modal = egui::Modal::new(egui::Id::new("confirm_quit")).show(ctx, |ui| {
+            ui.heading("播放器仍在运行");
+            ui.label(format!(
+                "{} 个播放器仍在使用本程序加载的字体，卸载后字幕可能无法正常显示。",
+                self.players.len()
+            ));
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui
+                    .button("保留字体并退出")
+                    .on_hover_text("下次启动时这些字体会列入已加载，可随时卸载")
+                    .clicked()
+                {
+                    choice = Some(true);
+                }
+                if ui.button("卸载并退出").clicked() {
+                    choice = Some(false);
+                }
+                if ui.button("取消").clicked() {
+                    self.confirm_quit = false;
+                }
+            });
+        });
+        if let Some(keep) = choice {
+            self.confirm_quit = false;
+            self.quit_confirmed = true;
+            self.keep_player_fonts = keep;
+            ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+        } else if modal.should_close() {
+            self.confirm_quit = false;
+        }
+    }
+
+    /// 把字体文件加入界面字体，以其首个家族名注册为 `FontFamily::Name`，下一帧起可用于
+    /// 渲染测试文字。不追加界面字体作为备选，缺字时能直接看出来。
+    fn handle_render_sample(&mut self, ctx: &egui::Context, path: &str) {
+        let data = match fs::read(path) {
+            Ok(data) => data,
+            Err(err) => {
+                self.logs
+                    .push(format!("[X] 读取字体失败: {}: {}", path, err));
+                return;
+            }
+        };
+        // egui 遇到无法解析的字体数据会直接 panic，先自行检查
+        if let Err(err) = ab_glyph::FontRef::try_from_slice(&data) {
+            self.logs
+                .push(format!("[X] 该字体无法在界面中渲染: {}: {}", path, err));
+            return;
+        }
+        let family = self
+            .loaded_view
+            .get(path)
+            .and_then(|font| font.families.first().cloned())
+            .unwrap_or_else(|| {
+                Path::new(path)
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().to_string())
+                    .unwrap_or_else(|| path.to_string())
+            });
+        let mut fonts = self.font_defs.clone();
+        fonts.font_data.insert(
+            "sample".to_owned(),
+            Arc::new(egui::FontData::from_owned(data)),
+        );
+        fonts.families.insert(
+            egui::FontFamily::Name(Arc::from(family.as_str())),
+            vec!["sample".to_owned()],
+        );
+        ctx.set_fonts(fonts);
+        self.sample_family = Some(family);
+    }
+
+    /// 按设置重启进程监视线程；未启用自动加载时只停止旧线程。
+    fn restart_process_watcher(&mut self) {
+        self.process_watch_stop.store(true, Ordering::Relaxed);
+        self.process_rx = None;
+        if !self.config.auto_load || self.config.auto_load_processes.is_empty() {
+            return;
+        }
+        let stop = Arc::new(AtomicBool::new(false));
+        self.process_watch_stop = stop.clone();
+        let (tx, rx) = mpsc::channel();
+        self.process_rx = Some(rx);
+        let names = self.config.auto_load_processes.clone();
+        let repaint = self.repaint.clone();
+        thread::spawn(move || watch_processes(names, tx, repaint, stop));
+    }
+
+    fn poll_started_processes(&mut self) {
+        let Some(rx) = &self.process_rx else {
+            return;
+        };
+        let started: Vec<StartedProcess> = rx.try_iter().collect();
+        for process in started {
+            self.auto_load_for(process);
+        }
+    }
+
+    /// 按设置重新启动 HTTP 接口：先停掉旧线程并等它释放端口，未启用时只停不启。
+    fn restart_http_server(&mut self) {
+        self.http_stop.store(true, Ordering::Relaxed);
+        self.http_rx = None;
+        if let Some(handle) = self.http_thread.take() {
+            let _ = handle.join();
+        }
+        if !self.config.http_enabled {
+            return;
+        }
+        let port = self.config.http_port;
+        let listener = match TcpListener::bind((Ipv4Addr::LOCALHOST, port))
+            .and_then(|listener| listener.set_nonblocking(true).map(|()| listener))
+        {
+            Ok(listener) => listener,
+            Err(err) => {
+                self.logs
+                    .push(format!("[X] HTTP 接口无法监听 127.0.0.1:{}: {}", port, err));
+                return;
+            }
+        };
+        let stop = Arc::new(AtomicBool::new(false));
+        self.http_stop = stop.clone();
+        let (tx, rx) = mpsc::channel();
+        self.http_rx = Some(rx);
+        let token = self.config.http_token.clone();
+        let repaint = self.repaint.clone();
+        self.http_thread = Some(thread::spawn(move || {
+            serve_http(listener, token, tx, repaint, stop)
+        }));
+        self.logs
+            .push(format!("[i] HTTP 接口已在 127.0.0.1:{} 上监听", port));
+    }
+
+    /// 按设置开始或停止监视配置文件。监视的是程序目录，只转发配置文件的改动。
+    fn restart_config_watch(&mut self) {
+        self.config_watch = None;
+        if !self.config.watch_config {
+            return;
+        }
+        let Some(path) = config_file_path() else {
+            return;
+        };
+        let Some(dir) = path.parent().map(Path::to_path_buf) else {
+            return;
+        };
+        let (tx, rx) = mpsc::channel();
+        let repaint = self.repaint.clone();
+        let handler = move |event: notify::Result<notify::Event>| {
+            let Ok(event) = event else {
+                return;
+            };
+            if matches!(
+                event.kind,
+                notify::EventKind::Create(_) | notify::EventKind::Modify(_)
+            ) && event
+                .paths
+                .iter()
+                .any(|p| p.file_name() == path.file_name())
+                && tx.send(()).is_ok()
+            {
+                repaint.request();
+            }
+        };
+        let mut watcher = match notify::recommended_watcher(handler) {
+            Ok(watcher) => watcher,
+            Err(err) => {
+                self.logs.push(format!("[X] 无法监视配置文件: {}", err));
+                return;
+            }
+        };
+        if let Err(err) =
+            notify::Watcher::watch(&mut watcher, &dir, notify::RecursiveMode::NonRecursive)
+        {
+            self.logs.push(format!("[X] 无法监视配置文件: {}", err));
+            return;
+        }
+        self.config_watch = Some(ConfigWatch {
+            rx,
+            _watcher: watcher,
+            changed_at: None,
+        });
+    }
+
+    /// 配置文件改动后等它停止变化再重载。本程序自己保存设置也会触发，此时内容与
+    /// 当前设置一致，不产生日志。
+    fn poll_config_watch(&mut self) {
+        let Some(watch) = &mut self.config_watch else {
+            return;
+        };
+        if watch.rx.try_iter().count() > 0 {
+            watch.changed_at = Some(Instant::now());
+        }
+        let Some(changed_at) = watch.changed_at else {
+            return;
+        };
+        let settled = changed_at + CONFIG_WATCH_SETTLE;
+        if Instant::now() < settled {
+            self.repaint
+                .0
+                .request_repaint_after(settled - Instant::now());
+            return;
+        }
+        watch.changed_at = None;
+        self.reload_config(true);
+    }
+
+    /// 从磁盘重新读取配置文件并应用，记录改动的设置项。文件无法解析或设置项无效时
+    /// 保留当前设置；`quiet` 为真时没有改动不记录日志。
+    fn reload_config(&mut self, quiet: bool) {
+        let config = match read_config_file() {
+            Ok(config) => config,
+            Err(err) => {
+                self.logs
+                    .push(format!("[X] 重载配置失败，保留当前设置: {}", err));
+                return;
+            }
+        };
+        let changes = config_changes(&self.config, &config);
+        if changes.is_empty() {
+            if !quiet {
+                self.logs.push("[i] 配置文件与当前设置一致".to_string());
+            }
+            return;
+        }
+        for (key, before, after) in &changes {
+            self.logs
+                .push(format!("[config] {}: {} -> {}", key, before, after));
+        }
+        self.logs
+            .push(format!("[i] 已重载配置，{} 项改动", changes.len()));
+        self.apply_config(config);
+    }
+
+    /// 上一批次有缺失字体且启用了自动重试时，监视 `dirs` 中新出现的字体文件。
+    fn start_missing_watch(&mut self, dirs: Vec<PathBuf>) {
+        self.missing_watch = None;
+        if !self.config.auto_retry_missing || dirs.is_empty() {
+            return;
+        }
+        let missing: HashSet<NameKey> = self
+            .last_summary
+            .iter()
+            .flat_map(|summary| &summary.entries)
+            .filter(|e| e.status == EntryStatus::Missing)
+            .map(|e| NameKey::new(&e.name))
+            .collect();
+        if missing.is_empty() {
+            return;
+        }
+        let count = missing.len();
+        let (tx, rx) = mpsc::channel();
+        let repaint = self.repaint.clone();
+        let roots = dirs.clone();
+        let mut seen = HashSet::new();
+        let handler = move |event: notify::Result<notify::Event>| {
+            let Ok(event) = event else {
+                return;
+            };
+            if !matches!(
+                event.kind,
+                notify::EventKind::Create(_) | notify::EventKind::Modify(_)
+            ) {
+                return;
+            }
+            for file in event.paths {
+                if seen.contains(&file) || !is_font_file(&file) {
+                    continue;
+                }
+                // 复制到一半的文件解析不出名称，等之后的修改事件再试
+                let names = parse_font_names(&file).names;
+                let Some(name) = names
+                    .into_iter()
+                    .find(|name| missing.contains(&NameKey::new(name)))
+                else {
+                    continue;
+                };
+                let Some(dir) = roots.iter().find(|root| file.starts_with(root)) else {
+                    continue;
+                };
+                seen.insert(file.clone());
+                let appeared = MissingFontAppeared {
+                    dir: dir.clone(),
+                    file,
+                    name,
+                };
+                if tx.send(appeared).is_err() {
+                    return;
+                }
+                repaint.request();
+            }
+        };
+        let mut watcher = match notify::recommended_watcher(handler) {
+            Ok(watcher) => watcher,
+            Err(err) => {
+                self.logs.push(format!("[X] 无法监视目录: {}", err));
+                return;
+            }
+        };
+        let mut watched = Vec::new();
+        for dir in dirs {
+            match notify::Watcher::watch(&mut watcher, &dir, notify::RecursiveMode::Recursive) {
+                Ok(()) => watched.push(dir),
+                Err(err) => self.logs.push(format!(
+                    "[X] 无法监视目录 {}: {}",
+                    dir.to_string_lossy(),
+                    err
+                )),
+            }
+        }
+        if watched.is_empty() {
+            return;
+        }
+        self.logs.push(format!(
+            "[retry] 缺失 {} 个字体，正在监视 {} 个目录，放入字体文件后自动重新查找（最多 {} 次）",
+            count,
+            watched.len(),
+            self.config.auto_retry_limit
+        ));
+        self.missing_watch = Some(MissingWatch {
+            rx,
+            _watcher: watcher,
+            pending: Vec::new(),
+            last_event: Instant::now(),
+            retries: 0,
+        });
+    }
+
+    /// 收集监视到的新字体文件，文件停止变化一会儿且当前没有任务时，对其所在的监视
+    /// 目录重新查找缺失字体，每次只查一个目录，下一次用更新后的缺失列表。
+    fn poll_missing_watch(&mut self) {
+        let Some(watch) = &mut self.missing_watch else {
+            return;
+        };
+        for appeared in watch.rx.try_iter() {
+            self.logs.push(format!(
+                "[retry] 新文件 {} 提供缺失字体 {}",
+                appeared.file.to_string_lossy(),
+                appeared.name
+            ));
+            if !watch.pending.contains(&appeared.dir) {
+                watch.pending.push(appeared.dir);
+            }
+            watch.last_event = Instant::now();
+        }
+        if watch.pending.is_empty() || self.busy || !self.job_queue.is_empty() {
+            return;
+        }
+        let settled = watch.last_event + MISSING_WATCH_SETTLE;
+        if Instant::now() < settled {
+            self.repaint
+                .0
+                .request_repaint_after(settled - Instant::now());
+            return;
+        }
+        let missing: Vec<FontEntry> = self
+            .last_summary
+            .iter()
+            .flat_map(|summary| &summary.entries)
+            .filter(|e| e.status == EntryStatus::Missing)
+            .cloned()
+            .collect();
+        if missing.is_empty() {
+            self.logs
+                .push("[retry] 缺失字体已全部补齐，停止监视".to_string());
+            self.missing_watch = None;
+            return;
+        }
+        if watch.retries >= self.config.auto_retry_limit {
+            self.logs.push(format!(
+                "[retry] 已自动重新查找 {} 次，达到上限，停止监视",
+                watch.retries
+            ));
+            self.missing_watch = None;
+            return;
+        }
+        watch.retries += 1;
+        let folder = watch.pending.remove(0);
+        self.submit_job(Job::ResolveMissing {
+            folder,
+            missing,
+            use_cache: self.mode == Mode::Normal,
+        });
+    }
+
+    /// 每秒至多读取一次 GDI 对象数；设置页打开时定时刷新以实时显示。
+    fn poll_gdi_usage(&mut self, ctx: &egui::Context) {
+        if self
+            .gdi_checked_at
+            .is_none_or(|at| at.elapsed() >= GDI_POLL_INTERVAL)
+        {
+            self.gdi_usage = gdi_object_counts();
+            self.gdi_checked_at = Some(Instant::now());
+        }
+        if self.tab == Tab::Settings {
+            ctx.request_repaint_after(GDI_POLL_INTERVAL);
+        }
+    }
+
+    fn poll_http_requests(&mut self) {
+        let Some(rx) = &self.http_rx else {
+            return;
+        };
+        let requests: Vec<HttpRequest> = rx.try_iter().collect();
+        for request in requests {
+            let response = self.handle_http_command(request.command);
+            let _ = request.reply.send(response);
+        }
+    }
+
+    /// 执行 HTTP 接口的命令，加载和卸载与界面操作一样经任务队列执行，立即以当前状态
+    /// 应答而不等任务完成。
+    fn handle_http_command(&mut self, command: HttpCommand) -> (u16, String) {
+        let status = match command {
+            HttpCommand::Load(paths) => {
+                self.logs
+                    .push(format!("[http] 请求加载 {} 个路径", paths.len()));
+                let use_cache = self.mode == Mode::Normal;
+                self.submit_job(Job::Process {
+                    paths,
+                    use_cache,
+                    launch: None,
+                });
+                202
+            }
+            HttpCommand::Unload => {
+                self.logs.push("[http] 请求卸载全部字体".to_string());
+                self.handle_unload();
+                202
+            }
+            HttpCommand::Status => 200,
+        };
+        let fonts = self
+            .loaded_view
+            .iter()
+            .map(|(path, font)| HttpFont {
+                path,
+                names: &font.names,
+                faces: font.faces,
+            })
+            .collect();
+        let body = HttpStatus {
+            busy: self.busy,
+            queued: self.job_queue.len(),
+            files: self.loaded_view.len(),
+            faces: self.registered_faces,
+            fonts,
+        };
+        (status, http_json(&body))
+    }
+
+    /// 根据新启动的播放器进程决定是否自动加载其打开的目录，每个决定都以 `[auto]` 记录
+    /// 依据：进程、命令行中取到的参数和目录。
+    fn auto_load_for(&mut self, process: StartedProcess) {
+        let who = format!("{} (PID {})", process.name, process.pid);
+        let Some(command_line) = process.command_line else {
+            self.logs
+                .push(format!("[auto] {} 已启动，读取不到命令行，跳过", who));
+            return;
+        };
+        let Some((arg, dir)) = command_line_folder(&command_line) else {
+            self.logs.push(format!(
+                "[auto] {} 的命令行中没有存在的本地路径，跳过: {}",
+                who,
+                truncate_chars(&command_line, 120)
+            ));
+            return;
+        };
+        let evidence = format!(
+            "{}，参数 \"{}\"，目录 {}",
+            who,
+            truncate_chars(&arg, 120),
+            dir.to_string_lossy()
+        );
+        let subs = count_ass_files(&dir);
+        if subs == 0 {
+            self.logs.push(format!(
+                "[auto] {}: 目录中没有 ASS/SSA 字幕，跳过",
+                evidence
+            ));
+            return;
+        }
+        if let Some(at) = self.auto_loaded_at.get(&dir)
+            && at.elapsed() < AUTO_LOAD_COOLDOWN
+        {
+            self.logs.push(format!(
+                "[auto] {}: {} 秒内已自动加载过该目录，跳过",
+                evidence,
+                AUTO_LOAD_COOLDOWN.as_secs()
+            ));
+            return;
+        }
+        self.auto_loaded_at.insert(dir.clone(), Instant::now());
+        self.logs.push(format!(
+            "[auto] {}: 目录中有 {} 个字幕，自动加载",
+            evidence, subs
+        ));
+        let use_cache = self.mode == Mode::Normal;
+        self.submit_job(Job::Process {
+            paths: vec![dir.to_string_lossy().to_string()],
+            use_cache,
+            launch: None,
+        });
+    }
+
+    fn handle_compact_cache(&mut self) {
+        self.submit_job(Job::CompactCache);
+    }
+
+    fn handle_index_system(&mut self) {
+        let use_cache = self.mode == Mode::Normal;
+        self.submit_job(Job::IndexSystem { use_cache });
+    }
+
+    fn handle_export_manifest(&mut self) {
+        if self.loaded_view.is_empty() {
+            self.logs.push("[i] 没有已加载的字体".to_string());
+            return;
+        }
+        self.open_dialog(
+            rfd::FileDialog::new()
+                .add_filter("加载清单", &["json"])
+                .add_filter("批处理脚本", &["bat"])
+                .set_file_name("fontloader-restore.json"),
+            DialogAction::SaveFile,
+            DialogPurpose::ExportManifest,
+        );
+    }
+
+    fn export_manifest_to(&mut self, path: &Path) {
+        let manifest = RestoreManifest {
+            version: 1,
+            fonts: self
+                .loaded_view
+                .iter()
+                .map(|(path, font)| ManifestFont {
+                    path: path.clone(),
+                    names: font.names.clone(),
+                })
+                .collect(),
+        };
+        match export_manifest(path, &manifest) {
+            Ok(written) => {
+                for file in written {
+                    self.logs
+                        .push(format!("[i] 已导出加载清单: {}", file.to_string_lossy()));
+                }
+            }
+            Err(err) => self.logs.push(format!("[X] 导出加载清单失败: {}", err)),
+        }
+    }
+
+    /// 导出设置。HTTP 密钥不导出，以免随配置文件分享给他人。
+    fn handle_export_config(&mut self) {
+        self.open_dialog(
+            rfd::FileDialog::new()
+                .add_filter("配置", &["json"])
+                .set_file_name("fontloader-config.json"),
+            DialogAction::SaveFile,
+            DialogPurpose::ExportConfig,
+        );
+    }
+
+    fn export_config_to(&mut self, path: &Path) {
+        let config = Config {
+            http_token: String::new(),
+            ..self.config.clone()
+        };
+        let bundle = ConfigBundle {
+            version: CONFIG_BUNDLE_VERSION,
+            config: &config,
+        };
+        let result = serde_json::to_vec_pretty(&bundle)
+            .map_err(|e| e.to_string())
+            .and_then(|data| fs::write(path, data).map_err(|e| e.to_string()));
+        match result {
+            Ok(()) => self
+                .logs
+                .push(format!("[i] 已导出配置: {}", path.to_string_lossy())),
+            Err(err) => self.logs.push(format!("[X] 导出配置失败: {}", err)),
+        }
+    }
+
+    /// 读取并校验配置文件，列出改动后等待确认，见 [`FontLoaderApp::ui_config_import`]。
+    fn handle_import_config(&mut self) {
+        self.open_dialog(
+            rfd::FileDialog::new().add_filter("配置", &["json"]),
+            DialogAction::PickFile,
+            DialogPurpose::ImportConfig,
+        );
+    }
+
+    fn import_config_from(&mut self, path: PathBuf) {
+        let mut config = match load_config_bundle(&path) {
+            Ok(config) => config,
+            Err(err) => {
+                self.logs.push(format!("[X] 导入配置失败: {}", err));
+                return;
+            }
+        };
+        // 密钥不随配置导出，启动标签页属于本机的使用习惯，均保留本机的值
+        config.http_token = self.config.http_token.clone();
+        config.initial_tab = self.config.initial_tab;
+        let changes = config_changes(&self.config, &config);
+        if changes.is_empty() {
+            self.logs.push(format!(
+                "[i] 配置 {} 与当前设置相同",
+                path.to_string_lossy()
+            ));
+            return;
+        }
+        let missing_paths = config_missing_paths(&config);
+        self.config_import = Some(ConfigImport {
+            path,
+            config,
+            changes,
+            missing_paths,
+        });
+    }
+
+    fn handle_find_missing(&mut self) {
+        let Some(summary) = &self.last_summary else {
+            return;
+        };
+        let missing: Vec<FontEntry> = summary
+            .entries
+            .iter()
+            .filter(|e| e.status == EntryStatus::Missing)
+            .cloned()
+            .collect();
+        if missing.is_empty() {
+            return;
+        }
+        self.open_dialog(
+            rfd::FileDialog::new().set_title("选择包含缺失字体的文件夹"),
+            DialogAction::PickFolder,
+            DialogPurpose::FindMissing(missing),
+        );
+    }
+
+    fn handle_everything_search(&mut self) {
+        let Some(summary) = &self.last_summary else {
+            return;
+        };
+        let missing: Vec<FontEntry> = summary
+            .entries
+            .iter()
+            .filter(|e| e.status == EntryStatus::Missing)
+            .cloned()
+            .collect();
+        if missing.is_empty() {
+            return;
+        }
+        let names = missing.iter().map(|e| e.name.clone()).collect();
+        self.everything_search = Some(EverythingSearch {
+            missing,
+            hits: None,
+        });
+        self.submit_job(Job::EverythingSearch {
+            names,
+            port: self.config.everything_port,
+        });
+    }
+
+    fn handle_export_html_report(&mut self) {
+        if self.last_summary.is_none() {
+            return;
+        }
+        self.open_dialog(
+            rfd::FileDialog::new()
+                .add_filter("HTML", &["html"])
+                .set_file_name(format!("fontloader-report-{}.html", file_timestamp())),
+            DialogAction::SaveFile,
+            DialogPurpose::ExportHtmlReport,
+        );
+    }
+
+    fn handle_export_missing_report(&mut self) {
+        let Some(summary) = &self.last_summary else {
+            return;
+        };
+        let missing: Vec<FontEntry> = summary
+            .entries
+            .iter()
+            .filter(|e| e.status == EntryStatus::Missing)
+            .cloned()
+            .collect();
+        if missing.is_empty() {
+            return;
+        }
+        self.open_dialog(
+            rfd::FileDialog::new()
+                .add_filter("HTML", &["html"])
+                .add_filter("Markdown", &["md"])
+                .set_file_name("missing-fonts.html"),
+            DialogAction::SaveFile,
+            DialogPurpose::ExportMissingReport(missing),
+        );
+    }
+
+    fn handle_export_font_pack(&mut self) {
+        let Some(summary) = &self.last_summary else {
+            return;
+        };
+        let standard: HashSet<NameKey> = self
+            .config
+            .standard_fonts
+            .iter()
+            .map(|name| NameKey::new(name))
+            .collect();
+        let mut fonts: Vec<(String, Vec<String>)> = Vec::new();
+        let mut skipped = 0;
+        for entry in &summary.entries {
+            if !matches!(
+                entry.status,
+                EntryStatus::Loaded | EntryStatus::Duplicate | EntryStatus::MissingVariant
+            ) {
+                continue;
+            }
+            let Some(path) = &entry.path else {
+                continue;
+            };
+            if self.config.pack_skip_standard
+                && (!entry.conflicts.is_empty()
+                    || std::iter::once(&entry.name)
+                        .chain(&entry.families)
+                        .any(|name| standard.contains(&NameKey::new(name))))
+            {
+                self.logs
+                    .push(format!("[i] 系统自带，不打包: {} > {}", entry.name, path));
+                skipped += 1;
+                continue;
+            }
+            match fonts.iter_mut().find(|(p, _)| p == path) {
+                Some((_, names)) => names.push(entry.name.clone()),
+                None => fonts.push((path.clone(), vec![entry.name.clone()])),
+            }
+        }
+        if skipped > 0 {
+            self.logs
+                .push(format!("[i] 已跳过 {} 个系统自带字体", skipped));
+        }
+        if fonts.is_empty() {
+            self.logs.push("[i] 没有可打包的字体".to_string());
+            return;
+        }
+        let mut subs: Vec<String> = summary
+            .entries
+            .iter()
+            .flat_map(|e| e.sources.iter().cloned())
+            .collect();
+        subs.sort();
+        subs.dedup();
+        let file_name = format!("font_pack_{}.zip", file_timestamp());
+        self.open_dialog(
+            rfd::FileDialog::new()
+                .add_filter("ZIP", &["zip"])
+                .set_file_name(&file_name),
+            DialogAction::SaveFile,
+            DialogPurpose::Pack { fonts, subs },
+        );
+    }
+
+    fn handle_import_manifest(&mut self) {
+        self.open_dialog(
+            rfd::FileDialog::new().add_filter("加载清单", &["json"]),
+            DialogAction::PickFile,
+            DialogPurpose::ImportManifest,
+        );
+    }
+
+    fn import_manifest_from(&mut self, path: &Path) {
+        match load_manifest(path) {
+            Ok(manifest) => {
+                self.logs
+                    .push(format!("[i] 已导入加载清单: {}", manifest.fonts.len()));
+                self.submit_job(Job::Restore(manifest.fonts));
+            }
+            Err(err) => self.logs.push(format!("[X] 导入加载清单失败: {}", err)),
+        }
+    }
+
+    fn submit_job(&mut self, job: Job) {
+        if self.busy {
+            self.logs
+                .push(format!("[i] 已加入任务队列: {}", job.label()));
+            self.job_queue.push_back(job);
+            return;
+        }
+        self.start_job(job);
+    }
+
+    fn remove_queued_job(&mut self, index: usize) {
+        let Some(job) = self.job_queue.remove(index) else {
+            return;
+        };
+        self.logs
+            .push(format!("[i] 已移除排队任务: {}", job.label()));
+        // 被移除的加载任务把路径还给待处理列表，避免丢失用户的选择
+        if let Job::Process {
+            paths,
+            launch: None,
+            ..
+        } = job
+        {
+            for path in paths {
+                if !self.pending_paths.iter().any(|p| p.path == path) {
+                    self.pending_paths.push(PendingEntry::new(path));
+                }
+            }
+        }
+    }
+
+    /// 按当前设置配置的加载参数，所有加载和卸载任务共用，匹配与注册方式保持一致。
+    fn loader_builder(&self, use_cache: bool) -> FontLoaderBuilder {
+        let mut builder = FontLoaderBuilder::new()
+            .with_cache(use_cache)
+            .with_cache_ttl_days(self.config.cache_ttl_days)
+            .with_case_sensitive(self.config.case_sensitive)
+            .with_strict_style(self.config.strict_style)
+            .with_skip_system_conflicts(self.config.skip_system_conflicts)
+            .with_load_retries(self.config.load_retries)
+            .with_max_logged_candidates(self.config.max_logged_candidates)
+            .with_registrations(self.registrations.clone())
+            .with_system_index(self.system_index.clone())
+            .with_session_index(self.session_font_index.clone())
+            .with_font_folders(self.config.font_folders.clone())
+            .with_aliases(&self.config.font_aliases)
+            .with_excluded_fonts(&self.config.excluded_fonts)
+            .with_name_rules(self.name_rules.clone())
+            .with_unload_verification(self.config.verify_unload)
+            .with_mode(self.config.load_mode);
+        if let Some(depth) = self.config.max_depth {
+            builder = builder.with_max_depth(depth);
+        }
+        if let Some(path) = &self.config.alias_map_path {
+            builder = builder.with_alias_map(path);
+        }
+        builder
+    }
+
+    fn start_job(&mut self, job: Job) {
+        let state = self.state.clone();
+        let cancel = self.reset_cancel();
+        let repaint = self.repaint.clone();
+        let (tx, rx) = mpsc::channel();
+        self.worker_rx = Some(rx);
+        self.busy = true;
+        if let Ok(mut registrations) = self.registrations.lock() {
+            registrations.clear();
+        }
+        // 任何改动已加载字体的任务都会让上次加载的快照失效
+        if !matches!(
+            job,
+            Job::Preview { .. }
+                | Job::SystemCoverage { .. }
+                | Job::FindFontFiles { .. }
+                | Job::EverythingSearch { .. }
+                | Job::Pack { .. }
+                | Job::IndexSystem { .. }
+                | Job::CompactCache
+                | Job::Download(_)
+        ) {
+            self.undo_batch = None;
+        }
+        let handle = match job {
+            Job::Process {
+                paths,
+                use_cache,
+                launch,
+            } => {
+                self.pending_launch = launch;
+                // 新批次开始，上一批次的缺失字体不再需要补
+                self.missing_watch = None;
+                let mut watch_dirs: Vec<PathBuf> = Vec::new();
+                for path in &paths {
+                    let path = Path::new(path);
+                    let dir = if path.is_dir() {
+                        Some(path)
+                    } else {
+                        path.parent()
+                    };
+                    if let Some(dir) = dir {
+                        if !self.processed_dirs.iter().any(|d| d == dir) {
+                            self.processed_dirs.push(dir.to_path_buf());
+                        }
+                        if !watch_dirs.iter().any(|d| d == dir) {
+                            watch_dirs.push(dir.to_path_buf());
+                        }
+                    }
+                }
+                self.pending_watch_dirs = Some(watch_dirs);
+                let loader = self.loader_builder(use_cache).with_state(state).build();
+                thread::spawn(move || {
+                    let progress = progress_reporter(tx.clone(), repaint.clone());
+                    let paths = paths.into_iter().map(PathBuf::from).collect();
+                    let result = loader
+                        .process_with(paths, &progress, &cancel)
+                        .map_err(|e| e.to_string());
+                    send_result(&tx, &repaint, WorkerResult::Process(result));
+                })
+            }
+            Job::Preview { paths, use_cache } => {
+                let options = LoadOptions {
+                    use_cache,
+                    font_folders: self.config.font_folders.clone(),
+                    excluded: excluded_font_keys(&self.config.excluded_fonts),
+                    name_rules: self.name_rules.clone(),
+                    cache_ttl_days: self.config.cache_ttl_days,
+                    ..LoadOptions::default()
+                };
+                thread::spawn(move || {
+                    let progress = progress_reporter(tx.clone(), repaint.clone());
+                    let result = preview_diff_worker(paths, &options, &progress, &cancel);
+                    send_result(&tx, &repaint, WorkerResult::Preview(result));
+                })
+            }
+            Job::SystemCoverage { paths, use_cache } => {
+                let options = LoadOptions {
+                    use_cache,
+                    font_folders: self.config.font_folders.clone(),
+                    excluded: excluded_font_keys(&self.config.excluded_fonts),
+                    name_rules: self.name_rules.clone(),
+                    cache_ttl_days: self.config.cache_ttl_days,
+                    ..LoadOptions::default()
+                };
+                thread::spawn(move || {
+                    let progress = progress_reporter(tx.clone(), repaint.clone());
+                    let result = system_coverage_worker(paths, &options, state, &progress, &cancel);
+                    send_result(&tx, &repaint, WorkerResult::SystemCoverage(result));
+                })
+            }
+            Job::ResolveMissing {
+                folder,
+                missing,
+                use_cache,
+            } => {
+                let loader = self.loader_builder(use_cache).with_state(state).build();
+                thread::spawn(move || {
+                    let progress = progress_reporter(tx.clone(), repaint.clone());
+                    let result = loader
+                        .resolve_missing_with(vec![folder], missing, &progress, &cancel)
+                        .map_err(|e| e.to_string());
+                    send_result(&tx, &repaint, WorkerResult::Resolve(result));
+                })
+            }
+            Job::ResolveMissingFiles {
+                files,
+                missing,
+                use_cache,
+            } => {
+                let loader = self.loader_builder(use_cache).with_state(state).build();
+                thread::spawn(move || {
+                    let progress = progress_reporter(tx.clone(), repaint.clone());
+                    let result = loader
+                        .resolve_missing_with(files, missing, &progress, &cancel)
+                        .map_err(|e| e.to_string());
+                    send_result(&tx, &repaint, WorkerResult::Resolve(result));
+                })
+            }
+            Job::EverythingSearch { names, port } => thread::spawn(move || {
+                let result = everything_search_worker(&names, port, &cancel);
+                send_result(&tx, &repaint, WorkerResult::EverythingSearch(result));
+            }),
+            Job::Restore(fonts) => {
+                let loader = self.loader_builder(false).with_state(state).build();
+                thread::spawn(move || {
+                    let result = loader.restore(fonts).map_err(|e| e.to_string());
+                    send_result(&tx, &repaint, WorkerResult::Process(result));
+                })
+            }
+            Job::Unload(only) => {
+                let loader = self.loader_builder(false).with_state(state).build();
+                thread::spawn(move || {
+                    let result = loader.unload(only).map_err(|e| e.to_string());
+                    send_result(&tx, &repaint, WorkerResult::Unload(result));
+                })
+            }
+            Job::Undo(batch) => {
+                let loader = self.loader_builder(false).with_state(state).build();
+                thread::spawn(move || {
+                    let result = loader.undo(batch).map_err(|e| e.to_string());
+                    send_result(&tx, &repaint, WorkerResult::Undo(result));
+                })
+            }
+            Job::Pack { dest, fonts, subs } => thread::spawn(move || {
+                let result = write_font_pack(&dest, &fonts, &subs).map(|()| dest);
+                send_result(&tx, &repaint, WorkerResult::Pack(result));
+            }),
+            Job::CompactCache => {
+                let ttl_days = self.config.cache_ttl_days;
+                thread::spawn(move || {
+                    let result = compact_cache_file(ttl_days);
+                    send_result(&tx, &repaint, WorkerResult::CompactCache(result));
+                })
+            }
+            Job::Download(urls) => thread::spawn(move || {
+                let progress = progress_reporter(tx.clone(), repaint.clone());
+                let result = download_files_worker(urls, &progress, &cancel);
+                send_result(&tx, &repaint, WorkerResult::Download(result));
+            }),
+            Job::IndexSystem { use_cache } => {
+                let rules = self.name_rules.clone();
+                let ttl_days = self.config.cache_ttl_days;
+                thread::spawn(move || {
+                    let progress = progress_reporter(tx.clone(), repaint.clone());
+                    let index_progress = |done, total| progress(ProgressPhase::Index, done, total);
+                    let result = index_system_fonts_worker(
+                        use_cache,
+                        ttl_days,
+                        &rules,
+                        &index_progress,
+                        &cancel,
+                    );
+                    send_result(&tx, &repaint, WorkerResult::SystemIndex(result));
+                })
+            }
+            Job::Clean(folder) => {
+                let folder_str = folder.to_string_lossy().to_string();
+                self.logs
+                    .push(format!("[i] 正在强力清理目录: {}", folder_str));
+                thread::spawn(move || {
+                    let result = clean_folder_worker(folder);
+                    send_result(&tx, &repaint, WorkerResult::Clean(result));
+                })
+            }
+            Job::FindFontFiles {
+                name,
+                dirs,
+                use_cache,
+            } => {
+                let session = self.session_font_index.clone();
+                let rules = self.name_rules.clone();
+                thread::spawn(move || {
+                    let progress = progress_reporter(tx.clone(), repaint.clone());
+                    let index_progress = |done, total| progress(ProgressPhase::Index, done, total);
+                    let result = find_font_files_worker(
+                        &name,
+                        &dirs,
+                        &session,
+                        &rules,
+                        use_cache,
+                        &index_progress,
+                        &cancel,
+                    );
+                    send_result(&tx, &repaint, WorkerResult::FindFontFiles(result));
+                })
+            }
+            Job::CleanFiles(paths) => thread::spawn(move || {
+                let result = clean_files_worker(paths);
+                send_result(&tx, &repaint, WorkerResult::CleanFiles(result));
+            }),
+        };
+        self.worker = Some(handle);
+    }
+
+    fn poll_worker(&mut self) {
+        let Some(rx) = self.worker_rx.take() else {
+            return;
+        };
+        let mut finished = false;
+        while let Ok(msg) = rx.try_recv() {
+            match msg {
+                WorkerResult::Progress(phase, done, total) => {
+                    self.progress = Some((phase, done, total));
+                }
+                WorkerResult::Process(result) => {
+                    self.busy = false;
+                    finished = true;
+                    match result {
+                        Ok(res) => {
+                            let mut summary = format!(
+                                "完成: 字幕{} 字体{} 已载入{} 失败{} 缺失{} 重复{}",
+                                res.subs, res.fonts, res.loaded, res.failed, res.missing, res.duplicates
+                            );
+                            if res.installed > 0 {
+                                summary.push_str(&format!(" 系统已安装{}", res.installed));
+                            }
+                            if res.system_conflicts > 0 {
+                                summary.push_str(&format!(" 覆盖系统字体{}", res.system_conflicts));
+                            }
+                            if res.missing_variants > 0 {
+                                summary.push_str(&format!(" 缺少变体{}", res.missing_variants));
+                            }
+                            if res.empty_subs > 0 {
+                                summary.push_str(&format!(" 空字幕{}", res.empty_subs));
+                            }
+                            if res.invalid_fonts > 0 {
+                                summary.push_str(&format!(" 无效字体{}", res.invalid_fonts));
+                            }
+                            if let Some(types) = res.types.summary() {
+                                summary.push_str(&format!(" {}", types));
+                            }
+                            if let Some(cache) = res.cache.summary() {
+                                summary.push_str(&format!(" {}", cache));
+                            }
+                            if res.subs_only {
+                                summary.push_str(&format!(" {}", SUBS_ONLY_HINT));
+                            }
+                            let attempted = res.loaded + res.failed;
+                            if attempted > 0 {
+                                self.session_success_rate
+                                    .push(res.loaded as f64 / attempted as f64);
+                            }
+                            self.append_logs(res.logs.clone());
+                            self.logs.push(summary);
+                            if !res.batch.loaded_paths.is_empty() {
+                                self.undo_batch = Some(res.batch.clone());
+                            }
+                            // 工作线程已把之前的索引并入本次索引，直接替换即为累积结果
+                            if let Some(index) = &res.index {
+                                self.session_font_index = index.clone();
+                            }
+                            if let Some(file) = self.pending_launch.take() {
+                                self.start_player_session(file, &res);
+                            }
+                            self.selected_results.clear();
+                            // 文件可能在两次处理之间被替换，版本信息重新读取
+                            self.font_meta.clear();
+                            self.last_summary = Some(res);
+                            if let Some(dirs) = self.pending_watch_dirs.take() {
+                                self.start_missing_watch(dirs);
+                            }
+                        }
+                        Err(err) => {
+                            if let Some(file) = self.pending_launch.take() {
+                                self.logs.push(format!(
+                                    "[i] 加载未完成，不再打开播放器: {}",
+                                    file.to_string_lossy()
+                                ));
+                            }
+                            self.logs.push(format!("[X] {}", err));
+                        }
+                    }
+                }
+                WorkerResult::Preview(result) => {
+                    self.busy = false;
+                    finished = true;
+                    match result {
+                        Ok(preview) => {
+                            self.logs.push(format!(
+                                "差异预览: 缺失{} 未使用{}",
+                                preview.missing.len(),
+                                preview.unused.len()
+                            ));
+                            self.diff_preview = Some(preview);
+                        }
+                        Err(err) => {
+                            self.logs.push(format!("[X] {}", err));
+                        }
+                    }
+                }
+                WorkerResult::Resolve(result) => {
+                    self.busy = false;
+                    finished = true;
+                    match result {
+                        Ok(res) => self.merge_resolved(res),
+                        Err(err) => {
+                            self.logs.push(format!("[X] {}", err));
+                        }
+                    }
+                }
+                WorkerResult::EverythingSearch(result) => {
+                    self.busy = false;
+                    finished = true;
+                    match &result {
+                        Ok(hits) => self
+                            .logs
+                            .push(format!("[i] Everything 找到 {} 个候选文件", hits.len())),
+                        Err(err) => self.logs.push(format!("[X] {}", err)),
+                    }
+                    if let Some(dialog) = &mut self.everything_search {
+                        dialog.hits = Some(result.map(|hits| {
+                            hits.into_iter()
+                                .map(|(font, path)| EverythingHit {
+                                    font,
+                                    path,
+                                    checked: false,
+                                })
+                                .collect()
+                        }));
+                    }
+                }
+                WorkerResult::Unload(result) => {
+                    self.busy = false;
+                    finished = true;
+                    match result {
+                        Ok(res) => {
+                            self.logs.push(format!("卸载完成: {}", res.count));
+                            for name in &res.still_present {
+                                self.logs.push(format!(
+                                    "[still-present] {} 卸载后仍可枚举，可能被其他程序占用",
+                                    name
+                                ));
+                            }
+                            let remaining = self.state.lock().map(|s| s.loaded.len()).unwrap_or(0);
+                            if remaining == 0 {
+                                self.selected_results.clear();
+                                self.last_summary = Some(ProcessResult {
+                                    loaded: 0,
+                                    failed: 0,
+                                    missing: 0,
+                                    duplicates: 0,
+                                    installed: 0,
+                                    system_conflicts: 0,
+                                    missing_variants: 0,
+                                    subs: 0,
+                                    empty_subs: 0,
+                                    fonts: 0,
+                                    invalid_fonts: 0,
+                                    logs: LogLines::default(),
+                                    entries: Vec::new(),
+                                    subtitles: Vec::new(),
+                                    cache: CacheStats::default(),
+                                    types: FontTypeStats::default(),
+                                    subs_only: false,
+                                    rule_hits: Vec::new(),
+                                    batch: LoadBatch::default(),
+                                    index: None,
+                                });
+                            }
+                        }
+                        Err(err) => {
+                            self.logs.push(format!("[X] {}", err));
+                        }
+                    }
+                }
+                WorkerResult::Undo(result) => {
+                    self.busy = false;
+                    finished = true;
+                    match result {
+                        Ok(res) => {
+                            self.logs
+                                .push(format!("[i] 已撤销上次加载，卸载了 {} 个字体", res.count));
+                        }
+                        Err(err) => {
+                            self.logs.push(format!("[X] {}", err));
+                        }
+                    }
+                }
+                WorkerResult::Clean(result) => {
+                    self.busy = false;
+                    finished = true;
+                    match result {
+                        Ok(res) => {
+                            self.logs.push(format!("强力清理完成，释放了 {} 个字体引用", res.count));
+                        }
+                        Err(err) => {
+                            self.logs.push(format!("[X] {}", err));
+                        }
+                    }
+                }
+                WorkerResult::FindFontFiles(result) => {
+                    self.busy = false;
+                    finished = true;
+                    match result {
+                        Ok(paths) => {
+                            self.logs
+                                .push(format!("[i] 找到 {} 个匹配的字体文件", paths.len()));
+                            if let Some(dialog) = &mut self.clean_by_name {
+                                dialog.candidates =
+                                    Some(paths.into_iter().map(|p| (p, true)).collect());
+                            }
+                        }
+                        Err(err) => self.logs.push(format!("[X] {}", err)),
+                    }
+                }
+                WorkerResult::CleanFiles(result) => {
+                    self.busy = false;
+                    finished = true;
+                    match result {
+                        Ok(released) => {
+                            for (path, count) in &released {
+                                self.logs
+                                    .push(format!("[i] {}: 释放了 {} 个引用", path, count));
+                            }
+                            let total: usize = released.iter().map(|(_, count)| count).sum();
+                            self.logs.push(format!(
+                                "按字体名清理完成，{} 个文件共释放了 {} 个字体引用",
+                                released.len(),
+                                total
+                            ));
+                        }
+                        Err(err) => self.logs.push(format!("[X] {}", err)),
+                    }
+                }
+                WorkerResult::Pack(result) => {
+                    self.busy = false;
+                    finished = true;
+                    match result {
+                        Ok(path) => self
+                            .logs
+                            .push(format!("[i] 已打包字体: {}", path.to_string_lossy())),
+                        Err(err) => self.logs.push(format!("[X] 打包字体失败: {}", err)),
+                    }
+                }
+                WorkerResult::SystemIndex(result) => {
+                    self.busy = false;
+                    finished = true;
+                    match result {
+                        Ok(index) => {
+                            let mut summary = format!(
+                                "[sys] 已索引系统字体库: 文件{} 字体名{}",
+                                index.families.len(),
+                                index.names.len()
+                            );
+                            if !index.invalid.is_empty() {
+                                summary.push_str(&format!(" 无效字体{}", index.invalid.len()));
+                            }
+                            if let Some(cache) = index.cache_stats.summary() {
+                                summary.push_str(&format!(" {}", cache));
+                            }
+                            self.logs.push(summary);
+                            self.system_library = library_rows(&index);
+                            self.system_index = Some(Arc::new(index));
+                        }
+                        Err(err) => self.logs.push(format!("[X] 加载系统字体库失败: {}", err)),
+                    }
+                }
+                WorkerResult::SystemCoverage(result) => {
+                    self.busy = false;
+                    finished = true;
+                    match result {
+                        Ok(coverage) => {
+                            for (font, name) in &coverage.covered {
+                                self.logs.push(format!("[sys] {} > {}", font, name));
+                            }
+                            for (font, file) in &coverage.bundle {
+                                self.logs.push(match file {
+                                    Some(file) => format!("[bundle] {} > {}", font, file),
+                                    None => format!("[??] {}", font),
+                                });
+                            }
+                            let unavailable =
+                                coverage.bundle.iter().filter(|(_, f)| f.is_none()).count();
+                            self.logs.push(format!(
+                                "系统字体覆盖: 系统已安装{} 需要打包{} (其中未找到{})",
+                                coverage.covered.len(),
+                                coverage.bundle.len(),
+                                unavailable
+                            ));
+                            self.system_coverage = Some(coverage);
+                        }
+                        Err(err) => {
+                            self.logs.push(format!("[X] {}", err));
+                        }
+                    }
+                }
+                WorkerResult::CompactCache(result) => {
+                    self.busy = false;
+                    finished = true;
+                    match result {
+                        Ok(res) => self.logs.push(format!(
+                            "[i] 缓存整理完成: 删除 {} 条，保留 {} 条，{} -> {}",
+                            res.removed,
+                            res.kept,
+                            format_bytes(res.before),
+                            format_bytes(res.after)
+                        )),
+                        Err(err) => self.logs.push(format!("[X] 整理缓存失败: {}", err)),
+                    }
+                }
+                WorkerResult::Download(results) => {
+                    self.busy = false;
+                    finished = true;
+                    let mut saved = Vec::new();
+                    for (url, result) in results {
+                        match result {
+                            Ok(file) => {
+                                self.logs.push(format!(
+                                    "[download-ok] Saved: {} ({})",
+                                    file.path.to_string_lossy(),
+                                    format_bytes(file.size)
+                                ));
+                                saved.push(file.path);
+                            }
+                            Err(err) => self.logs.push(format!("[X] 下载失败 {}: {}", url, err)),
+                        }
+                    }
+                    if !saved.is_empty() {
+                        self.enqueue_paths(saved);
+                    }
+                }
+            }
+        }
+        if finished {
+            self.worker_rx = None;
+            self.worker = None;
+            self.progress = None;
+            self.refresh_loaded_view();
+            self.jobs_done += 1;
+            if self.cancel.load(Ordering::Relaxed)
+                && self.config.cancel_clears_queue
+                && !self.job_queue.is_empty()
+            {
+                let count = self.job_queue.len();
+                while !self.job_queue.is_empty() {
+                    self.remove_queued_job(0);
+                }
+                self.logs
+                    .push(format!("[i] 已取消，清空了 {} 个排队任务", count));
+            }
+            if let Some(job) = self.job_queue.pop_front() {
+                self.start_job(job);
+            } else {
+                self.jobs_done = 0;
+            }
+        } else {
+            self.worker_rx = Some(rx);
+        }
+    }
+
+    /// 把查找缺失字体的结果合并进当前结果：替换同名的缺失条目并更新计数。
+    fn merge_resolved(&mut self, res: ProcessResult) {
+        let resolved = res.entries.len() - res.missing;
+        let attempted = res.loaded + res.failed;
+        if attempted > 0 {
+            self.session_success_rate
+                .push(res.loaded as f64 / attempted as f64);
+        }
+        let mut text = format!(
+            "查找缺失字体: 字体{} 找到{} 已载入{} 仍缺失{}",
+            res.fonts, resolved, res.loaded, res.missing
+        );
+        if let Some(cache) = res.cache.summary() {
+            text.push_str(&format!(" {}", cache));
+        }
+        self.logs.push(text);
+        self.append_logs(res.logs);
+        let Some(summary) = &mut self.last_summary else {
+            return;
+        };
+        for entry in res.entries {
+            if let Some(slot) = summary
+                .entries
+                .iter_mut()
+                .find(|e| e.status == EntryStatus::Missing && e.name == entry.name)
+            {
+                *slot = entry;
+            }
+        }
+        summary.loaded += res.loaded;
+        summary.failed += res.failed;
+        summary.duplicates += res.duplicates;
+        summary.installed += res.installed;
+        summary.system_conflicts += res.system_conflicts;
+        summary.missing = summary.missing.saturating_sub(resolved);
+        summary.fonts += res.fonts;
+        summary.cache.add(res.cache);
+        let subs: Vec<String> = summary.subtitles.iter().map(|s| s.path.clone()).collect();
+        summary.subtitles = subtitle_reports(&subs, &summary.entries);
+    }
+
+    fn refresh_loaded_view(&mut self) {
+        let Ok(state) = self.state.lock() else {
+            return;
+        };
+        self.loaded_view = state
+            .loaded
+            .values()
+            .map(|font| (font.path.clone(), font.clone()))
+            .collect();
+        self.selected_loaded
+            .retain(|path| self.loaded_view.contains_key(path));
+
+        let faces = state.registered_faces();
+        drop(state);
+        if faces == self.registered_faces {
+            return;
+        }
+        let threshold = self.config.face_warn_threshold;
+        if self.registered_faces < threshold && faces >= threshold {
+            self.logs.push(format!(
+                "[i] 已注册 {} 个字体面，超过警告阈值 {}，字体过多会导致部分程序无法枚举字体，建议卸载已看完的批次",
+                faces, threshold
+            ));
+        }
+        self.registered_faces = faces;
+        // 保留的字体此时已列入已加载，计入 `faces`
+        let session = SessionState {
+            registered_faces: faces,
+            ..SessionState::default()
+        };
+        if let Err(err) = save_session(&session) {
+            self.logs.push(format!("[X] 保存会话状态失败: {}", err));
+        }
+    }
+
+    fn clear_residual_faces(&mut self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.residual_faces = 0;
+        }
+        self.refresh_loaded_view();
+    }
+
+    /// Ctrl+Shift+M：把上次处理的缺失字体名逐行复制到剪贴板。
+    fn handle_copy_missing_names(&mut self, ctx: &egui::Context) {
+        let Some(summary) = &self.last_summary else {
+            return;
+        };
+        if summary.missing == 0 {
+            return;
+        }
+        let mut names: Vec<&str> = summary
+            .entries
+            .iter()
+            .filter(|e| e.status == EntryStatus::Missing)
+            .map(|e| e.name.as_str())
+            .collect();
+        names.dedup();
+        ctx.copy_text(names.join("\n"));
+        self.logs
+            .push(format!("[i] 已复制 {} 个缺失字体名", names.len()));
+    }
+
+    fn apply_row_action(&mut self, ctx: &egui::Context, action: RowAction) {
+        match action {
+            RowAction::OpenFolder(path) => open_containing_folder(&path),
+            RowAction::CopyNames(names) => {
+                self.logs.push(format!("[i] 已复制字体名: {}", names.len()));
+                ctx.copy_text(names.join("\n"));
+            }
+            RowAction::CopyPaths(paths) => {
+                self.logs
+                    .push(format!("[i] 已复制文件路径: {}", paths.len()));
+                ctx.copy_text(paths.join("\n"));
+            }
+            RowAction::Unload(paths) => self.handle_unload_paths(paths),
+            RowAction::AddAlias(name) => {
+                let target = self
+                    .config
+                    .font_aliases
+                    .get(&name)
+                    .cloned()
+                    .unwrap_or_default();
+                self.alias_edit = Some(AliasEdit { name, target });
+            }
+            RowAction::Exclude(paths) => self.exclude_fonts(paths),
+        }
+    }
+
+    /// 把文件加入排除列表，之后的处理不再匹配它们；已加载的不受影响。
+    fn exclude_fonts(&mut self, paths: Vec<String>) {
+        let mut keys: HashSet<PathKey> = self
+            .config
+            .excluded_fonts
+            .iter()
+            .map(|path| PathKey::new(path))
+            .collect();
+        let mut added = 0;
+        for path in paths {
+            if keys.insert(PathKey::new(&path)) {
+                self.logs.push(format!("[i] 已排除字体文件: {}", path));
+                self.config.excluded_fonts.push(path);
+                added += 1;
+            }
+        }
+        if added == 0 {
+            return;
+        }
+        self.excluded_fonts_text = self.config.excluded_fonts.join("\n");
+        if let Err(err) = save_config(&self.config) {
+            self.logs.push(format!("[X] 保存设置失败: {}", err));
+        }
+    }
+
+    /// 保存别名对话框的结果，目标为空或与原名相同时删除别名。
+    fn set_font_alias(&mut self, name: String, target: String) {
+        let target = target.trim().to_string();
+        if target.is_empty() || target == name {
+            if self.config.font_aliases.remove(&name).is_some() {
+                self.logs.push(format!("[i] 删除别名: {}", name));
+            }
+        } else {
+            self.logs.push(format!("[i] 别名: {} -> {}", name, target));
+            self.config.font_aliases.insert(name, target);
+        }
+        self.font_aliases_text = format_font_aliases(&self.config.font_aliases);
+        if let Err(err) = save_config(&self.config) {
+            self.logs.push(format!("[X] 保存设置失败: {}", err));
+        }
+    }
+
+    fn ui_operate(&mut self, ui: &mut egui::Ui) {
+        ui.vertical(|ui| {
+            ui.label("将字幕/字体文件或文件夹拖入窗口，加入待处理后再点击开始处理");
+            ui.add_space(4.0);
+
+            let available_width = ui.available_width();
+            let spacing = ui.spacing().item_spacing.x;
+            let row_height = 35.0;
+
+            // 第一行：选文件，选文件夹
+            ui.horizontal(|ui| {
+                let btn_w = (available_width - spacing) / 2.0;
+                if ui.add_sized([btn_w, row_height], egui::Button::new("选文件")).clicked() {
+                    self.open_dialog(
+                        rfd::FileDialog::new(),
+                        DialogAction::PickFiles,
+                        DialogPurpose::AddFiles,
+                    );
+                }
+                if ui.add_sized([btn_w, row_height], egui::Button::new("选文件夹")).clicked() {
+                    self.open_dialog(
+                        rfd::FileDialog::new(),
+                        DialogAction::PickFolder,
+                        DialogPurpose::AddFolder,
+                    );
+                }
+            });
+
+            ui.add_space(4.0);
+
+            // 手动输入路径，每行一个
+            ui.horizontal(|ui| {
+                let btn_w = 80.0;
+                let rows_height = ui.text_style_height(&egui::TextStyle::Body) * 3.0
+                    + ui.spacing().button_padding.y * 2.0;
+                egui::ScrollArea::vertical()
+                    .id_salt("path_input")
+                    .max_width(available_width - btn_w - spacing)
+                    .min_scrolled_height(rows_height)
+                    .max_height(rows_height)
+                    .show(ui, |ui| {
+                        ui.add(
+                            egui::TextEdit::multiline(&mut self.path_input)
+                                .desired_rows(3)
+                                .desired_width(f32::INFINITY)
+                                .hint_text("粘贴路径，每行一个"),
+                        );
+                    });
+                if ui
+                    .add_sized([btn_w, rows_height], egui::Button::new("添加"))
+                    .clicked()
+                {
+                    self.handle_add_typed_paths();
+                }
+            });
+            if let Some(err) = &self.path_input_error {
+                ui.colored_label(ui.visuals().error_fg_color, err);
+            }
+
+            ui.add_space(4.0);
+
+            // 第二行：开始处理（加载），预览差异，系统覆盖检查，卸载
+            ui.horizontal(|ui| {
+                let btn_w = (available_width - spacing * 3.0) / 4.0;
+                if ui.add_sized([btn_w, row_height], egui::Button::new("加载字体")).clicked() {
+                    self.handle_process_pending();
+                }
+                if ui
+                    .add_sized([btn_w, row_height], egui::Button::new("预览差异"))
+                    .on_hover_text("加载前对比字幕所需字体与已索引字体")
+                    .clicked()
+                {
+                    self.handle_preview_diff();
+                }
+                if ui
+                    .add_sized([btn_w, row_height], egui::Button::new("系统覆盖检查"))
+                    .on_hover_text("检查字幕所需字体在未加载任何字体的系统上能否显示，列出需要打包的字体")
+                    .clicked()
+                {
+                    self.handle_system_coverage();
+                }
+                if ui.add_sized([btn_w, row_height], egui::Button::new("卸载已加载字体")).clicked() {
+                    self.handle_unload();
+                }
+            });
+
+            ui.add_space(4.0);
+
+            // 第三行：系统字体库，播放器
+            ui.horizontal(|ui| {
+                let btn_w = (available_width - spacing) / 2.0;
+                if ui
+                    .add_sized([btn_w, row_height], egui::Button::new("加载系统字体库"))
+                    .on_hover_text("索引注册表中登记的系统字体，字幕用到的系统字体不再重复加载")
+                    .clicked()
+                {
+                    self.handle_index_system();
+                }
+                if ui
+                    .add_sized([btn_w, row_height], egui::Button::new("用播放器打开"))
+                    .on_hover_text("选择视频或字幕，加载所需字体后用播放器打开，播放器退出后自动卸载")
+                    .clicked()
+                {
+                    self.handle_open_with_player();
+                }
+            });
+
+            ui.add_space(4.0);
+
+            // 第四行：强制清理
+            ui.horizontal(|ui| {
+                let btn_w = (available_width - spacing) / 2.0;
+                if ui
+                    .add_sized([btn_w, row_height], egui::Button::new("⚠强制清理目录残留"))
+                    .on_hover_text("选择一个文件夹，尝试强制卸载其中所有字体文件的系统占用（无论是否由本程序加载）")
+                    .clicked()
+                {
+                    self.open_dialog(
+                        rfd::FileDialog::new(),
+                        DialogAction::PickFolder,
+                        DialogPurpose::CleanFolder,
+                    );
+                }
+                if ui
+                    .add_sized([btn_w, row_height], egui::Button::new("⚠按字体名强制清理"))
+                    .on_hover_text("输入字体名，在缓存、字体库和处理过的目录中查找对应文件并强制卸载")
+                    .clicked()
+                {
+                    self.clean_by_name = Some(CleanByName::default());
+                }
+            });
+
+            ui.add_space(4.0);
+
+            // 第五行：诊断
+            if ui
+                .add_sized([available_width, row_height], egui::Button::new("单字体匹配测试"))
+                .on_hover_text("选择一个字幕和一个字体文件，逐个字体名说明能否匹配及原因")
+                .clicked()
+            {
+                self.match_test = Some(MatchTest {
+                    rules: self.name_rules.clone(),
+                    ..MatchTest::default()
+                });
+            }
+
+            ui.add_space(8.0);
+
+            ui.horizontal(|ui| {
+                let mut mode = self.mode;
+                ui.label("模式:");
+                if ui.radio_value(&mut mode, Mode::NoResidue, "无残留").clicked() {
+                    self.mode = Mode::NoResidue;
+                }
+                ui.weak("ℹ")
+                    .on_hover_text("无残留: 加载完成后字体不写入缓存，适合临时使用");
+                if ui.radio_value(&mut mode, Mode::Normal, "普通").clicked() {
+                    self.mode = Mode::Normal;
+                }
+                ui.weak("ℹ").on_hover_text(
+                    "普通: 建立并使用字体名称缓存，重复使用同一字体目录时速度更快",
+                );
+            });
+
+            ui.label(format!("待处理路径: {}", self.pending_paths.len()));
+            if !self.pending_paths.is_empty() {
+                egui::ScrollArea::vertical()
+                    .id_salt("pending_paths")
+                    .max_height(100.0)
+                    .show(ui, |ui| {
+                        for entry in &self.pending_paths {
+                            ui.horizontal(|ui| {
+                                ui.label(entry.kind.icon())
+                                    .on_hover_text(entry.kind.label());
+                                ui.label(&entry.path);
+                            });
+                        }
+                    });
+            }
+            if let Some(summary) = &self.last_summary {
+                let mut text = format!(
+                    "摘要: 字幕{} 字体{} 已载入{} 失败{} 缺失{} 重复{}",
+                    summary.subs,
+                    summary.fonts,
+                    summary.loaded,
+                    summary.failed,
+                    summary.missing,
+                    summary.duplicates
+                );
+                if summary.installed > 0 {
+                    text.push_str(&format!(" 系统已安装{}", summary.installed));
+                }
+                if summary.system_conflicts > 0 {
+                    text.push_str(&format!(" 覆盖系统字体{}", summary.system_conflicts));
+                }
+                if summary.missing_variants > 0 {
+                    text.push_str(&format!(" 缺少变体{}", summary.missing_variants));
+                }
+                if summary.empty_subs > 0 {
+                    text.push_str(&format!(" 空字幕{}", summary.empty_subs));
+                }
+                if summary.invalid_fonts > 0 {
+                    text.push_str(&format!(" 无效字体{}", summary.invalid_fonts));
+                }
+                ui.label(text);
+                if summary.subs_only {
+                    ui.colored_label(ui.visuals().warn_fg_color, SUBS_ONLY_HINT);
+                }
+                if let Some(types) = summary.types.summary() {
+                    ui.weak(types);
+                }
+                if let Some(cache) = summary.cache.summary() {
+                    ui.weak(cache);
+                }
+            }
+            if let Some(batch) = &self.undo_batch
+                && ui
+                    .add_enabled(!self.busy, egui::Button::new("撤销上次加载"))
+                    .on_hover_text(format!(
+                        "卸载上次加载新注册的 {} 个字体文件，之前已加载的不受影响",
+                        batch.loaded_paths.len()
+                    ))
+                    .clicked()
+            {
+                let batch = batch.clone();
+                self.submit_job(Job::Undo(batch));
+            }
+
+            if self.busy {
+                ui.horizontal(|ui| {
+                    ui_spinner(ui);
+                    let total = self.jobs_done + 1 + self.job_queue.len();
+                    if total > 1 {
+                        ui.label(format!("处理中... {}/{} 任务", self.jobs_done + 1, total));
+                    } else {
+                        ui.label("处理中...");
+                    }
+                    if ui.button("取消").clicked() {
+                        self.handle_cancel();
+                    }
+                });
+                if let Some((phase, done, total)) = self.progress
+                    && total > 0
+                {
+                    let label = match phase {
+                        ProgressPhase::Index => "索引字体",
+                        ProgressPhase::Load => "加载字体",
+                        ProgressPhase::Download => "下载链接",
+                    };
+                    ui.add(
+                        egui::ProgressBar::new(done as f32 / total as f32)
+                            .text(format!("{} {}/{}", label, done, total)),
+                    );
+                }
+            }
+
+            if !self.job_queue.is_empty() {
+                ui.label(format!("排队中的任务: {}", self.job_queue.len()));
+                let mut remove = None;
+                for (idx, job) in self.job_queue.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        if ui.small_button("移除").clicked() {
+                            remove = Some(idx);
+                        }
+                        ui.label(format!("{}. {}", idx + 1, job.label()));
+                    });
+                }
+                if let Some(idx) = remove {
+                    self.remove_queued_job(idx);
+                }
+            }
+        });
+    }
+
+    fn ui_diff_preview(&mut self, ctx: &egui::Context) {
+        let Some(preview) = &self.diff_preview else {
+            return;
+        };
+        let mut close = false;
+        let mut proceed = false;
+        let modal = egui::Modal::new(egui::Id::new("diff_preview")).show(ctx, |ui| {
+            ui.set_width(560.0);
+            ui.heading("预览差异");
+            ui.columns(2, |cols| {
+                let sections = [
+                    ("缺失字体", &preview.missing),
+                    ("未使用字体", &preview.unused),
+                ];
+                for (ui, (title, names)) in cols.iter_mut().zip(sections) {
+                    ui.label(format!("{} ({})", title, names.len()));
+                    egui::ScrollArea::vertical()
+                        .id_salt(title)
+                        .max_height(320.0)
+                        .auto_shrink([false, true])
+                        .show(ui, |ui| {
+                            for name in names {
+                                ui.label(name);
+                            }
+                        });
+                }
+            });
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui.button("继续加载").clicked() {
+                    proceed = true;
+                }
+                if ui.button("关闭").clicked() {
+                    close = true;
+                }
+            });
+        });
+        if proceed {
+            self.diff_preview = None;
+            self.handle_process_pending();
+        } else if close || modal.should_close() {
+            self.diff_preview = None;
+        }
+    }
+
+    fn ui_system_coverage(&mut self, ctx: &egui::Context) {
+        let Some(coverage) = &self.system_coverage else {
+            return;
+        };
+        let mut close = false;
+        let modal = egui::Modal::new(egui::Id::new("system_coverage")).show(ctx, |ui| {
+            ui.set_width(560.0);
+            ui.heading("系统字体覆盖");
+            ui.weak("按纯净系统计算：不含本程序加载的字体");
+            let covered: Vec<String> = coverage
+                .covered
+                .iter()
+                .map(|(font, name)| {
+                    if font.eq_ignore_ascii_case(name) {
+                        font.clone()
+                    } else {
+                        format!("{} ({})", font, name)
+                    }
+                })
+                .collect();
+            let bundle: Vec<String> = coverage
+                .bundle
+                .iter()
+                .map(|(font, file)| match file {
+                    Some(file) => format!("{} > {}", font, file),
+                    None => format!("{} (未找到)", font),
+                })
+                .collect();
+            ui.columns(2, |cols| {
+                let sections = [("系统已安装", &covered), ("需要打包", &bundle)];
+                for (ui, (title, names)) in cols.iter_mut().zip(sections) {
+                    ui.label(format!("{} ({})", title, names.len()));
+                    egui::ScrollArea::vertical()
+                        .id_salt(title)
+                        .max_height(320.0)
+                        .auto_shrink([false, true])
+                        .show(ui, |ui| {
+                            for name in names {
+                                ui.label(name);
+                            }
+                        });
+                }
+            });
+            ui.separator();
+            if ui.button("关闭").clicked() {
+                close = true;
+            }
+        });
+        if close || modal.should_close() {
+            self.system_coverage = None;
+        }
+    }
+
+    fn ui_results(&mut self, ui: &mut egui::Ui) -> Option<RowAction> {
+        let Some(summary) = &self.last_summary else {
+            ui.label("暂无处理结果");
+            return None;
+        };
+        if summary.entries.is_empty() {
+            ui.label("暂无处理结果");
+            return None;
+        }
+        let missing = summary
+            .entries
+            .iter()
+            .filter(|e| e.status == EntryStatus::Missing)
+            .count();
+        let packable = summary.entries.iter().any(|e| {
+            matches!(
+                e.status,
+                EntryStatus::Loaded | EntryStatus::Duplicate | EntryStatus::MissingVariant
+            )
+        });
+        let mut export_report = false;
+        let mut export_html = false;
+        let mut find_missing = false;
+        let mut everything = false;
+        let mut export_pack = false;
+        ui.horizontal(|ui| {
+            ui.label(format!("缺失字体: {}", missing));
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                export_pack = ui
+                    .add_enabled(packable, egui::Button::new("打包字体"))
+                    .on_hover_text("把本次加载的字体文件连同说明打包为 ZIP")
+                    .clicked();
+                export_html = ui
+                    .button("导出 HTML 报告")
+                    .on_hover_text(
+                        "把摘要、每个字体的结果和各字幕的覆盖率保存为单个 HTML 文件，便于分享",
+                    )
+                    .clicked();
+                export_report = ui
+                    .add_enabled(missing > 0, egui::Button::new("导出缺失字体报告"))
+                    .on_hover_text("生成带搜索链接的 HTML 或 Markdown 报告")
+                    .clicked();
+                find_missing = ui
+                    .add_enabled(missing > 0, egui::Button::new("查找缺失字体"))
+                    .on_hover_text("选择一个文件夹，只在其中查找并加载缺失的字体，不影响待处理列表")
+                    .clicked();
+                if self.config.everything_enabled {
+                    everything = ui
+                        .add_enabled(missing > 0, egui::Button::new("用 Everything 查找"))
+                        .on_hover_text("按文件名在所有磁盘上搜索缺失字体，确认后再加载")
+                        .clicked();
+                }
+            });
+        });
+        let command = ui.input(|i| i.modifiers.command);
+        let show_meta = self.config.show_font_meta;
+        let mut action = None;
+        egui::ScrollArea::vertical()
+            .auto_shrink([false; 2])
+            .show(ui, |ui| {
+                if !summary.subtitles.is_empty() {
+                    egui::CollapsingHeader::new(format!(
+                        "字幕覆盖率 ({})",
+                        summary.subtitles.len()
+                    ))
+                    .default_open(true)
+                    .show(ui, |ui| {
+                        ui_subtitle_coverage(ui, &summary.subtitles, &self.processed_dirs);
+                    });
+                }
+                egui::Grid::new("results_grid")
+                    .num_columns(3)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for (idx, entry) in summary.entries.iter().enumerate() {
+                            ui.colored_label(
+                                status_color(ui.visuals(), entry.status),
+                                entry_status_label(entry.status),
+                            );
+                            let response = ui.selectable_label(
+                                self.selected_results.contains(&idx),
+                                display_font_names(
+                                    std::slice::from_ref(&entry.name),
+                                    &entry.families,
+                                ),
+                            );
+                            update_selection(&mut self.selected_results, idx, &response, command);
+                            ui.horizontal(|ui| {
+                                ui.label(entry.path.as_deref().unwrap_or("-"));
+                                if show_meta && let Some(path) = &entry.path {
+                                    let meta = cached_font_meta(&mut self.font_meta, path);
+                                    if !meta.is_empty() {
+                                        ui.weak(meta);
+                                    }
+                                }
+                                if !entry.conflicts.is_empty() {
+                                    ui.colored_label(ui.visuals().error_fg_color, "将覆盖系统字体")
+                                        .on_hover_text(entry.conflicts.join("\n"));
+                                }
+                                if !entry.alternatives.is_empty() {
+                                    ui.colored_label(ui.visuals().warn_fg_color, "多个版本")
+                                        .on_hover_ui(|ui| {
+                                            ui.label("其他匹配文件:");
+                                            for path in &entry.alternatives {
+                                                let meta = if show_meta {
+                                                    cached_font_meta(&mut self.font_meta, path)
+                                                } else {
+                                                    String::new()
+                                                };
+                                                if meta.is_empty() {
+                                                    ui.label(path.as_str());
+                                                } else {
+                                                    ui.label(format!("{}  ({})", path, meta));
+                                                }
+                                            }
+                                        });
+                                }
+                                if !entry.missing_variants.is_empty() {
+                                    ui.colored_label(
+                                        ui.visuals().warn_fg_color,
+                                        format!("缺少{}", entry.missing_variants.join("、")),
+                                    )
+                                    .on_hover_text("字幕用到了该变体，但匹配的文件都不提供");
+                                }
+                                if let Some(dir) = &entry.local_dir {
+                                    ui.colored_label(ui.visuals().warn_fg_color, "使用库中副本")
+                                        .on_hover_text(format!(
+                                            "字幕旁的字体目录中没有可用的匹配，本地副本可能解析失败:\n{}",
+                                            dir
+                                        ));
+                                }
+                            });
+                            ui.end_row();
+                            response.context_menu(|ui| {
+                                let selected: Vec<&FontEntry> = summary
+                                    .entries
+                                    .iter()
+                                    .enumerate()
+                                    .filter(|(i, _)| self.selected_results.contains(i))
+                                    .map(|(_, e)| e)
+                                    .collect();
+                                let names = selected.iter().map(|e| e.name.clone()).collect();
+                                let paths: Vec<String> =
+                                    selected.iter().filter_map(|e| e.path.clone()).collect();
+                                let unloadable = paths
+                                    .iter()
+                                    .filter(|p| self.loaded_view.contains_key(*p))
+                                    .cloned()
+                                    .collect();
+                                if let Some(a) = row_context_menu(ui, names, paths, unloadable) {
+                                    action = Some(a);
+                                }
+                            });
+                        }
+                    });
+            });
+        if export_report {
+            self.handle_export_missing_report();
+        }
+        if export_html {
+            self.handle_export_html_report();
+        }
+        if export_pack {
+            self.handle_export_font_pack();
+        }
+        if find_missing {
+            self.handle_find_missing();
+        }
+        if everything {
+            self.handle_everything_search();
+        }
+        action
+    }
+
+    fn ui_loaded(&mut self, ui: &mut egui::Ui) -> Option<RowAction> {
+        ui.horizontal(|ui| {
+            ui.label(format!("已加载字体文件: {}", self.loaded_view.len()));
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if ui
+                    .button("导入清单")
+                    .on_hover_text("按清单重新加载其中记录的字体文件")
+                    .clicked()
+                {
+                    self.handle_import_manifest();
+                }
+                if ui
+                    .button("导出清单")
+                    .on_hover_text("导出为 JSON 清单，或选择 .bat 生成可直接双击恢复的脚本")
+                    .clicked()
+                {
+                    self.handle_export_manifest();
+                }
+            });
+        });
+
+        let filter = filter_key(&self.loaded_filter);
+        let visible: Vec<String> = self
+            .loaded_view
+            .iter()
+            .filter(|(path, font)| {
+                filter.is_empty()
+                    || filter_key(path).contains(&filter)
+                    || font
+                        .names
+                        .iter()
+                        .chain(&font.families)
+                        .any(|name| filter_key(name).contains(&filter))
+            })
+            .map(|(path, _)| path.clone())
+            .collect();
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut self.loaded_filter)
+                    .hint_text("筛选字体名或路径")
+                    .desired_width(240.0),
+            );
+            if !filter.is_empty() {
+                ui.label(format!("{}/{}", visible.len(), self.loaded_view.len()));
+            }
+            ui.checkbox(&mut self.apply_to_filtered, "仅作用于筛选结果")
+                .on_hover_text("勾选后全选、全部卸载只针对当前显示的条目");
+            let scoped = self.apply_to_filtered && !filter.is_empty();
+            if ui.button("全选").clicked() {
+                self.selected_loaded = if scoped {
+                    visible.iter().cloned().collect()
+                } else {
+                    self.loaded_view.keys().cloned().collect()
+                };
+            }
+            if ui
+                .add_enabled(!self.loaded_view.is_empty(), egui::Button::new("全部卸载"))
+                .clicked()
+            {
+                if scoped {
+                    self.handle_unload_paths(visible.clone());
+                } else {
+                    self.handle_unload();
+                }
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("测试文字");
+            ui.add(egui::TextEdit::singleline(&mut self.sample_text).desired_width(240.0));
+            let target = visible
+                .iter()
+                .find(|path| self.selected_loaded.contains(*path))
+                .cloned();
+            if ui
+                .add_enabled(target.is_some(), egui::Button::new("渲染"))
+                .on_hover_text("用选中的第一个字体显示测试文字")
+                .clicked()
+                && let Some(path) = target
+            {
+                self.handle_render_sample(ui.ctx(), &path);
+            }
+        });
+        if let Some(family) = &self.sample_family {
+            let family = egui::FontFamily::Name(Arc::from(family.as_str()));
+            // set_fonts 在下一帧才生效，之前引用该家族会 panic
+            if ui.ctx().fonts(|f| f.families().contains(&family)) {
+                let color = ui.visuals().text_color();
+                let galley = ui.ctx().fonts(|f| {
+                    f.layout_no_wrap(
+                        self.sample_text.clone(),
+                        egui::FontId::new(24.0, family),
+                        color,
+                    )
+                });
+                let (rect, _) = ui.allocate_exact_size(galley.size(), egui::Sense::hover());
+                ui.painter().galley(rect.min, galley, color);
+            }
+        }
+
+        if !self.system_library.is_empty() {
+            let rows: Vec<&(String, String)> = self
+                .system_library
+                .iter()
+                .filter(|(name, path)| {
+                    filter.is_empty()
+                        || filter_key(name).contains(&filter)
+                        || filter_key(path).contains(&filter)
+                })
+                .collect();
+            egui::CollapsingHeader::new(format!("系统字体库 ({})", self.system_library.len()))
+                .id_salt("system_library")
+                .show(ui, |ui| {
+                    let row_height = ui.text_style_height(&egui::TextStyle::Body);
+                    egui::ScrollArea::vertical()
+                        .id_salt("system_library_scroll")
+                        .max_height(200.0)
+                        .show_rows(ui, row_height, rows.len(), |ui, range| {
+                            for (name, path) in &rows[range] {
+                                ui.weak(name.as_str()).on_hover_text(path.as_str());
+                            }
+                        });
+                });
+        }
+
+        let command = ui.input(|i| i.modifiers.command);
+        let mut action = None;
+        egui::ScrollArea::vertical()
+            .auto_shrink([false; 2])
+            .show(ui, |ui| {
+                for path in &visible {
+                    let Some(font) = self.loaded_view.get(path) else {
+                        continue;
+                    };
+                    let text = format!(
+                        "{}  {}",
+                        display_font_names(&font.names, &font.families),
+                        relative_display_path(Path::new(path), &self.processed_dirs)
+                    );
+                    let response = ui
+                        .selectable_label(self.selected_loaded.contains(path), text)
+                        .on_hover_text(path);
+                    update_selection(&mut self.selected_loaded, path.clone(), &response, command);
+                    response.context_menu(|ui| {
+                        let selected: Vec<(&String, &LoadedFont)> = self
+                            .loaded_view
+                            .iter()
+                            .filter(|(p, _)| self.selected_loaded.contains(*p))
+                            .collect();
+                        let names = selected
+                            .iter()
+                            .flat_map(|(_, f)| f.names.iter().cloned())
+                            .collect();
+                        let paths: Vec<String> =
+                            selected.iter().map(|(p, _)| (*p).clone()).collect();
+                        if let Some(a) = row_context_menu(ui, names, paths.clone(), paths) {
+                            action = Some(a);
+                        }
+                    });
+                }
+            });
+        action
+    }
+
+    fn ui_settings(&mut self, ui: &mut egui::Ui) {
+        let mut changed = false;
+        let mut watch_changed = false;
+        let mut http_changed = false;
+        egui::Grid::new("settings_grid")
+            .num_columns(2)
+            .spacing([16.0, 8.0])
+            .show(ui, |ui| {
+                ui.label("加载重试次数");
+                changed |= ui
+                    .add(egui::DragValue::new(&mut self.config.load_retries).range(0..=10))
+                    .on_hover_text("字体文件仍在被其他程序写入（下载、复制中）时，等待后重试的次数")
+                    .changed();
+                ui.end_row();
+
+                ui.label("日志候选上限");
+                changed |= ui
+                    .add(
+                        egui::DragValue::new(&mut self.config.max_logged_candidates)
+                            .range(1..=100),
+                    )
+                    .on_hover_text("同名字体有多个候选文件或与多个系统字体冲突时，每条日志最多列出的数量")
+                    .changed();
+                ui.end_row();
+
+                ui.label("字体版本信息");
+                changed |= ui
+                    .checkbox(&mut self.config.show_font_meta, "在结果中显示")
+                    .on_hover_text("显示匹配文件 name 表中的版本 (ID 5)、厂商 (ID 8) 和 head 表的修订号，便于区分同名字体")
+                    .changed();
+                ui.end_row();
+
+                ui.label("字体面警告阈值");
+                changed |= ui
+                    .add(
+                        egui::DragValue::new(&mut self.config.face_warn_threshold)
+                            .range(1..=100_000),
+                    )
+                    .on_hover_text("本程序注册的字体面数达到该值时显示警告")
+                    .changed();
+                ui.end_row();
+
+                ui.label("注册范围");
+                ui.horizontal(|ui| {
+                    // 注销必须与注册方式一致，有已加载的字体时不能切换
+                    ui.add_enabled_ui(self.loaded_view.is_empty(), |ui| {
+                        changed |= ui
+                            .radio_value(&mut self.config.load_mode, LoadMode::Public, "所有程序")
+                            .on_hover_text("播放器等其他程序都能使用加载的字体")
+                            .changed();
+                        changed |= ui
+                            .radio_value(&mut self.config.load_mode, LoadMode::Private, "仅本程序")
+                            .on_hover_text("只检查匹配和注册结果，其他程序看不到这些字体，退出后自动注销")
+                            .changed();
+                    });
+                });
+                ui.end_row();
+
+                ui.label("卸载后验证");
+                changed |= ui
+                    .checkbox(&mut self.config.verify_unload, "检查字体是否仍可枚举")
+                    .on_hover_text("卸载后逐个检查字体家族名，仍存在的记为 [still-present]")
+                    .changed();
+                ui.end_row();
+
+                ui.label("字体名匹配");
+                changed |= ui
+                    .checkbox(&mut self.config.case_sensitive, "区分大小写")
+                    .on_hover_text("优先使用大小写完全一致的字体名，找不到时仍按不区分大小写匹配")
+                    .changed();
+                ui.end_row();
+
+                ui.label("字形变体");
+                changed |= ui
+                    .checkbox(&mut self.config.strict_style, "严格检查粗体/斜体")
+                    .on_hover_text(
+                        "字幕用到粗体或斜体、但匹配的文件都不提供该变体时，条目记为 [variant] 而不是已载入",
+                    )
+                    .changed();
+                ui.end_row();
+
+                ui.label("与系统字体同名");
+                changed |= ui
+                    .checkbox(&mut self.config.skip_system_conflicts, "跳过加载")
+                    .on_hover_text(
+                        "字体文件的家族名与系统已安装字体相同时不加载，避免在会话期间覆盖系统字体",
+                    )
+                    .changed();
+                ui.end_row();
+
+                ui.label("取消任务时");
+                ui.horizontal(|ui| {
+                    changed |= ui
+                        .radio_value(&mut self.config.cancel_clears_queue, false, "继续下一个")
+                        .changed();
+                    changed |= ui
+                        .radio_value(&mut self.config.cancel_clears_queue, true, "清空队列")
+                        .changed();
+                });
+                ui.end_row();
+
+                ui.label("字体目录名");
+                let response = ui
+                    .add(
+                        egui::TextEdit::singleline(&mut self.font_folders_text)
+                            .hint_text("Fonts, 字体, attachments"),
+                    )
+                    .on_hover_text("输入目录或字幕所在目录下的同名子目录总是一并扫描，逗号分隔");
+                if response.changed() {
+                    self.config.font_folders = parse_name_list(&self.font_folders_text);
+                    changed = true;
+                }
+                ui.end_row();
+
+                ui.label("打包字体");
+                changed |= ui
+                    .checkbox(&mut self.config.pack_skip_standard, "跳过系统自带字体")
+                    .on_hover_text("不打包下面列出的标准字体，以及与本机已安装字体同名的字体")
+                    .changed();
+                ui.end_row();
+
+                ui.label("标准字体");
+                let response = ui
+                    .add(
+                        egui::TextEdit::singleline(&mut self.standard_fonts_text)
+                            .hint_text("Arial, Times New Roman, 微软雅黑"),
+                    )
+                    .on_hover_text("打包时视为系统自带的字体名，不区分大小写，逗号分隔");
+                if response.changed() {
+                    self.config.standard_fonts = parse_name_list(&self.standard_fonts_text);
+                    changed = true;
+                }
+                ui.end_row();
+
+                ui.label("字体名规则");
+                let response = ui
+                    .add(
+                        egui::TextEdit::multiline(&mut self.name_rules_text)
+                            .desired_rows(3)
+                            .hint_text("每行一条，如 ^FZ => 方正 或 (.+)_GBK$ => $1"),
+                    )
+                    .on_hover_text(
+                        "匹配前按顺序改写字幕和字体两侧的字体名，写法为 \"正则 => 替换\"；\
+                         所有匹配处都会替换，替换中可用 $1 或 ${1} 引用分组，区分大小写。\
+                         系统已安装字体的名称不改写",
+                    );
+                if response.changed() {
+                    self.config.name_rules = parse_name_rules(&self.name_rules_text);
+                    self.set_name_rules();
+                    changed = true;
+                }
+                ui.end_row();
+                let errors = name_rule_errors(&self.config.name_rules);
+                if !errors.is_empty() {
+                    ui.label("");
+                    ui.vertical(|ui| {
+                        for err in errors {
+                            ui.colored_label(
+                                ui.visuals().error_fg_color,
+                                format!("规则无效，已忽略 {err}"),
+                            );
+                        }
+                    });
+                    ui.end_row();
+                }
+
+                ui.label("字体别名");
+                let response = ui
+                    .add(
+                        egui::TextEdit::multiline(&mut self.font_aliases_text)
+                            .desired_rows(3)
+                            .hint_text("每行一条，如 方正兰亭圆_GBK => 方正兰亭圆简体"),
+                    )
+                    .on_hover_text(
+                        "字幕中的字体名 => 实际查找的字体名，不区分大小写；也可在结果页右键添加",
+                    );
+                if response.changed() {
+                    self.config.font_aliases = parse_font_aliases(&self.font_aliases_text);
+                    changed = true;
+                }
+                ui.end_row();
+
+                ui.label("排除的字体文件");
+                let response = ui
+                    .add(
+                        egui::TextEdit::multiline(&mut self.excluded_fonts_text)
+                            .desired_rows(2)
+                            .hint_text("每行一个路径"),
+                    )
+                    .on_hover_text("处理时不匹配这些文件；可在结果页或已加载页右键\"排除此文件\"");
+                if response.changed() {
+                    self.config.excluded_fonts = self
+                        .excluded_fonts_text
+                        .lines()
+                        .map(str::trim)
+                        .filter(|line| !line.is_empty())
+                        .map(String::from)
+                        .collect();
+                    changed = true;
+                }
+                ui.end_row();
+
+                ui.label("自动加载");
+                watch_changed |= ui
+                    .checkbox(&mut self.config.auto_load, "播放器启动时加载其目录")
+                    .on_hover_text(
+                        "检测到下列进程启动时，从命令行取出打开的文件所在目录，目录中有字幕就自动加载；每次判断都记录为 [auto] 日志",
+                    )
+                    .changed();
+                ui.end_row();
+
+                ui.label("播放器进程名");
+                let response = ui
+                    .add(
+                        egui::TextEdit::singleline(&mut self.auto_load_text)
+                            .hint_text("mpv.exe, PotPlayerMini64.exe"),
+                    )
+                    .on_hover_text("逗号分隔，不区分大小写");
+                if response.changed() {
+                    self.config.auto_load_processes = parse_name_list(&self.auto_load_text);
+                    watch_changed = true;
+                }
+                ui.end_row();
+
+                ui.label("播放器");
+                ui.horizontal(|ui| {
+                    changed |= ui
+                        .add(
+                            egui::TextEdit::singleline(&mut self.config.player_path)
+                                .hint_text("首次使用时选择")
+                                .desired_width(240.0),
+                        )
+                        .changed();
+                    if ui.button("浏览").clicked() {
+                        self.open_dialog(
+                            player_dialog(),
+                            DialogAction::PickFile,
+                            DialogPurpose::PickPlayer,
+                        );
+                    }
+                });
+                ui.end_row();
+
+                ui.label("界面字体");
+                ui.horizontal(|ui| {
+                    if self.config.ui_font_path.is_empty() {
+                        ui.weak("自动");
+                    } else {
+                        ui.label(&self.config.ui_font_path);
+                    }
+                    if ui.button("浏览").clicked() {
+                        self.open_dialog(
+                            rfd::FileDialog::new().add_filter("字体", &["ttf", "otf", "ttc"]),
+                            DialogAction::PickFile,
+                            DialogPurpose::PickUiFont,
+                        );
+                    }
+                    if ui
+                        .add_enabled(
+                            !self.config.ui_font_path.is_empty(),
+                            egui::Button::new("恢复自动"),
+                        )
+                        .on_hover_text("按微软雅黑、黑体、微软正黑体、Noto Sans CJK 的顺序自动选择")
+                        .clicked()
+                    {
+                        self.config.ui_font_path.clear();
+                        changed = true;
+                        self.apply_ui_font();
+                    }
+                });
+                ui.end_row();
+
+                ui.label("播放器参数");
+                changed |= ui
+                    .text_edit_singleline(&mut self.config.player_args)
+                    .on_hover_text("按空格分隔，%f 替换为要打开的文件；不含 %f 时文件追加在最后")
+                    .changed();
+                ui.end_row();
+
+                ui.label("HTTP 接口");
+                ui.horizontal(|ui| {
+                    http_changed |= ui
+                        .checkbox(&mut self.config.http_enabled, "启用")
+                        .on_hover_text(
+                            "只监听 127.0.0.1。POST /load 正文为 {\"paths\": [...]}，POST /unload 卸载全部，GET /status 返回已加载字体和忙碌状态",
+                        )
+                        .changed();
+                    ui.label("端口");
+                    http_changed |= ui
+                        .add(egui::DragValue::new(&mut self.config.http_port).range(1024..=65535))
+                        .changed();
+                });
+                ui.end_row();
+
+                ui.label("HTTP 密钥");
+                http_changed |= ui
+                    .add(
+                        egui::TextEdit::singleline(&mut self.config.http_token)
+                            .password(true)
+                            .hint_text("留空则不校验"),
+                    )
+                    .on_hover_text("设置后请求须带 X-Fontloader-Token 头；未设置时拒绝来自浏览器网页（带 Origin 头）的请求")
+                    .changed();
+                ui.end_row();
+
+                ui.label("拖入链接");
+                changed |= ui
+                    .checkbox(&mut self.config.download_dropped_urls, "下载字幕和字体链接")
+                    .on_hover_text(format!(
+                        "从浏览器拖入指向 .ass/.ssa/.ttf/.otf/.ttc 的链接时下载到临时目录并加入待处理，单个文件不超过 {}",
+                        format_bytes(MAX_DOWNLOAD_BYTES)
+                    ))
+                    .changed();
+                ui.end_row();
+
+                ui.label("Everything");
+                ui.horizontal(|ui| {
+                    changed |= ui
+                        .checkbox(&mut self.config.everything_enabled, "启用")
+                        .on_hover_text(
+                            "在结果页提供“用 Everything 查找”，需在 Everything 的 工具 > 选项 > HTTP 服务器 中启用",
+                        )
+                        .changed();
+                    ui.label("端口");
+                    changed |= ui
+                        .add(egui::DragValue::new(&mut self.config.everything_port).range(1..=65535))
+                        .changed();
+                });
+                ui.end_row();
+
+                ui.label("配置文件");
+                if ui
+                    .checkbox(&mut self.config.watch_config, "外部修改后自动重载")
+                    .changed()
+                {
+                    self.restart_config_watch();
+                    changed = true;
+                }
+                ui.end_row();
+
+                ui.label("结构化日志");
+                ui.horizontal(|ui| {
+                    let current = self.config.structured_log_path.clone();
+                    match &current {
+                        Some(path) => ui.label(path.to_string_lossy()),
+                        None => ui.weak("关闭"),
+                    };
+                    if ui
+                        .button("浏览")
+                        .on_hover_text(
+                            "每条日志产生后立即追加为一行 JSON：{\"level\", \"message\", \"elapsed_ms\"}，供外部工具读取",
+                        )
+                        .clicked()
+                    {
+                        self.open_dialog(
+                            rfd::FileDialog::new()
+                                .add_filter("JSON Lines", &["jsonl", "ndjson"])
+                                .set_file_name("fontloader-log.jsonl"),
+                            DialogAction::SaveFile,
+                            DialogPurpose::PickStructuredLog,
+                        );
+                    }
+                    if ui
+                        .add_enabled(current.is_some(), egui::Button::new("关闭"))
+                        .clicked()
+                    {
+                        self.set_structured_log(None);
+                    }
+                });
+                ui.end_row();
+
+                ui.label("扫描子目录层数");
+                ui.horizontal(|ui| {
+                    let mut limited = self.config.max_depth.is_some();
+                    if ui
+                        .checkbox(&mut limited, "限制")
+                        .on_hover_text("拖入目录时只进入指定层数的子目录，0 只扫描目录本身；不限制时扫描全部子目录")
+                        .changed()
+                    {
+                        self.config.max_depth = limited.then_some(2);
+                        changed = true;
+                    }
+                    if let Some(depth) = self.config.max_depth.as_mut() {
+                        changed |= ui
+                            .add(egui::DragValue::new(depth).range(0..=32))
+                            .changed();
+                    }
+                });
+                ui.end_row();
+
+                ui.label("别名表文件");
+                ui.horizontal(|ui| {
+                    let current = self.config.alias_map_path.clone();
+                    match &current {
+                        Some(path) => ui.label(path.to_string_lossy()),
+                        None => ui.weak("未使用"),
+                    };
+                    if ui
+                        .button("浏览")
+                        .on_hover_text(
+                            "JSON 对象，键为字幕中的字体名，值为字体文件中的名称；每次处理时读入，与上面的别名合并",
+                        )
+                        .clicked()
+                    {
+                        self.open_dialog(
+                            rfd::FileDialog::new().add_filter("JSON", &["json"]),
+                            DialogAction::PickFile,
+                            DialogPurpose::PickAliasMap,
+                        );
+                    }
+                    if ui
+                        .add_enabled(current.is_some(), egui::Button::new("停用"))
+                        .clicked()
+                    {
+                        self.set_alias_map(None);
+                    }
+                });
+                ui.end_row();
+
+                ui.label("缺失自动重试");
+                ui.horizontal(|ui| {
+                    if ui
+                        .checkbox(&mut self.config.auto_retry_missing, "监视输入目录")
+                        .on_hover_text(
+                            "加载后仍有缺失字体时监视拖入的目录，放入能补上缺失字体的文件后自动执行\"查找缺失字体\"",
+                        )
+                        .changed()
+                    {
+                        if !self.config.auto_retry_missing {
+                            self.missing_watch = None;
+                        }
+                        changed = true;
+                    }
+                    ui.label("最多");
+                    changed |= ui
+                        .add(egui::DragValue::new(&mut self.config.auto_retry_limit).range(1..=50))
+                        .changed();
+                    ui.label("次");
+                });
+                ui.end_row();
+
+                ui.label("缓存有效期");
+                ui.horizontal(|ui| {
+                    let response = ui
+                        .add(
+                            egui::DragValue::new(&mut self.config.cache_ttl_days)
+                                .range(0..=MAX_CACHE_TTL_DAYS),
+                        )
+                        .on_hover_text("缓存条目写入超过该天数后重新解析字体文件，0 表示不过期");
+                    if response.changed() {
+                        changed = true;
+                    }
+                    ui.label("天");
+                });
+                ui.end_row();
+
+                ui.label("字体名缓存");
+                if ui
+                    .add_enabled(!self.busy, egui::Button::new("整理缓存"))
+                    .on_hover_text("删除已不存在或已改动的字体文件的缓存条目，并重写缓存文件")
+                    .clicked()
+                {
+                    self.handle_compact_cache();
+                }
+                ui.end_row();
+
+                ui.label("当前已注册字体面");
+                ui.label(self.registered_faces.to_string());
+                ui.end_row();
+
+                ui.label("GDI 对象");
+                match self.gdi_usage {
+                    Some((current, peak)) => {
+                        let mut bar = egui::ProgressBar::new(current as f32 / GDI_HANDLE_LIMIT as f32)
+                            .desired_width(240.0)
+                            .text(format!("{} / {} (峰值 {})", current, GDI_HANDLE_LIMIT, peak));
+                        if current >= GDI_WARN_OBJECTS {
+                            bar = bar.fill(ui.visuals().warn_fg_color);
+                        }
+                        ui.add(bar).on_hover_text(
+                            "本进程当前占用的 GDI 对象数，默认每个进程上限 10000，接近上限时界面绘制和字体操作会失败",
+                        );
+                    }
+                    None => {
+                        ui.weak("无法读取");
+                    }
+                }
+                ui.end_row();
+
+                ui.label("本次会话载入成功率");
+                match self.session_success_rate.mean() {
+                    Some(rate) => ui.label(format!(
+                        "{:.1}% ({}次操作)",
+                        rate * 100.0,
+                        self.session_success_rate.count
+                    )),
+                    None => ui.label("暂无"),
+                };
+                ui.end_row();
+            });
+        if watch_changed {
+            self.restart_process_watcher();
+        }
+        if http_changed {
+            self.restart_http_server();
+        }
+        if (changed || watch_changed || http_changed)
+            && let Err(err) = save_config(&self.config)
+        {
+            self.logs.push(format!("[X] 保存设置失败: {}", err));
+        }
+
+        ui.add_space(16.0);
+        ui.horizontal(|ui| {
+            if ui
+                .button("重置所有设置")
+                .on_hover_text("恢复默认设置，包括主题和窗口置顶")
+                .clicked()
+            {
+                self.confirm_reset = true;
+            }
+            if ui
+                .button("导出配置")
+                .on_hover_text("把设置保存为一个 JSON 文件，不含字体名缓存、会话状态和 HTTP 密钥")
+                .clicked()
+            {
+                self.handle_export_config();
+            }
+            if ui
+                .button("重载配置")
+                .on_hover_text("重新读取程序目录下的 config.json，适合手动编辑配置文件后使用")
+                .clicked()
+            {
+                self.reload_config(false);
+            }
+            if ui
+                .button("导入配置")
+                .on_hover_text("读取导出的配置文件，确认改动后应用")
+                .clicked()
+            {
+                self.handle_import_config();
+            }
+        });
+    }
+
+    fn ui_alias_edit(&mut self, ctx: &egui::Context) {
+        let Some(dialog) = &mut self.alias_edit else {
+            return;
+        };
+        let mut save = false;
+        let mut close = false;
+        let modal = egui::Modal::new(egui::Id::new("alias_edit")).show(ctx, |ui| {
+            ui.set_width(480.0);
+            ui.heading("添加别名");
+            ui.label(format!("字幕中的字体名: {}", dialog.name));
+            ui.horizontal(|ui| {
+                ui.label("按此名称查找");
+                let response = ui.add(
+                    egui::TextEdit::singleline(&mut dialog.target)
+                        .hint_text("字体文件中的名称")
+                        .desired_width(280.0),
+                );
+                if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                    save = true;
+                }
+            });
+            ui.weak("留空则删除该字体名的别名；下次处理时生效");
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui.button("保存").clicked() {
+                    save = true;
+                }
+                if ui.button("取消").clicked() {
+                    close = true;
+                }
+            });
+        });
+        if save {
+            if let Some(dialog) = self.alias_edit.take() {
+                self.set_font_alias(dialog.name, dialog.target);
+            }
+        } else if close || modal.should_close() {
+            self.alias_edit = None;
+        }
+    }
+
+    fn ui_clean_by_name(&mut self, ctx: &egui::Context) {
+        let busy = self.busy;
+        let Some(dialog) = &mut self.clean_by_name else {
+            return;
+        };
+        let mut find = false;
+        let mut clean = false;
+        let mut close = false;
+        let modal = egui::Modal::new(egui::Id::new("clean_by_name")).show(ctx, |ui| {
+            ui.set_width(560.0);
+            ui.heading("按字体名强制清理");
+            ui.horizontal(|ui| {
+                ui.label("字体名");
+                let response = ui.add(
+                    egui::TextEdit::singleline(&mut dialog.name)
+                        .hint_text("如 思源黑体 CN")
+                        .desired_width(280.0),
+                );
+                let enter = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                let can_find = !busy && !dialog.name.trim().is_empty();
+                if (ui
+                    .add_enabled(can_find, egui::Button::new("查找"))
+                    .clicked()
+                    || enter)
+                    && can_find
+                {
+                    find = true;
+                }
+            });
+            ui.weak("在字体名缓存、本次会话的字体库和处理过的目录中查找");
+            if let Some(candidates) = &mut dialog.candidates {
+                ui.separator();
+                if candidates.is_empty() {
+                    ui.label("没有找到匹配的字体文件");
+                }
+                egui::ScrollArea::vertical()
+                    .max_height(280.0)
+                    .auto_shrink([false, true])
+                    .show(ui, |ui| {
+                        for (path, checked) in candidates.iter_mut() {
+                            ui.checkbox(checked, path.as_str());
+                        }
+                    });
+            }
+            ui.separator();
+            ui.horizontal(|ui| {
+                let any_checked = dialog
+                    .candidates
+                    .as_ref()
+                    .is_some_and(|c| c.iter().any(|(_, checked)| *checked));
+                if ui
+                    .add_enabled(any_checked, egui::Button::new("清理选中"))
+                    .on_hover_text("对每个文件反复调用 RemoveFontResourceW，直到不再有注册")
+                    .clicked()
+                {
+                    clean = true;
+                }
+                if ui.button("关闭").clicked() {
+                    close = true;
+                }
+            });
+        });
+        if find {
+            let name = dialog.name.trim().to_string();
+            dialog.candidates = None;
+            let dirs = self.processed_dirs.clone();
+            let use_cache = self.mode == Mode::Normal;
+            self.submit_job(Job::FindFontFiles {
+                name,
+                dirs,
+                use_cache,
+            });
+        } else if clean {
+            let paths = dialog
+                .candidates
+                .take()
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|(_, checked)| *checked)
+                .map(|(path, _)| path)
+                .collect();
+            self.clean_by_name = None;
+            self.submit_job(Job::CleanFiles(paths));
+        } else if close || modal.should_close() {
+            self.clean_by_name = None;
+        }
+    }
+
+    fn ui_everything_search(&mut self, ctx: &egui::Context) {
+        let busy = self.busy;
+        let Some(dialog) = &mut self.everything_search else {
+            return;
+        };
+        let mut load = false;
+        let mut close = false;
+        let modal = egui::Modal::new(egui::Id::new("everything_search")).show(ctx, |ui| {
+            ui.set_width(640.0);
+            ui.heading("用 Everything 查找缺失字体");
+            ui.weak("结果只按文件名搜索，可能不准确。勾选的文件加载前会重新解析字体名，只加载确实匹配的字体");
+            ui.separator();
+            match &mut dialog.hits {
+                None => {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label(if busy { "正在搜索…" } else { "等待搜索…" });
+                    });
+                }
+                Some(Err(err)) => {
+                    ui.colored_label(ui.visuals().error_fg_color, err.as_str());
+                }
+                Some(Ok(hits)) if hits.is_empty() => {
+                    ui.label("没有找到候选文件");
+                }
+                Some(Ok(hits)) => {
+                    egui::ScrollArea::vertical()
+                        .max_height(320.0)
+                        .auto_shrink([false, true])
+                        .show(ui, |ui| {
+                            egui::Grid::new("everything_hits")
+                                .num_columns(2)
+                                .striped(true)
+                                .show(ui, |ui| {
+                                    for hit in hits.iter_mut() {
+                                        ui.checkbox(&mut hit.checked, hit.font.as_str());
+                                        ui.label(hit.path.as_str());
+                                        ui.end_row();
+                                    }
+                                });
+                        });
+                }
+            }
+            ui.separator();
+            ui.horizontal(|ui| {
+                let any_checked = matches!(&dialog.hits, Some(Ok(hits)) if hits.iter().any(|h| h.checked));
+                if ui
+                    .add_enabled(any_checked, egui::Button::new("加载选中"))
+                    .clicked()
+                {
+                    load = true;
+                }
+                if ui.button("关闭").clicked() {
+                    close = true;
+                }
+            });
+        });
+        if load {
+            let Some(dialog) = self.everything_search.take() else {
+                return;
+            };
+            let files = match dialog.hits {
+                Some(Ok(hits)) => hits
+                    .into_iter()
+                    .filter(|hit| hit.checked)
+                    .map(|hit| PathBuf::from(hit.path))
+                    .collect(),
+                _ => Vec::new(),
+            };
+            self.submit_job(Job::ResolveMissingFiles {
+                files,
+                missing: dialog.missing,
+                use_cache: self.mode == Mode::Normal,
+            });
+        } else if close || modal.should_close() {
+            self.everything_search = None;
+        }
+    }
+
+    fn ui_match_test(&mut self, ctx: &egui::Context) {
+        let Some(test) = &self.match_test else {
+            return;
+        };
+        let mut pick = None;
+        let mut close = false;
+        let modal = egui::Modal::new(egui::Id::new("match_test")).show(ctx, |ui| {
+            ui.set_width(640.0);
+            ui.heading("单字体匹配测试");
+            egui::Grid::new("match_test_files")
+                .num_columns(3)
+                .show(ui, |ui| {
+                    for (label, path, purpose) in [
+                        ("字幕", &test.sub, DialogPurpose::MatchTestSub),
+                        ("字体", &test.font, DialogPurpose::MatchTestFont),
+                    ] {
+                        ui.label(label);
+                        match path {
+                            Some(path) => ui.label(path.to_string_lossy()),
+                            None => ui.weak("未选择"),
+                        };
+                        if ui.button("选择…").clicked() {
+                            pick = Some(purpose);
+                        }
+                        ui.end_row();
+                    }
+                });
+            match &test.report {
+                None => {}
+                Some(Err(err)) => {
+                    ui.separator();
+                    ui.colored_label(ui.visuals().error_fg_color, err);
+                }
+                Some(Ok(report)) => {
+                    ui.separator();
+                    ui_match_report(ui, report);
+                }
+            }
+            ui.separator();
+            if ui.button("关闭").clicked() {
+                close = true;
+            }
+        });
+        if let Some(purpose) = pick {
+            let (name, extensions): (&str, &[&str]) = match purpose {
+                DialogPurpose::MatchTestSub => ("字幕", &["ass", "ssa"]),
+                _ => ("字体", &["ttf", "otf", "ttc"]),
+            };
+            self.open_dialog(
+                rfd::FileDialog::new().add_filter(name, extensions),
+                DialogAction::PickFile,
+                purpose,
+            );
+        } else if close || modal.should_close() {
+            self.match_test = None;
+        }
+    }
+
+    fn ui_confirm_reset(&mut self, ctx: &egui::Context) {
+        if !self.confirm_reset {
+            return;
+        }
+        let mut confirm = false;
+        let mut close = false;
+        let modal = egui::Modal::new(egui::Id::new("confirm_reset")).show(ctx, |ui| {
+            ui.heading("重置所有设置");
+            ui.label("所有设置将恢复为默认值并立即保存，此操作无法撤销。");
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui.button("重置").clicked() {
+                    confirm = true;
+                }
+                if ui.button("取消").clicked() {
+                    close = true;
+                }
+            });
+        });
+        if confirm {
+            self.confirm_reset = false;
+            self.reset_config();
+        } else if close || modal.should_close() {
+            self.confirm_reset = false;
+        }
+    }
+
+    fn ui_config_import(&mut self, ctx: &egui::Context) {
+        let Some(import) = &self.config_import else {
+            return;
+        };
+        let mut confirm = false;
+        let mut close = false;
+        let modal = egui::Modal::new(egui::Id::new("config_import")).show(ctx, |ui| {
+            ui.set_width(560.0);
+            ui.heading("导入配置");
+            ui.weak(import.path.to_string_lossy());
+            ui.label(format!("将改动 {} 项设置:", import.changes.len()));
+            egui::ScrollArea::vertical()
+                .max_height(320.0)
+                .auto_shrink([false, true])
+                .show(ui, |ui| {
+                    egui::Grid::new("config_import_changes")
+                        .num_columns(3)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for (key, old, new) in &import.changes {
+                                ui.label(key);
+                                ui.weak(old);
+                                ui.label(new);
+                                ui.end_row();
+                            }
+                        });
+                });
+            for path in &import.missing_paths {
+                ui.colored_label(ui.visuals().warn_fg_color, format!("本机不存在: {}", path));
+            }
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui.button("应用").clicked() {
+                    confirm = true;
+                }
+                if ui.button("取消").clicked() {
+                    close = true;
+                }
+            });
+        });
+        if confirm {
+            if let Some(import) = self.config_import.take() {
+                self.apply_config(import.config);
+                self.logs
+                    .push(format!("[i] 已导入配置: {}", import.path.to_string_lossy()));
+            }
+        } else if close || modal.should_close() {
+            self.config_import = None;
+        }
+    }
+
+    /// 把 [`Config`] 恢复为默认值，操作模式回到启动时的无残留模式。
+    fn reset_config(&mut self) {
+        self.apply_config(Config::default());
+        self.mode = Mode::NoResidue;
+        self.logs.push("[i] 已重置所有设置".to_string());
+    }
+
+    /// 换用 `config`：同步设置页的编辑内容，重启受设置影响的后台线程，立即应用主题、
+    /// 窗口置顶，并写回磁盘。
+    fn apply_config(&mut self, config: Config) {
+        let font_changed = config.ui_font_path != self.config.ui_font_path;
+        self.config = config;
+        if font_changed {
+            self.apply_ui_font();
+        }
+        self.font_folders_text = self.config.font_folders.join(", ");
+        self.standard_fonts_text = self.config.standard_fonts.join(", ");
+        self.name_rules_text = format_name_rules(&self.config.name_rules);
+        self.font_aliases_text = format_font_aliases(&self.config.font_aliases);
+        self.excluded_fonts_text = self.config.excluded_fonts.join("\n");
+        self.set_name_rules();
+        self.auto_load_text = self.config.auto_load_processes.join(", ");
+        self.restart_process_watcher();
+        self.restart_http_server();
+        self.restart_config_watch();
+        if !self.config.auto_retry_missing {
+            self.missing_watch = None;
+        }
+        let ctx = &self.repaint.0;
+        apply_theme(ctx, self.config.theme);
+        apply_window_level(ctx, self.config.always_on_top);
+        if let Err(err) = save_config(&self.config) {
+            self.logs.push(format!("[X] 保存设置失败: {}", err));
+        }
+    }
+}
+
+impl eframe::App for FontLoaderApp {
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        self.poll_worker();
+        self.poll_players();
+        self.poll_started_processes();
+        self.poll_http_requests();
+        self.poll_missing_watch();
+        self.poll_config_watch();
+        self.poll_gdi_usage(ctx);
+        self.poll_dialog();
+        let copy_missing = egui::KeyboardShortcut::new(
+            egui::Modifiers::COMMAND | egui::Modifiers::SHIFT,
+            egui::Key::M,
+        );
+        if ctx.input_mut(|i| i.consume_shortcut(&copy_missing)) {
+            self.handle_copy_missing_names(ctx);
+        }
+        if self.busy {
+            // 驱动"处理中"旁的旋转动画
+            ctx.request_repaint_after(Duration::from_millis(16));
+        }
+        let close_requested = ctx.input(|i| i.viewport().close_requested());
+        if close_requested && !self.players.is_empty() && !self.quit_confirmed {
+            ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+            self.confirm_quit = true;
+        } else if close_requested && self.busy && self.stopping.is_none() {
+            // 先让工作线程停下，清理时才能看到它登记的全部字体
+            ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+            self.job_queue.clear();
+            self.cancel.store(true, Ordering::Relaxed);
+            self.stopping = Some(Instant::now());
+        }
+        if let Some(started) = self.stopping
+            && (!self.busy || started.elapsed() >= SHUTDOWN_TIMEOUT)
+        {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+        }
+        let taskbar_status = match self.progress {
+            _ if !self.busy => TaskbarStatus::Idle(self.loaded_view.len()),
+            Some((ProgressPhase::Load, done, total)) if total > 0 => {
+                TaskbarStatus::Progress(done, total)
+            }
+            _ => TaskbarStatus::Busy,
+        };
+        self.taskbar.update(frame, taskbar_status);
+        let dropped = ctx.input(|i| i.raw.dropped_files.clone());
+        if !dropped.is_empty() {
+            // 从浏览器拖入的链接没有本地路径，内容是 text/uri-list
+            let uris: Vec<String> = dropped
+                .iter()
+                .filter(|f| f.path.is_none() && f.mime == "text/uri-list")
+                .flat_map(dropped_uris)
+                .collect();
+            if !uris.is_empty() {
+                self.handle_dropped_urls(uris);
+            }
+            let paths: Vec<PathBuf> = dropped.into_iter().filter_map(|f| f.path).collect();
+            if !paths.is_empty() {
+                self.enqueue_paths(paths);
+            }
+        }
+
+        egui::TopBottomPanel::top("top_bar").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                for (tab, label) in [
+                    (Tab::Operate, "操作"),
+                    (Tab::Results, "结果"),
+                    (Tab::Loaded, "已加载"),
+                    (Tab::Logs, "日志"),
+                    (Tab::Settings, "设置"),
+                ] {
+                    if ui.selectable_label(self.tab == tab, label).clicked() {
+                        self.tab = tab;
+                    }
+                }
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    let mut theme = self.config.theme;
+                    egui::ComboBox::from_id_salt("theme")
+                        .selected_text(theme.label())
+                        .show_ui(ui, |ui| {
+                            for option in Theme::ALL {
+                                ui.selectable_value(&mut theme, option, option.label());
+                            }
+                        });
+                    if theme != self.config.theme {
+                        self.config.theme = theme;
+                        apply_theme(ctx, theme);
+                        if let Err(err) = save_config(&self.config) {
+                            self.logs.push(format!("[X] 保存设置失败: {}", err));
+                        }
+                    }
+                    if ui
+                        .selectable_label(self.config.always_on_top, "置顶")
+                        .on_hover_text("窗口保持在播放器等其他窗口之上")
+                        .clicked()
+                    {
+                        self.config.always_on_top = !self.config.always_on_top;
+                        apply_window_level(ctx, self.config.always_on_top);
+                        if let Err(err) = save_config(&self.config) {
+                            self.logs.push(format!("[X] 保存设置失败: {}", err));
+                        }
+                    }
+                });
+            });
+        });
+
+        if let Some((current, _)) = self.gdi_usage
+            && current >= GDI_WARN_OBJECTS
+        {
+            egui::TopBottomPanel::top("gdi_warning").show(ctx, |ui| {
+                ui.colored_label(
+                    ui.visuals().warn_fg_color,
+                    format!(
+                        "GDI 对象已用 {} 个，接近每个进程 {} 个的上限，建议卸载部分字体或重启程序",
+                        current, GDI_HANDLE_LIMIT
+                    ),
+                );
+            });
+        }
+
+        if self.registered_faces >= self.config.face_warn_threshold {
+            egui::TopBottomPanel::top("face_warning").show(ctx, |ui| {
+                ui.horizontal_wrapped(|ui| {
+                    ui.colored_label(
+                        ui.visuals().warn_fg_color,
+                        format!(
+                            "已注册 {} 个字体面（阈值 {}），可能导致部分程序无法枚举字体，建议卸载已看完的批次",
+                            self.registered_faces, self.config.face_warn_threshold
+                        ),
+                    );
+                    if ui
+                        .add_enabled(!self.busy, egui::Button::new("卸载已加载字体"))
+                        .clicked()
+                    {
+                        self.handle_unload();
+                    }
+                    // 工作线程加载期间一直持有状态锁，这里不能阻塞界面
+                    let residual = self.state.try_lock().map(|s| s.residual_faces).unwrap_or(0);
+                    if residual > 0
+                        && ui
+                            .button("清零残留计数")
+                            .on_hover_text("注销或重启后上次会话遗留的字体已被系统释放")
+                            .clicked()
+                    {
+                        self.clear_residual_faces();
+                    }
+                });
+            });
+        }
+
+        let mut action = None;
+        egui::CentralPanel::default().show(ctx, |ui| match self.tab {
+            Tab::Operate => self.ui_operate(ui),
+            Tab::Settings => self.ui_settings(ui),
+            Tab::Results => action = self.ui_results(ui),
+            Tab::Loaded => action = self.ui_loaded(ui),
+            Tab::Logs => {
+                egui::ScrollArea::vertical()
+                    .auto_shrink([false; 2])
+                    .show(ui, |ui| {
+                        for line in &self.logs {
+                            match log_line_status(line) {
+                                Some(status) => {
+                                    ui.colored_label(status_color(ui.visuals(), status), line)
+                                }
+                                None => ui.label(line),
+                            };
+                        }
+                    });
+            }
+        });
+        if let Some(action) = action {
+            self.apply_row_action(ctx, action);
+        }
+        self.ui_diff_preview(ctx);
+        self.ui_system_coverage(ctx);
+        self.ui_confirm_reset(ctx);
+        self.ui_config_import(ctx);
+        self.ui_confirm_quit(ctx);
+        self.ui_stopping(ctx);
+        self.ui_clean_by_name(ctx);
+        self.ui_alias_edit(ctx);
+        self.ui_everything_search(ctx);
+        self.ui_match_test(ctx);
+        self.flush_structured_log();
+    }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.process_watch_stop.store(true, Ordering::Relaxed);
+        self.http_stop.store(true, Ordering::Relaxed);
+        if self.config.initial_tab != self.tab {
+            self.config.initial_tab = self.tab;
+            let _ = save_config(&self.config);
+        }
+        let mode = self.config.load_mode;
+        // 用户选择为仍在运行的播放器保留字体时，这些文件不卸载，记入会话状态；
+        // 仅本程序可见的注册随进程结束失效，无需保留也无需记录
+        let keep: HashSet<PathKey> = if self.keep_player_fonts && mode == LoadMode::Public {
+            self.players
+                .iter()
+                .flat_map(|p| &p.used)
+                .map(|path| PathKey::new(path))
+                .collect()
+        } else {
+            HashSet::new()
+        };
+        // 工作线程可能正要向 AppState 登记新加载的字体，先让它停下并等它写完，
+        // 否则清理时会漏掉这些字体
+        self.job_queue.clear();
+        if let Some(handle) = self.worker.take() {
+            self.cancel.store(true, Ordering::Relaxed);
+            // 关闭前已在界面上等过的不再重复等待
+            let deadline = self.stopping.unwrap_or_else(Instant::now) + SHUTDOWN_TIMEOUT;
+            while !handle.is_finished() && Instant::now() < deadline {
+                thread::sleep(Duration::from_millis(20));
+            }
+            if handle.is_finished() {
+                let _ = handle.join();
+            } else {
+                if mode == LoadMode::Public {
+                    self.save_outstanding_registrations(&keep);
+                }
+                return;
+            }
+        }
+        if let Ok(state) = self.state.lock() {
+            let mut count = 0;
+            let mut remaining = state.residual_faces;
+            let mut kept_fonts = Vec::new();
+            for (key, font) in state.loaded.iter() {
+                if keep.contains(key) {
+                    kept_fonts.push(KeptFont {
+                        path: font.path.clone(),
+                        faces: font.faces,
+                    });
+                } else if remove_font_resource(&font.path, mode) {
+                    count += 1;
+                } else if mode == LoadMode::Public {
+                    remaining += font.faces;
+                }
+            }
+            if count > 0 {
+                broadcast_font_change();
+            }
+            let _ = save_session(&SessionState {
+                registered_faces: remaining,
+                kept_fonts,
+                ..SessionState::default()
+            });
+        }
+    }
+}
+
+fn entry_status_label(status: EntryStatus) -> &'static str {
+    match status {
+        EntryStatus::Loaded => "[ok]",
+        EntryStatus::Duplicate => "[^]",
+        EntryStatus::Failed => "[X]",
+        EntryStatus::Missing => "[??]",
+        EntryStatus::System => "[sys]",
+        EntryStatus::Conflict => "[conflict]",
+        EntryStatus::MissingVariant => "[variant]",
+    }
+}
+
+fn log_line_status(line: &str) -> Option<EntryStatus> {
+    [
+        EntryStatus::Loaded,
+        EntryStatus::Duplicate,
+        EntryStatus::Failed,
+        EntryStatus::Missing,
+        EntryStatus::System,
+        EntryStatus::Conflict,
+        EntryStatus::MissingVariant,
+    ]
+    .into_iter()
+    .find(|status| line.starts_with(entry_status_label(*status)))
+}
+
+/// 任务进行中的旋转圆环，占 24×24 像素，每 1.2 秒转一圈。重绘由 `update` 在忙碌时驱动。
+fn ui_spinner(ui: &mut egui::Ui) {
+    const PERIOD: f64 = 1.2;
+    const SWEEP: f32 = std::f32::consts::PI * 1.5;
+    let (rect, _) = ui.allocate_exact_size(egui::vec2(24.0, 24.0), egui::Sense::hover());
+    let center = rect.center();
+    let radius = 9.0;
+    let color = ui.visuals().widgets.noninteractive.fg_stroke.color;
+    let painter = ui.painter();
+    painter.circle_stroke(
+        center,
+        radius,
+        egui::Stroke::new(2.0, color.gamma_multiply(0.2)),
+    );
+    let time = ui.ctx().input(|i| i.time);
+    let start = ((time % PERIOD) / PERIOD) as f32 * std::f32::consts::TAU;
+    let points: Vec<egui::Pos2> = (0..=24)
+        .map(|i| {
+            let angle = start + SWEEP * i as f32 / 24.0;
+            center + radius * egui::vec2(angle.cos(), angle.sin())
+        })
+        .collect();
+    painter.add(egui::Shape::line(points, egui::Stroke::new(2.0, color)));
+}
+
+/// 各字幕的覆盖率进度条：不低于 90% 为绿色，不低于 70% 为黄色，其余为红色。
+fn ui_subtitle_coverage(ui: &mut egui::Ui, reports: &[SubtitleReport], base_dirs: &[PathBuf]) {
+    egui::Grid::new("coverage_grid")
+        .num_columns(3)
+        .striped(true)
+        .show(ui, |ui| {
+            for report in reports {
+                let color = if report.coverage_pct >= 90.0 {
+                    egui::Color32::from_rgb(0x2E, 0xA0, 0x43)
+                } else if report.coverage_pct >= 70.0 {
+                    egui::Color32::from_rgb(0xD2, 0x99, 0x22)
+                } else {
+                    egui::Color32::from_rgb(0xCF, 0x22, 0x2E)
+                };
+                if report.available == report.required {
+                    ui.colored_label(color, "✔");
+                } else {
+                    ui.label("");
+                }
+                ui.add(
+                    egui::ProgressBar::new(report.coverage_pct / 100.0)
+                        .desired_width(160.0)
+                        .fill(color)
+                        .text(format!(
+                            "{:.0}% ({}/{})",
+                            report.coverage_pct, report.available, report.required
+                        )),
+                );
+                ui.label(relative_display_path(Path::new(&report.path), base_dirs))
+                    .on_hover_text(&report.path);
+                ui.end_row();
+            }
+        });
+}
+
+fn status_color(visuals: &egui::Visuals, status: EntryStatus) -> egui::Color32 {
+    match status {
+        EntryStatus::Loaded if visuals.dark_mode => egui::Color32::from_rgb(0x6E, 0xE7, 0x7E),
+        EntryStatus::Loaded => egui::Color32::from_rgb(0x1A, 0x6B, 0x2C),
+        EntryStatus::Duplicate => visuals.hyperlink_color,
+        EntryStatus::Failed => visuals.error_fg_color,
+        EntryStatus::Missing => visuals.warn_fg_color,
+        EntryStatus::System => visuals.weak_text_color(),
+        EntryStatus::Conflict => visuals.error_fg_color,
+        EntryStatus::MissingVariant => visuals.error_fg_color,
+    }
+}
+
+fn apply_theme(ctx: &egui::Context, theme: Theme) {
+    ctx.set_visuals_of(egui::Theme::Dark, egui::Visuals::dark());
+    ctx.set_visuals_of(egui::Theme::Light, egui::Visuals::light());
+    match theme {
+        Theme::Dark => ctx.set_theme(egui::ThemePreference::Dark),
+        Theme::Light => ctx.set_theme(egui::ThemePreference::Light),
+        Theme::System => ctx.set_theme(egui::ThemePreference::System),
+        Theme::HighContrast => {
+            ctx.set_visuals_of(egui::Theme::Dark, high_contrast_visuals());
+            ctx.set_theme(egui::ThemePreference::Dark);
+        }
+    }
+}
+
+fn apply_window_level(ctx: &egui::Context, always_on_top: bool) {
+    let level = if always_on_top {
+        egui::WindowLevel::AlwaysOnTop
+    } else {
+        egui::WindowLevel::Normal
+    };
+    ctx.send_viewport_cmd(egui::ViewportCommand::WindowLevel(level));
+}
+
+/// 高对比度主题：纯黑底白字，控件描边加粗，状态色在黑底上保持足够的对比度。
+/// 其他界面只通过 `Visuals` 取色，新增的界面会自动沿用这里的配色。
+fn high_contrast_visuals() -> egui::Visuals {
+    use egui::{Color32, Stroke};
+    let mut visuals = egui::Visuals::dark();
+    visuals.override_text_color = Some(Color32::WHITE);
+    visuals.panel_fill = Color32::BLACK;
+    visuals.window_fill = Color32::BLACK;
+    visuals.extreme_bg_color = Color32::BLACK;
+    visuals.faint_bg_color = Color32::from_gray(0x24);
+    visuals.code_bg_color = Color32::from_gray(0x24);
+    visuals.window_stroke = Stroke::new(2.0, Color32::WHITE);
+    visuals.hyperlink_color = Color32::from_rgb(0x5C, 0xE1, 0xFF);
+    visuals.warn_fg_color = Color32::from_rgb(0xFF, 0xD7, 0x00);
+    visuals.error_fg_color = Color32::from_rgb(0xFF, 0x7A, 0x7A);
+    visuals.selection.bg_fill = Color32::from_rgb(0x00, 0x4B, 0xA8);
+    visuals.selection.stroke = Stroke::new(2.0, Color32::WHITE);
+
+    let widgets = &mut visuals.widgets;
+    widgets.noninteractive.bg_stroke = Stroke::new(1.0, Color32::from_gray(0xC0));
+    widgets.noninteractive.fg_stroke = Stroke::new(1.0, Color32::WHITE);
+    for state in [&mut widgets.inactive, &mut widgets.open] {
+        state.bg_fill = Color32::from_gray(0x10);
+        state.weak_bg_fill = Color32::from_gray(0x10);
+        state.bg_stroke = Stroke::new(1.5, Color32::WHITE);
+        state.fg_stroke = Stroke::new(1.5, Color32::WHITE);
+    }
+    // 悬停与按下使用黄色粗描边作为焦点指示
+    for state in [&mut widgets.hovered, &mut widgets.active] {
+        state.bg_fill = Color32::from_gray(0x30);
+        state.weak_bg_fill = Color32::from_gray(0x30);
+        state.bg_stroke = Stroke::new(2.5, Color32::from_rgb(0xFF, 0xD7, 0x00));
+        state.fg_stroke = Stroke::new(2.0, Color32::WHITE);
+    }
+    visuals
+}
+
+/// 匹配名后附上其他语言的家族名，如 "方正兰亭黑 / FZLanTingHei"。
+///
+/// 与某个匹配名相同或是其前缀（全名通常以家族名开头）的家族名不重复显示。
+fn display_font_names(names: &[String], families: &[String]) -> String {
+    let mut parts: Vec<&str> = names.iter().map(String::as_str).collect();
+    for family in families {
+        let family_lower = family.to_lowercase();
+        let covered = parts
+            .iter()
+            .any(|part| part.to_lowercase().starts_with(&family_lower));
+        if !covered {
+            parts.push(family);
+        }
+    }
+    parts.join(" / ")
+}
+
+/// 字体库列表的显示行 `(字体名, 路径)`，按字体名排序；系统字体带 `[sys]` 标记。
+fn library_rows(index: &FontIndex) -> Vec<(String, String)> {
+    let marker = match index.source {
+        FontSource::System => "[sys] ",
+        FontSource::User => "",
+    };
+    let mut rows: Vec<(String, String)> = index
+        .families
+        .iter()
+        .map(|(path, families)| {
+            let name = format!("{}{}", marker, display_font_names(&[], families));
+            (name, path.to_string_lossy().to_string())
+        })
+        .collect();
+    rows.sort_by_cached_key(|(name, _)| name.to_lowercase());
+    rows
+}
+
+/// 字体文件位于某个 `base_dirs` 目录内时，返回其中最短的相对路径，形如
+/// `./Fonts/Arial.ttf`；不在任何目录内时返回完整路径。
+fn relative_display_path(font_path: &Path, base_dirs: &[PathBuf]) -> String {
+    base_dirs
+        .iter()
+        .filter_map(|base| font_path.strip_prefix(base).ok())
+        .filter(|rel| !rel.as_os_str().is_empty())
+        .map(|rel| {
+            let parts: Vec<_> = rel
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect();
+            format!("./{}", parts.join("/"))
+        })
+        .min_by_key(|rel| rel.len())
+        .unwrap_or_else(|| font_path.to_string_lossy().to_string())
+}
+
+/// 筛选用的比较形式：先按 [`NameKey::new`] 规范化，再把连续空白合并为一个空格。
+fn filter_key(text: &str) -> String {
+    NameKey::new(text)
+        .as_str()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn update_selection<T: Eq + std::hash::Hash>(
+    selected: &mut HashSet<T>,
+    key: T,
+    response: &egui::Response,
+    command: bool,
+) {
+    if response.clicked() {
+        if command {
+            if !selected.remove(&key) {
+                selected.insert(key);
+            }
+        } else {
+            selected.clear();
+            selected.insert(key);
+        }
+    } else if response.secondary_clicked() && !selected.contains(&key) {
+        selected.clear();
+        selected.insert(key);
+    }
+}
+
+fn row_context_menu(
+    ui: &mut egui::Ui,
+    names: Vec<String>,
+    paths: Vec<String>,
+    unloadable: Vec<String>,
+) -> Option<RowAction> {
+    let mut action = None;
+    if ui
+        .add_enabled(paths.len() == 1, egui::Button::new("打开所在文件夹"))
+        .clicked()
+    {
+        action = paths.first().cloned().map(RowAction::OpenFolder);
+    }
+    if ui
+        .add_enabled(!names.is_empty(), egui::Button::new("复制字体名"))
+        .clicked()
+    {
+        action = Some(RowAction::CopyNames(names.clone()));
+    }
+    if ui
+        .add_enabled(!paths.is_empty(), egui::Button::new("复制文件路径"))
+        .clicked()
+    {
+        action = Some(RowAction::CopyPaths(paths.clone()));
+    }
+    if ui
+        .add_enabled(!unloadable.is_empty(), egui::Button::new("卸载此字体"))
+        .clicked()
+    {
+        action = Some(RowAction::Unload(unloadable));
+    }
+    ui.separator();
+    if ui
+        .add_enabled(names.len() == 1, egui::Button::new("为此字体名添加别名…"))
+        .on_hover_text("之后处理时，字幕中的这个字体名改按别名查找")
+        .clicked()
+    {
+        action = names.first().cloned().map(RowAction::AddAlias);
+    }
+    if ui
+        .add_enabled(!paths.is_empty(), egui::Button::new("排除此文件"))
+        .on_hover_text("之后处理时不再匹配这些文件，可在设置页中移除")
+        .clicked()
+    {
+        action = Some(RowAction::Exclude(paths));
+    }
+    if action.is_some() {
+        ui.close_menu();
+    }
+    action
+}
+
+/// 一次扫描、加载的参数。
+#[derive(Clone, Default)]
+struct LoadOptions {
+    use_cache: bool,
+    /// 目录递归层数上限，`None` 不限
+    max_depth: Option<usize>,
+    /// 字幕字体名的查找键 -> 实际查找的字体名
+    aliases: HashMap<NameKey, String>,
+    /// 不参与匹配的字体文件，建立索引前去掉
+    excluded: HashSet<PathKey>,
+    verify_unload: bool,
+    /// 优先使用大小写完全一致的字体名匹配
+    case_sensitive: bool,
+    /// 缺少字幕需要的粗体/斜体变体时条目记为 [`EntryStatus::MissingVariant`]
+    strict_style: bool,
+    /// 不加载家族名与系统已安装字体相同的文件
+    skip_system_conflicts: bool,
+    /// 注册字体文件的方式，卸载时使用同一方式
+    mode: LoadMode,
+    /// 匹配前改写字幕和字体两侧名称的规则
+    name_rules: NameRules,
+    /// 缓存条目的有效天数，0 表示不过期，见 [`load_cache_file`]
+    cache_ttl_days: u64,
+    /// 注册遇到暂时性错误时的重试次数，见 [`add_font_resource_with_retry`]
+    load_retries: usize,
+    /// 每条日志最多列出的候选文件或冲突字体数，0 不限，见 [`join_capped`]
+    max_logged_candidates: usize,
+    /// 注册成功的文件同时记入此处，不必获取状态锁即可读取
+    registrations: Option<Arc<Mutex<Vec<KeptFont>>>>,
+    /// 从注册表导入的系统字体索引，其中的字体与系统已安装字体同样处理
+    system_index: Option<Arc<FontIndex>>,
+    /// 本次会话之前处理时建立的索引，并入本次的索引一起匹配
+    session_index: Option<Arc<FontIndex>>,
+    /// 输入目录（或输入文件所在目录）下总是扫描的字体子目录名，见 [`font_folder_dirs`]
+    font_folders: Vec<String>,
+}
+
+#[derive(Debug)]
+enum FontLoaderError {
+    AliasMap(String),
+    Cancelled,
+    Failed(String),
+}
+
+impl std::fmt::Display for FontLoaderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FontLoaderError::AliasMap(err) => write!(f, "读取别名表失败: {}", err),
+            FontLoaderError::Cancelled => write!(f, "已取消"),
+            FontLoaderError::Failed(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+/// 不依赖界面配置一次加载会话，将来拆出 `lib.rs` 后作为库接口。
+///
+/// ```ignore
+/// let loader = FontLoaderBuilder::new()
+///     .with_mode(LoadMode::Private)
+///     .with_alias_map("aliases.json")
+///     .with_max_depth(5)
+///     .build();
+/// let result = loader.process(vec![PathBuf::from(r"D:\Anime\Ep01")])?;
+/// ```
+#[derive(Default)]
+struct FontLoaderBuilder {
+    options: LoadOptions,
+    alias_map: Option<PathBuf>,
+    state: Option<Arc<Mutex<AppState>>>,
+}
+
+impl FontLoaderBuilder {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn with_cache(mut self, use_cache: bool) -> Self {
+        self.options.use_cache = use_cache;
+        self
+    }
+
+    /// 见 [`Config::cache_ttl_days`]。
+    fn with_cache_ttl_days(mut self, days: u64) -> Self {
+        self.options.cache_ttl_days = days;
+        self
+    }
+
+    /// 字幕中的字体名 -> 实际查找的字体名，与 [`Self::with_alias_map`] 读到的合并。
+    fn with_aliases(mut self, aliases: &BTreeMap<String, String>) -> Self {
+        self.options.aliases = alias_key_map(aliases);
+        self
+    }
+
+    /// 见 [`NameRules`]。
+    fn with_name_rules(mut self, rules: NameRules) -> Self {
+        self.options.name_rules = rules;
+        self
+    }
+
+    /// 不参与匹配的字体文件。
+    fn with_excluded_fonts(mut self, paths: &[String]) -> Self {
+        self.options.excluded = excluded_font_keys(paths);
+        self
+    }
+
+    fn with_case_sensitive(mut self, case_sensitive: bool) -> Self {
+        self.options.case_sensitive = case_sensitive;
+        self
+    }
+
+    fn with_strict_style(mut self, strict: bool) -> Self {
+        self.options.strict_style = strict;
+        self
+    }
+
+    fn with_skip_system_conflicts(mut self, skip: bool) -> Self {
+        self.options.skip_system_conflicts = skip;
+        self
+    }
+
+    fn with_load_retries(mut self, retries: usize) -> Self {
+        self.options.load_retries = retries;
+        self
+    }
+
+    fn with_max_logged_candidates(mut self, limit: usize) -> Self {
+        self.options.max_logged_candidates = limit;
+        self
+    }
+
+    fn with_registrations(mut self, registrations: Arc<Mutex<Vec<KeptFont>>>) -> Self {
+        self.options.registrations = Some(registrations);
+        self
+    }
+
+    /// 发布包中存放字体的子目录名，如 `Fonts`、`字体`、`attachments`。
+    fn with_font_folders(mut self, names: Vec<String>) -> Self {
+        self.options.font_folders = names;
+        self
+    }
+
+    /// 见 [`index_system_fonts_worker`]。
+    fn with_system_index(mut self, index: Option<Arc<FontIndex>>) -> Self {
+        self.options.system_index = index;
+        self
+    }
+
+    /// 之前各次处理累积的索引，见 [`FontIndex::merge`]。
+    fn with_session_index(mut self, index: Arc<FontIndex>) -> Self {
+        self.options.session_index = Some(index);
+        self
+    }
+
+    /// 见 [`LoadMode`]，默认公开注册。
+    fn with_mode(mut self, mode: LoadMode) -> Self {
+        self.options.mode = mode;
+        self
+    }
+
+    /// JSON 对象，键为字幕中的字体名，值为字体文件中的名称；在 [`FontLoader::process`]
+    /// 时读取。
+    fn with_alias_map(mut self, path: impl Into<PathBuf>) -> Self {
+        self.alias_map = Some(path.into());
+        self
+    }
+
+    /// 见 [`collect_files`]。
+    fn with_max_depth(mut self, depth: usize) -> Self {
+        self.options.max_depth = Some(depth);
+        self
+    }
+
+    /// 卸载后检查字体是否仍可枚举，见 [`unload_fonts_worker`]。
+    fn with_unload_verification(mut self, verify: bool) -> Self {
+        self.options.verify_unload = verify;
+        self
+    }
+
+    /// 与界面共享已加载字体的状态；不设置时使用独立的状态。
+    fn with_state(mut self, state: Arc<Mutex<AppState>>) -> Self {
+        self.state = Some(state);
+        self
+    }
+
+    fn build(self) -> FontLoader {
+        FontLoader {
+            options: self.options,
+            alias_map: self.alias_map,
+            state: self.state.unwrap_or_default(),
+        }
+    }
+}
+
+struct FontLoader {
+    options: LoadOptions,
+    alias_map: Option<PathBuf>,
+    state: Arc<Mutex<AppState>>,
+}
+
+impl FontLoader {
+    fn process(&self, paths: Vec<PathBuf>) -> Result<ProcessResult, FontLoaderError> {
+        self.process_with(paths, &|_, _, _| {}, &AtomicBool::new(false))
+    }
+
+    fn process_with(
+        &self,
+        paths: Vec<PathBuf>,
+        progress: &dyn Fn(ProgressPhase, usize, usize),
+        cancel: &AtomicBool,
+    ) -> Result<ProcessResult, FontLoaderError> {
+        let options = self.options()?;
+        let paths = paths
+            .iter()
+            .map(|p| p.to_string_lossy().to_string())
+            .collect();
+        process_drop_worker(paths, &options, self.state.clone(), progress, cancel)
+            .map_err(|err| worker_error(err, cancel))
+    }
+
+    /// 只在 `inputs`（目录或文件）中查找 `missing` 中的字体并加载，见
+    /// [`resolve_missing_worker`]。
+    fn resolve_missing_with(
+        &self,
+        inputs: Vec<PathBuf>,
+        missing: Vec<FontEntry>,
+        progress: &dyn Fn(ProgressPhase, usize, usize),
+        cancel: &AtomicBool,
+    ) -> Result<ProcessResult, FontLoaderError> {
+        let options = self.options()?;
+        resolve_missing_worker(
+            inputs,
+            missing,
+            &options,
+            self.state.clone(),
+            progress,
+            cancel,
+        )
+        .map_err(|err| worker_error(err, cancel))
+    }
+
+    /// 本次使用的参数，设置了别名表文件时读入并合并。
+    fn options(&self) -> Result<LoadOptions, FontLoaderError> {
+        let mut options = self.options.clone();
+        if let Some(path) = &self.alias_map {
+            let aliases = load_alias_map(path).map_err(FontLoaderError::AliasMap)?;
+            options.aliases.extend(aliases);
+        }
+        Ok(options)
+    }
+
+    /// 重新加载清单中的字体，见 [`restore_fonts_worker`]。
+    fn restore(&self, fonts: Vec<ManifestFont>) -> Result<ProcessResult, FontLoaderError> {
+        restore_fonts_worker(fonts, self.options.mode, self.state.clone())
+            .map_err(FontLoaderError::Failed)
+    }
+
+    /// 卸载 `only` 中的字体，`None` 时卸载全部。
+    fn unload(&self, only: Option<Vec<String>>) -> Result<UnloadResult, FontLoaderError> {
+        unload_fonts_worker(
+            self.state.clone(),
+            only,
+            self.options.verify_unload,
+            self.options.mode,
+        )
+        .map_err(FontLoaderError::Failed)
+    }
+
+    fn undo(&self, batch: LoadBatch) -> Result<UnloadResult, FontLoaderError> {
+        undo_batch_worker(batch, self.options.mode, self.state.clone())
+            .map_err(FontLoaderError::Failed)
+    }
+}
+
+fn worker_error(err: String, cancel: &AtomicBool) -> FontLoaderError {
+    if cancel.load(Ordering::Relaxed) {
+        FontLoaderError::Cancelled
+    } else {
+        FontLoaderError::Failed(err)
+    }
+}
+
+fn load_alias_map(path: &Path) -> Result<HashMap<NameKey, String>, String> {
+    let data = fs::read(path).map_err(|e| e.to_string())?;
+    let map: BTreeMap<String, String> = serde_json::from_slice(&data).map_err(|e| e.to_string())?;
+    Ok(alias_key_map(&map))
+}
+
+fn alias_key_map(aliases: &BTreeMap<String, String>) -> HashMap<NameKey, String> {
+    aliases
+        .iter()
+        .map(|(alias, target)| (NameKey::new(alias), target.clone()))
+        .collect()
+}
+
+fn excluded_font_keys(paths: &[String]) -> HashSet<PathKey> {
+    paths.iter().map(|path| PathKey::new(path)).collect()
+}
+
+/// 解析设置页中的别名，每行 `字幕中的字体名 => 实际查找的字体名`，两侧都不能为空。
+fn parse_font_aliases(text: &str) -> BTreeMap<String, String> {
+    text.lines()
+        .filter_map(|line| {
+            let (name, target) = line.split_once("=>")?;
+            let (name, target) = (name.trim(), target.trim());
+            (!name.is_empty() && !target.is_empty()).then(|| (name.to_string(), target.to_string()))
+        })
+        .collect()
+}
+
+fn format_font_aliases(aliases: &BTreeMap<String, String>) -> String {
+    aliases
+        .iter()
+        .map(|(name, target)| format!("{} => {}", name, target))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn scan_inputs(
+    paths: &[String],
+    options: &LoadOptions,
+    progress: &dyn Fn(usize, usize),
+    cancel: &AtomicBool,
+) -> Result<ScanOutput, String> {
+    let use_cache = options.use_cache;
+    let mut shortcuts = Vec::new();
+    let mut redundant = 0;
+    let file_list = collect_files(paths, options.max_depth, &mut shortcuts, &mut redundant)?;
+    let mut sub_files = Vec::new();
+    let mut font_files = Vec::new();
+    for path in file_list {
+        if is_sub_file(&path) {
+            sub_files.push(path);
+        } else if is_font_file(&path) {
+            font_files.push(path);
+        }
+    }
+    // 只拖入字幕或限制了递归层数时，同级的字体目录也要扫描
+    let mut font_dirs = Vec::new();
+    let mut seen: HashSet<PathBuf> = font_files.iter().cloned().collect();
+    for dir in font_folder_dirs(paths, &options.font_folders) {
+        let mut files = Vec::new();
+        let _ = walk_dir(&dir, &mut files, None);
+        let before = font_files.len();
+        for path in files {
+            if is_font_file(&path) && seen.insert(path.clone()) {
+                font_files.push(path);
+            }
+        }
+        let added = font_files.len() - before;
+        if added > 0 {
+            font_dirs.push((dir, added));
+        }
+    }
+    let before = font_files.len();
+    font_files.retain(|path| !is_excluded_font(options, path));
+    let excluded = before - font_files.len();
+
+    let mut required_fonts = HashSet::new();
+    let mut font_sources: HashMap<String, Vec<String>> = HashMap::new();
+    let mut unsupported_subs = Vec::new();
+    let mut empty_subs = Vec::new();
+    let mut starred_fonts = HashSet::new();
+    let mut required_variants: HashMap<String, u8> = HashMap::new();
+    let mut complex_subs = HashSet::new();
+    let mut rule_hits = BTreeSet::new();
+    let rules = &options.name_rules;
+    for sub in &sub_files {
+        if is_ass_file(sub) {
+            let sub_str = sub.to_string_lossy().to_string();
+            // 读取失败和空文件要与"解析成功但没有字体"区分开，后者可能是截断的文件
+            let Some(parsed) = read_ass_fonts(sub) else {
+                empty_subs.push(sub_str);
+                continue;
+            };
+            starred_fonts.extend(parsed.starred.iter().filter_map(|font| rules.apply(font)));
+            if parsed.complex_script {
+                complex_subs.insert(sub_str.clone());
+            }
+            for (font, bits) in parsed.variants {
+                if let Some(font) = rules.apply(&font) {
+                    *required_variants.entry(font).or_default() |= bits;
+                }
+            }
+            for font in parsed.fonts {
+                let Some(font) = rules.apply_logged(&font, &mut rule_hits) else {
+                    continue;
+                };
+                font_sources
+                    .entry(font.clone())
+                    .or_default()
+                    .push(sub_str.clone());
+                required_fonts.insert(font);
+            }
+        } else {
+            unsupported_subs.push(sub.to_string_lossy().to_string());
+        }
+    }
+
+    let (mut cache, cache_expired) = if use_cache {
+        load_cache_file(options.cache_ttl_days)
+    } else {
+        (CacheFile::default(), 0)
+    };
+    let index = build_font_index(&font_files, use_cache, &mut cache, rules, progress, cancel);
+    rule_hits.extend(index.rule_hits.iter().cloned());
+    // 取消时同样写回缓存，已解析的部分下次可直接命中
+    if use_cache {
+        let _ = save_cache_file(&cache);
+    }
+    if cancel.load(Ordering::Relaxed) {
+        return Err("已取消".to_string());
+    }
+
+    Ok(ScanOutput {
+        sub_files,
+        font_files,
+        required_fonts,
+        font_sources,
+        unsupported_subs,
+        empty_subs,
+        starred_fonts,
+        required_variants,
+        complex_subs,
+        index,
+        font_dirs,
+        shortcuts,
+        redundant,
+        excluded,
+        rule_hits,
+        cache_expired,
+    })
+}
+
+fn is_excluded_font(options: &LoadOptions, path: &Path) -> bool {
+    !options.excluded.is_empty()
+        && options
+            .excluded
+            .contains(&PathKey::new(&path.to_string_lossy()))
+}
+
+fn preview_diff_worker(
+    paths: Vec<String>,
+    options: &LoadOptions,
+    progress: &dyn Fn(ProgressPhase, usize, usize),
+    cancel: &AtomicBool,
+) -> Result<DiffPreview, String> {
+    let index_progress = |done, total| progress(ProgressPhase::Index, done, total);
+    let scan = scan_inputs(&paths, options, &index_progress, cancel)?;
+    let required_keys: HashSet<NameKey> = scan
+        .required_fonts
+        .iter()
+        .map(|f| NameKey::new(f))
+        .collect();
+    let mut missing: Vec<String> = scan
+        .required_fonts
+        .iter()
+        .filter(|f| !scan.index.names.contains_key(&NameKey::new(f)))
+        .cloned()
+        .collect();
+    let mut unused: Vec<String> = scan
+        .index
+        .names
+        .keys()
+        .filter(|k| !required_keys.contains(*k))
+        .map(|k| k.as_str().to_string())
+        .collect();
+    missing.sort();
+    unused.sort();
+    Ok(DiffPreview { missing, unused })
+}
+
+/// 工作线程唤醒界面重绘用，在启动任务时交给工作线程。
+#[derive(Clone)]
+struct RepaintSignal(egui::Context);
+
+impl RepaintSignal {
+    fn request(&self) {
+        self.0.request_repaint();
+    }
+}
+
+/// 发送工作线程消息并唤醒界面，界面空闲时不会自行轮询。
+fn send_result(tx: &mpsc::Sender<WorkerResult>, repaint: &RepaintSignal, msg: WorkerResult) {
+    let _ = tx.send(msg);
+    repaint.request();
+}
+
+/// 进度回调：阶段切换或结束时立即上报，其余按时间节流，每次上报后让出时间片。
+fn progress_reporter(
+    tx: mpsc::Sender<WorkerResult>,
+    repaint: RepaintSignal,
+) -> impl Fn(ProgressPhase, usize, usize) {
+    const INTERVAL: Duration = Duration::from_millis(50);
+    let last: Cell<Option<(ProgressPhase, Instant)>> = Cell::new(None);
+    move |phase, done, total| {
+        let due = match last.get() {
+            Some((last_phase, at)) => last_phase != phase || at.elapsed() >= INTERVAL,
+            None => true,
+        };
+        if due || done == total {
+            last.set(Some((phase, Instant::now())));
+            send_result(&tx, &repaint, WorkerResult::Progress(phase, done, total));
+            thread::sleep(Duration::from_millis(0));
+        }
+    }
+}
+
+fn process_drop_worker(
+    paths: Vec<String>,
+    options: &LoadOptions,
+    state: Arc<Mutex<AppState>>,
+    progress: &dyn Fn(ProgressPhase, usize, usize),
+    cancel: &AtomicBool,
+) -> Result<ProcessResult, String> {
+    let index_progress = |done, total| progress(ProgressPhase::Index, done, total);
+    let ScanOutput {
+        sub_files,
+        font_files,
+        required_fonts,
+        mut font_sources,
+        unsupported_subs,
+        empty_subs,
+        starred_fonts,
+        required_variants,
+        complex_subs,
+        mut index,
+        font_dirs,
+        shortcuts,
+        redundant,
+        excluded,
+        rule_hits,
+        cache_expired,
+    } = scan_inputs(&paths, options, &index_progress, cancel)?;
+    let reused = options
+        .session_index
+        .as_ref()
+        .map_or(0, |session| index.merge(session, &options.excluded));
+
+    let required: Vec<(String, Vec<String>)> = required_fonts
+        .into_iter()
+        .map(|font| {
+            let sources = font_sources.remove(&font).unwrap_or_default();
+            (font, sources)
+        })
+        .collect();
+    let parsed_subs: Vec<String> = sub_files
+        .iter()
+        .map(|sub| sub.to_string_lossy().to_string())
+        .filter(|sub| !empty_subs.contains(sub) && !unsupported_subs.contains(sub))
+        .collect();
+    // 只拖入了字幕时逐个报告缺失没有多大用处，先给出总的说明
+    let subs_only = font_files.is_empty() && index.families.is_empty() && !required.is_empty();
+    let required_count = required.len();
+    let mut state = state.lock().map_err(|_| "状态锁失败".to_string())?;
+    let register = |path: &str| add_font_resource(path, options.mode);
+    let mut result = load_required_fonts(
+        required, &index, options, &mut state, progress, cancel, &register,
+    );
+    let mut logs: LogLines = font_dirs
+        .iter()
+        .map(|(dir, added)| {
+            format!(
+                "[i] 自动包含字体目录: {} ({} 个字体文件)",
+                dir.to_string_lossy(),
+                added
+            )
+        })
+        .collect();
+    for (from, to) in &rule_hits {
+        logs.push(format!("[i] 字体名规则: {} -> {}", from, to));
+    }
+    if cache_expired > 0 {
+        logs.push(format!(
+            "[i] 丢弃 {} 个超过 {} 天的缓存条目",
+            cache_expired, options.cache_ttl_days
+        ));
+    }
+    if redundant > 0 {
+        logs.push(format!(
+            "[i] 合并 {} 个重复或已包含在其他输入目录中的路径",
+            redundant
+        ));
+    }
+    if excluded > 0 {
+        logs.push(format!("[i] 跳过 {} 个已排除的字体文件", excluded));
+    }
+    for (link, target) in &shortcuts {
+        logs.push(match target {
+            Some(target) => format!(
+                "[i] 快捷方式 {} -> {}",
+                link.to_string_lossy(),
+                target.to_string_lossy()
+            ),
+            None => format!("[i] 无法解析快捷方式，已跳过: {}", link.to_string_lossy()),
+        });
+    }
+    if reused > 0 {
+        logs.push(format!("[i] 沿用本次会话之前索引的 {} 个字体文件", reused));
+    }
+    if subs_only {
+        logs.push(format!(
+            "[info] 仅处理了字幕文件，无字体文件可索引。所需字体: {}个",
+            required_count
+        ));
+    }
+    logs.extend(
+        unsupported_subs
+            .into_iter()
+            .map(|sub| format!("[i] 跳过不支持解析的字幕: {}", sub)),
+    );
+    for sub in &empty_subs {
+        logs.push(format!("[empty-sub] {}", sub));
+    }
+    for (path, reason) in &index.invalid {
+        logs.push(invalid_font_log(path, reason));
+    }
+    let mut starred: Vec<&String> = starred_fonts.iter().collect();
+    starred.sort();
+    for font in starred {
+        logs.push(format!(
+            "[i] 样式字体名带有旧式 '*' 前缀: *{0}，按 {0} 匹配",
+            font
+        ));
+    }
+    logs.append(&mut result.logs);
+    // GDI 加载 Graphite 字体只用到普通的 OpenType 部分，复杂文字要靠支持 Graphite
+    // 的渲染器才能正确成形
+    for entry in &result.entries {
+        if matches!(entry.status, EntryStatus::Loaded | EntryStatus::Duplicate)
+            && let Some(path) = &entry.path
+            && index.graphite.contains(Path::new(path))
+            && entry.sources.iter().any(|sub| complex_subs.contains(sub))
+        {
+            logs.push(format!(
+                "[info] Graphite字体已加载，需支持Graphite的渲染器: {} > {}",
+                entry.name, path
+            ));
+        }
+    }
+    let lacking = find_missing_variants(&required_variants, &index, options);
+    for (font, needed, have) in &lacking {
+        if let Some(entry) = result.entries.iter_mut().find(|e| &e.name == font) {
+            entry.missing_variants = variant_labels(*needed)
+                .into_iter()
+                .map(String::from)
+                .collect();
+            if options.strict_style
+                && matches!(entry.status, EntryStatus::Loaded | EntryStatus::Duplicate)
+            {
+                entry.status = EntryStatus::MissingVariant;
+            }
+        }
+        logs.push(format!(
+            "[??] 缺少变体 {}: 需要{}，匹配的文件只有{}",
+            font,
+            variant_labels(*needed).join("、"),
+            variant_labels(*have).join("、")
+        ));
+    }
+    Ok(ProcessResult {
+        subs: sub_files.len() - empty_subs.len(),
+        empty_subs: empty_subs.len(),
+        fonts: font_files.len(),
+        invalid_fonts: index.invalid.len(),
+        missing_variants: lacking.len(),
+        subtitles: subtitle_reports(&parsed_subs, &result.entries),
+        cache: index.cache_stats,
+        types: index.types,
+        subs_only,
+        rule_hits: rule_hits.into_iter().collect(),
+        logs,
+        index: Some(Arc::new(index)),
+        ..result
+    })
+}
+
+/// 返回 `families` 中与系统已安装字体同名的家族名，比较前按匹配时的规则规范化。
+fn system_conflicts(families: &[String]) -> Vec<String> {
+    let system = system_fonts();
+    families
+        .iter()
+        .filter(|family| {
+            normalize_font_name(family)
+                .is_some_and(|name| system.contains_key(&NameKey::new(&name)))
+        })
+        .cloned()
+        .collect()
+}
+
+impl MatchTest {
+    /// 两个文件都已选择时重新比对。
+    fn refresh(&mut self) {
+        self.report = match (&self.sub, &self.font) {
+            (Some(sub), Some(font)) => Some(match_font_to_sub(sub, font, &self.rules)),
+            _ => None,
+        };
+    }
+}
+
+/// 逐个检查字幕需要的字体名能否由 `font` 提供：先找完全一致的名称，再按
+/// [`NameKey`] 比较；匹配到的名称只作为 PostScript 名出现时单独标出。匹配成功的
+/// 字体名还会检查字幕用到的粗体/斜体变体，规则同 [`find_missing_variants`]。
+///
+/// 不经过别名表和系统字体，只回答“这个文件本身能不能匹配”；两侧的名称照常套用
+/// `rules`。
+fn match_font_to_sub(sub: &Path, font: &Path, rules: &NameRules) -> Result<MatchReport, String> {
+    let parsed =
+        read_ass_fonts(sub).ok_or_else(|| "字幕无法读取、无法解码或内容为空".to_string())?;
+    let mut names = parse_font_names(font);
+    if let Some(reason) = names.invalid {
+        return Err(format!("字体文件无效: {}", reason));
+    }
+    names.names = names.names.iter().filter_map(|n| rules.apply(n)).collect();
+    names.ps_names = names
+        .ps_names
+        .iter()
+        .filter_map(|n| rules.apply(n))
+        .collect();
+    let mut requested: Vec<&String> = parsed.fonts.iter().collect();
+    requested.sort();
+    let rows = requested
+        .into_iter()
+        .map(|name| {
+            let wanted = rules.apply(name).unwrap_or_else(|| name.clone());
+            let key = NameKey::new(&wanted);
+            let exact = names.names.iter().find(|n| **n == wanted);
+            let normalized = names.names.iter().find(|n| NameKey::new(n) == key);
+            let outcome = match exact.or(normalized) {
+                Some(found) if names.ps_names.contains(found) => {
+                    MatchOutcome::PostScript(found.clone())
+                }
+                Some(found) if exact.is_some() => MatchOutcome::Exact(found.clone()),
+                Some(found) => MatchOutcome::Normalized(found.clone()),
+                None => MatchOutcome::None,
+            };
+            let needed =
+                parsed.variants.get(name).copied().unwrap_or(0) & !variant_bit(false, false);
+            let missing_variants = match outcome {
+                MatchOutcome::None => Vec::new(),
+                _ => variant_labels(needed & !names.variants),
+            };
+            MatchRow {
+                name: name.clone(),
+                starred: parsed.starred.contains(name),
+                outcome,
+                missing_variants,
+            }
+        })
+        .collect();
+    Ok(MatchReport {
+        rows,
+        font_names: names.names,
+        families: names.families,
+        ps_names: names.ps_names,
+        variants: names.variants,
+    })
+}
+
+fn ui_match_report(ui: &mut egui::Ui, report: &MatchReport) {
+    let matched = report
+        .rows
+        .iter()
+        .filter(|row| !matches!(row.outcome, MatchOutcome::None))
+        .count();
+    ui.label(format!(
+        "字幕需要 {} 个字体，该文件匹配 {} 个",
+        report.rows.len(),
+        matched
+    ));
+    egui::ScrollArea::vertical()
+        .id_salt("match_rows")
+        .max_height(240.0)
+        .auto_shrink([false, true])
+        .show(ui, |ui| {
+            egui::Grid::new("match_rows_grid")
+                .num_columns(2)
+                .striped(true)
+                .show(ui, |ui| {
+                    for row in &report.rows {
+                        if row.starred {
+                            ui.label(format!("*{}", row.name))
+                                .on_hover_text("样式字体名带有旧式 '*' 前缀，按去掉前缀的名称匹配");
+                        } else {
+                            ui.label(&row.name);
+                        }
+                        let visuals = ui.visuals();
+                        let (color, text) = match &row.outcome {
+                            MatchOutcome::Exact(name) => {
+                                (visuals.text_color(), format!("✔ 匹配 {}", name))
+                            }
+                            MatchOutcome::Normalized(name) => (
+                                visuals.text_color(),
+                                format!("✔ 匹配 {}（仅大小写、全角或不可见字符不同）", name),
+                            ),
+                            MatchOutcome::PostScript(name) => (
+                                visuals.warn_fg_color,
+                                format!(
+                                    "⚠ 只匹配到 PostScript 名 {}，部分播放器可能无法正确渲染",
+                                    name
+                                ),
+                            ),
+                            MatchOutcome::None => (
+                                visuals.error_fg_color,
+                                "✘ 不匹配，该文件没有这个名称".to_string(),
+                            ),
+                        };
+                        ui.vertical(|ui| {
+                            ui.colored_label(color, text);
+                            if !row.missing_variants.is_empty() {
+                                ui.colored_label(
+                                    ui.visuals().warn_fg_color,
+                                    format!(
+                                        "缺少变体: {}，将由渲染器模拟",
+                                        row.missing_variants.join("、")
+                                    ),
+                                );
+                            }
+                        });
+                        ui.end_row();
+                    }
+                });
+        });
+    ui.collapsing(
+        format!("字体文件中的名称 ({})", report.font_names.len()),
+        |ui| {
+            ui.label(format!("家族名: {}", report.families.join(", ")));
+            ui.label(format!(
+                "包含变体: {}",
+                variant_labels(report.variants).join("、")
+            ));
+            for name in &report.font_names {
+                if report.ps_names.contains(name) {
+                    ui.label(format!("{} (PostScript)", name));
+                } else {
+                    ui.label(name);
+                }
+            }
+        },
+    );
+}
+
+/// 找出字幕用到了粗体/斜体变体、而索引中匹配到的全部文件都不提供该变体的字体。
+///
+/// 返回按名称排序的 (字体名, 缺少的变体位, 匹配文件已有的变体位)。常规变体不检查；
+/// 找不到匹配文件或系统已安装的字体跳过，它们另有记录。开启 `options.case_sensitive`
+/// 且有大小写完全一致的匹配时，只看这些文件提供的变体，与加载时的选择一致。
+fn find_missing_variants(
+    required: &HashMap<String, u8>,
+    index: &FontIndex,
+    options: &LoadOptions,
+) -> Vec<(String, u8, u8)> {
+    let system = system_fonts();
+    let mut lacking = Vec::new();
+    for (font, needed) in required {
+        let needed = needed & !variant_bit(false, false);
+        if needed == 0 {
+            continue;
+        }
+        let target = options.aliases.get(&NameKey::new(font)).unwrap_or(font);
+        let key = NameKey::new(target);
+        let in_system_index = options
+            .system_index
+            .as_ref()
+            .is_some_and(|system_index| system_index.names.contains_key(&key));
+        if system.contains_key(&key) || in_system_index {
+            continue;
+        }
+        let exact = index.exact.get(target).filter(|_| options.case_sensitive);
+        let Some(files) = exact.or_else(|| index.names.get(&key)) else {
+            continue;
+        };
+        let have = files
+            .iter()
+            .filter_map(|path| index.variants.get(path))
+            .fold(0, |acc, bits| acc | bits);
+        if needed & !have != 0 {
+            lacking.push((font.clone(), needed & !have, have));
+        }
+    }
+    lacking.sort();
+    lacking
+}
+
+/// 按 `entries` 中每个字体的来源统计 `subs` 中各字幕的覆盖率。已加载、已在别处加载
+/// 和系统已安装的字体算作可用，严格样式模式下缺少变体的不算；没有引用任何字体的
+/// 字幕为 100%。
+fn subtitle_reports(subs: &[String], entries: &[FontEntry]) -> Vec<SubtitleReport> {
+    subs.iter()
+        .map(|sub| {
+            let used: Vec<&FontEntry> =
+                entries.iter().filter(|e| e.sources.contains(sub)).collect();
+            let available = used
+                .iter()
+                .filter(|e| {
+                    matches!(
+                        e.status,
+                        EntryStatus::Loaded | EntryStatus::Duplicate | EntryStatus::System
+                    )
+                })
+                .count();
+            let coverage_pct = if used.is_empty() {
+                100.0
+            } else {
+                available as f32 * 100.0 / used.len() as f32
+            };
+            SubtitleReport {
+                path: sub.clone(),
+                required: used.len(),
+                available,
+                coverage_pct,
+            }
+        })
+        .collect()
+}
+
+/// 在已建立的索引中查找并加载 `required` 中的字体，`required` 每项为字体名及引用它的
+/// 字幕；`options.aliases` 中有记录的名称改用映射后的名称查找。返回的
+/// [`ProcessResult`] 中 `subs`/`fonts` 为 0，由调用方填写。
+///
+/// 开启 `options.case_sensitive` 时优先在 `index.exact`（保留大小写的索引）中取大小写
+/// 完全一致的匹配，找不到再退回不区分大小写的 `index.names`；两者结果不同时记一条
+/// `[case]` 日志。
+///
+/// 字体名只与所选文件的 PostScript 名 (ID 6) 相同、而不是家族名或全名时记一条
+/// `[ps-name]` 警告：libass 能按 PostScript 名找到字体，但其他播放器不一定能。
+///
+/// 用 `, ` 连接 `items`，超过 `limit` 个时只列出前 `limit` 个并注明其余数量。
+/// `limit` 为 0 时全部列出。
+fn join_capped(items: &[String], limit: usize) -> String {
+    if limit == 0 || items.len() <= limit {
+        return items.join(", ");
+    }
+    format!(
+        "{} …及另外 {} 个",
+        items[..limit].join(", "),
+        items.len() - limit
+    )
+}
+
+/// 即将注册的文件若有家族名与系统已安装的字体相同，记一条 `[conflict]` 警告；开启
+/// `options.skip_system_conflicts` 时不加载该文件，条目标记为 [`EntryStatus::Conflict`]。
+///
+/// `register` 注册一个字体文件，返回值同 [`add_font_resource`]，测试时可替换。
+fn load_required_fonts(
+    required: Vec<(String, Vec<String>)>,
+    index: &FontIndex,
+    options: &LoadOptions,
+    state: &mut AppState,
+    progress: &dyn Fn(ProgressPhase, usize, usize),
+    cancel: &AtomicBool,
+    register: &dyn Fn(&str) -> Result<usize, u32>,
+) -> ProcessResult {
+    let mut logs = LogLines::default();
+    let mut loaded = 0;
+    let mut failed = 0;
+    let mut missing = 0;
+    let mut duplicates = 0;
+    let mut installed = 0;
+    let mut system_conflict = 0;
+    let mut entries = Vec::new();
+    let mut batch = LoadBatch::default();
+    let system = system_fonts();
+
+    let total = required.len();
+    for (done, (font, sources)) in required.into_iter().enumerate() {
+        progress(ProgressPhase::Load, done, total);
+        let target = options.aliases.get(&NameKey::new(&font)).unwrap_or(&font);
+        let mut key = NameKey::new(target);
+        // 系统已安装的字体无需重复注册
+        let system_path = system.get(&key).or_else(|| {
+            let index = options.system_index.as_ref()?;
+            index.names.get(&key)?.first()
+        });
+        if let Some(path) = system_path {
+            installed += 1;
+            let path_str = path.to_string_lossy().to_string();
+            logs.push(format!("[sys] {} > {}", font, path_str));
+            entries.push(FontEntry {
+                name: font,
+                path: Some(path_str),
+                status: EntryStatus::System,
+                sources,
+                families: Vec::new(),
+                alternatives: Vec::new(),
+                conflicts: Vec::new(),
+                local_dir: None,
+                missing_variants: Vec::new(),
+            });
+            continue;
+        }
+        let mut folded = index
+            .names
+            .get(&key)
+            .and_then(|files| closest_to_sources(files, &sources));
+        let exact_match = index
+            .exact
+            .get(target)
+            .filter(|_| options.case_sensitive)
+            .and_then(|files| closest_to_sources(files, &sources));
+        // 字幕里写的是缩写时，唯一的前缀匹配就当作要找的字体
+        if exact_match.is_none()
+            && folded.is_none()
+            && let [full] = prefix_lookup(key.as_str(), &index.sorted_keys).as_slice()
+        {
+            let full_key = NameKey::new(full);
+            let full_name = index
+                .exact
+                .keys()
+                .find(|name| NameKey::new(name) == full_key)
+                .unwrap_or(full);
+            logs.push(format!("[prefix-match] '{}' → '{}'", font, full_name));
+            folded = index
+                .names
+                .get(&full_key)
+                .and_then(|files| closest_to_sources(files, &sources));
+            key = full_key;
+        }
+        if let (Some(exact_path), Some(folded_path)) = (exact_match, folded)
+            && exact_path != folded_path
+        {
+            logs.push(format!(
+                "[case] {} > {}（不区分大小写时为 {}）",
+                font,
+                exact_path.to_string_lossy(),
+                folded_path.to_string_lossy()
+            ));
+        }
+        let Some(path) = exact_match.or(folded) else {
+            missing += 1;
+            logs.push(format!("[??] {}", font));
+            entries.push(FontEntry {
+                name: font.clone(),
+                path: None,
+                status: EntryStatus::Missing,
+                sources,
+                families: Vec::new(),
+                alternatives: Vec::new(),
+                conflicts: Vec::new(),
+                local_dir: None,
+                missing_variants: Vec::new(),
+            });
+            continue;
+        };
+        let path_str = path.to_string_lossy().to_string();
+        let families = index.families.get(path).cloned().unwrap_or_default();
+        // 同一文件的家族名与全名可能相同，会在同一个键下出现多次
+        let mut alternatives: Vec<String> = Vec::new();
+        for other in index.names.get(&key).into_iter().flatten() {
+            let other = other.to_string_lossy().to_string();
+            if other != path_str && !alternatives.contains(&other) {
+                alternatives.push(other);
+            }
+        }
+        if !alternatives.is_empty() {
+            logs.push(format!(
+                "[warn-multi] '{}' 找到 {} 个匹配文件，使用: {}，其他: {}",
+                font,
+                alternatives.len() + 1,
+                path_str,
+                join_capped(&alternatives, options.max_logged_candidates)
+            ));
+        }
+        if index
+            .ps_names
+            .get(path)
+            .is_some_and(|ps| ps.iter().any(|name| name.eq_ignore_ascii_case(target)))
+        {
+            logs.push(format!(
+                "[ps-name] {} > {} 只匹配到 PostScript 名，部分播放器可能无法正确渲染",
+                font, path_str
+            ));
+        }
+        let local_dir = bypassed_font_dir(path, &sources, &options.font_folders);
+        if let Some(dir) = &local_dir {
+            logs.push(format!(
+                "[library] {} > {}，字幕旁的字体目录 {} 中没有可用的匹配",
+                font, path_str, dir
+            ));
+        }
+        let path_key = PathKey::new(&path_str);
+        let conflicts = if state.loaded.contains_key(&path_key) {
+            Vec::new()
+        } else {
+            system_conflicts(&families)
+        };
+        if !conflicts.is_empty() {
+            system_conflict += 1;
+            logs.push(format!(
+                "[conflict] {} > {} 将覆盖系统字体: {}",
+                font,
+                path_str,
+                join_capped(&conflicts, options.max_logged_candidates)
+            ));
+        }
+        let mut note = String::new();
+        let mut gdi_failed = false;
+        let status = if !conflicts.is_empty() && options.skip_system_conflicts {
+            EntryStatus::Conflict
+        } else if let Some(entry) = state.loaded.get_mut(&path_key) {
+            duplicates += 1;
+            if !entry.names.contains(&font) {
+                entry.names.push(font.clone());
+                batch.added_names.push((path_str.clone(), font.clone()));
+            }
+            EntryStatus::Duplicate
+        } else if let Err(err) = materialize_archive_member(path) {
+            note = format!(" ({})", err);
+            failed += 1;
+            EntryStatus::Failed
+        } else {
+            let (result, retried) =
+                add_font_resource_with_retry(&path_str, register, options.load_retries, cancel);
+            match result {
+                Ok(faces) => {
+                    if retried > 0 {
+                        note = format!(" (重试 {} 次后成功)", retried);
+                    }
+                    if let Some(registrations) = &options.registrations
+                        && let Ok(mut registrations) = registrations.lock()
+                    {
+                        registrations.push(KeptFont {
+                            path: path_str.clone(),
+                            faces,
+                        });
+                    }
+                    state.loaded.insert(
+                        path_key,
+                        LoadedFont {
+                            path: path_str.clone(),
+                            names: vec![font.clone()],
+                            families: families.clone(),
+                            faces,
+                        },
+                    );
+                    batch.loaded_paths.push(path_str.clone());
+                    loaded += 1;
+                    EntryStatus::Loaded
+                }
+                Err(code) => {
+                    note = load_error_note(code, retried);
+                    gdi_failed = true;
+                    failed += 1;
+                    EntryStatus::Failed
+                }
+            }
+        };
+        logs.push(format!(
+            "{} {} > {}{}",
+            entry_status_label(status),
+            font,
+            path_str,
+            note
+        ));
+        if gdi_failed && index.cff.contains(path) {
+            logs.push(cff_failure_note(&font, &path_str, &alternatives, index));
+        }
+        entries.push(FontEntry {
+            name: font.clone(),
+            path: Some(path_str),
+            status,
+            sources,
+            families,
+            alternatives,
+            conflicts,
+            local_dir,
+            missing_variants: Vec::new(),
+        });
+    }
+    progress(ProgressPhase::Load, total, total);
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+    if loaded > 0 {
+        broadcast_font_change();
+    }
+
+    ProcessResult {
+        loaded,
+        failed,
+        missing,
+        duplicates,
+        installed,
+        system_conflicts: system_conflict,
+        missing_variants: 0,
+        subs: 0,
+        empty_subs: 0,
+        fonts: 0,
+        invalid_fonts: 0,
+        logs,
+        entries,
+        subtitles: Vec::new(),
+        cache: CacheStats::default(),
+        types: FontTypeStats::default(),
+        subs_only: false,
+        rule_hits: Vec::new(),
+        batch,
+        index: None,
+    }
+}
+
+/// 两个目录间的距离：各自去掉公共前缀后剩余的层数之和。
+fn path_distance(a: &Path, b: &Path) -> usize {
+    let common = a
+        .components()
+        .zip(b.components())
+        .take_while(|(x, y)| x == y)
+        .count();
+    a.components().count() + b.components().count() - 2 * common
+}
+
+/// 在同名的多个文件中选出离引用它的字幕最近的一个，距离相同时保持索引顺序。
+///
+/// 发布包自带的字体与字幕位于同一目录树中，比远处字体库中的副本更接近制作时
+/// 实际使用的版本。没有来源字幕时取第一个。
+fn closest_to_sources<'a>(files: &'a [PathBuf], sources: &[String]) -> Option<&'a PathBuf> {
+    files.iter().min_by_key(|file| {
+        let file_dir = file.parent().unwrap_or(Path::new(""));
+        sources
+            .iter()
+            .filter_map(|sub| Path::new(sub).parent())
+            .map(|sub_dir| path_distance(sub_dir, file_dir))
+            .min()
+            .unwrap_or(0)
+    })
+}
+
+/// `path` 不在任何来源字幕的目录树中、而某个字幕旁却有 `font_folders` 约定的字体
+/// 目录时返回该目录，通常说明本地副本解析失败或缺少该字体。
+fn bypassed_font_dir(path: &Path, sources: &[String], font_folders: &[String]) -> Option<String> {
+    let local = sources.iter().filter_map(|sub| Path::new(sub).parent());
+    if local.clone().any(|sub_dir| path.starts_with(sub_dir)) {
+        return None;
+    }
+    sources.iter().find_map(|sub| {
+        let dirs = font_folder_dirs(std::slice::from_ref(sub), font_folders);
+        dirs.first().map(|dir| dir.to_string_lossy().to_string())
+    })
+}
+
+/// 撤销一次加载：卸载该批次新注册的文件，并去掉追加到其他文件上的字体名。
+fn undo_batch_worker(
+    batch: LoadBatch,
+    mode: LoadMode,
+    state: Arc<Mutex<AppState>>,
+) -> Result<UnloadResult, String> {
+    let mut state = state.lock().map_err(|_| "状态锁失败".to_string())?;
+    let mut count = 0;
+    for path in &batch.loaded_paths {
+        let key = PathKey::new(path);
+        if state.loaded.contains_key(&key) && remove_font_resource(path, mode) {
+            state.loaded.remove(&key);
+            count += 1;
+        }
+    }
+    for (path, name) in &batch.added_names {
+        if let Some(entry) = state.loaded.get_mut(&PathKey::new(path)) {
+            entry.names.retain(|n| n != name);
+        }
+    }
+    if count > 0 {
+        broadcast_font_change();
+    }
+    Ok(UnloadResult {
+        count,
+        still_present: Vec::new(),
+    })
+}
+
+/// 以 GDI 枚举到的已安装字体代替拖入的字体文件作为匹配来源，按与加载相同的别名和
+/// 名称规范化规则检查字幕所需字体。本程序加载的字体也会被枚举到，除非同名字体也登记
+/// 在系统中，否则从枚举结果中去掉，以得到纯净系统上的结果。不在系统中的字体再到拖入的
+/// 文件中查找，作为需要打包的文件。
+fn system_coverage_worker(
+    paths: Vec<String>,
+    options: &LoadOptions,
+    state: Arc<Mutex<AppState>>,
+    progress: &dyn Fn(ProgressPhase, usize, usize),
+    cancel: &AtomicBool,
+) -> Result<SystemCoverage, String> {
+    let index_progress = |done, total| progress(ProgressPhase::Index, done, total);
+    let scan = scan_inputs(&paths, options, &index_progress, cancel)?;
+    let registered = system_fonts();
+    let loaded_by_us: HashSet<NameKey> = {
+        let state = state.lock().map_err(|_| "状态锁失败".to_string())?;
+        state
+            .loaded
+            .values()
+            .flat_map(|font| font.families.iter().chain(&font.names))
+            .map(|name| NameKey::new(name))
+            .filter(|key| !registered.contains_key(key))
+            .collect()
+    };
+    let installed: HashMap<NameKey, String> = enumerate_font_families()
+        .into_iter()
+        .filter_map(|name| options.name_rules.apply(&normalize_font_name(&name)?))
+        .map(|name| (NameKey::new(&name), name))
+        .filter(|(key, _)| !loaded_by_us.contains(key))
+        .collect();
+
+    let mut required: Vec<&String> = scan.required_fonts.iter().collect();
+    required.sort();
+    let mut covered = Vec::new();
+    let mut bundle = Vec::new();
+    for font in required {
+        let target = options.aliases.get(&NameKey::new(font)).unwrap_or(font);
+        let key = NameKey::new(target);
+        if let Some(name) = installed.get(&key) {
+            covered.push((font.clone(), name.clone()));
+            continue;
+        }
+        let sources = scan
+            .font_sources
+            .get(font)
+            .map(Vec::as_slice)
+            .unwrap_or(&[]);
+        let file = scan
+            .index
+            .names
+            .get(&key)
+            .and_then(|files| closest_to_sources(files, sources))
+            .map(|path| path.to_string_lossy().to_string());
+        bundle.push((font.clone(), file));
+    }
+    Ok(SystemCoverage { covered, bundle })
+}
+
+/// 只在 `inputs`（目录或字体文件）中查找并加载 `missing` 中的字体。
+fn resolve_missing_worker(
+    inputs: Vec<PathBuf>,
+    missing: Vec<FontEntry>,
+    options: &LoadOptions,
+    state: Arc<Mutex<AppState>>,
+    progress: &dyn Fn(ProgressPhase, usize, usize),
+    cancel: &AtomicBool,
+) -> Result<ProcessResult, String> {
+    let inputs: Vec<String> = inputs
+        .iter()
+        .map(|path| path.to_string_lossy().to_string())
+        .collect();
+    let font_files: Vec<PathBuf> = collect_files(&inputs, None, &mut Vec::new(), &mut 0)?
+        .into_iter()
+        .filter(|path| is_font_file(path) && !is_excluded_font(options, path))
+        .collect();
+    let use_cache = options.use_cache;
+    let mut cache = if use_cache {
+        load_cache_file(options.cache_ttl_days).0
+    } else {
+        CacheFile::default()
+    };
+    let index_progress = |done, total| progress(ProgressPhase::Index, done, total);
+    let index = build_font_index(
+        &font_files,
+        use_cache,
+        &mut cache,
+        &options.name_rules,
+        &index_progress,
+        cancel,
+    );
+    if use_cache {
+        let _ = save_cache_file(&cache);
+    }
+    if cancel.load(Ordering::Relaxed) {
+        return Err("已取消".to_string());
+    }
+
+    let required = missing
+        .into_iter()
+        .map(|entry| (entry.name, entry.sources))
+        .collect();
+    let mut state = state.lock().map_err(|_| "状态锁失败".to_string())?;
+    let register = |path: &str| add_font_resource(path, options.mode);
+    let mut result = load_required_fonts(
+        required, &index, options, &mut state, progress, cancel, &register,
+    );
+    let mut logs: LogLines = index
+        .rule_hits
+        .iter()
+        .map(|(from, to)| format!("[i] 字体名规则: {} -> {}", from, to))
+        .collect();
+    logs.extend(
+        index
+            .invalid
+            .iter()
+            .map(|(path, reason)| invalid_font_log(path, reason)),
+    );
+    logs.append(&mut result.logs);
+    Ok(ProcessResult {
+        fonts: font_files.len(),
+        invalid_fonts: index.invalid.len(),
+        cache: index.cache_stats,
+        types: index.types,
+        rule_hits: index.rule_hits.into_iter().collect(),
+        logs,
+        ..result
+    })
+}
+
+fn restore_fonts_worker(
+    fonts: Vec<ManifestFont>,
+    mode: LoadMode,
+    state: Arc<Mutex<AppState>>,
+) -> Result<ProcessResult, String> {
+    let mut logs = LogLines::default();
+    let mut entries = Vec::new();
+    let mut loaded = 0;
+    let mut failed = 0;
+    let mut missing = 0;
+    let mut duplicates = 0;
+    // 清单只记录匹配名，显示用的各语言家族名从缓存取，不重新解析
+    let (cache, _) = load_cache_file(0);
+
+    let mut state = state.lock().map_err(|_| "状态锁失败".to_string())?;
+    for font in fonts.iter() {
+        let families = cache
+            .entries
+            .get(&font.path)
+            .map(|entry| entry.families.clone())
+            .unwrap_or_default();
+        let name = if font.names.is_empty() {
+            Path::new(&font.path)
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| font.path.clone())
+        } else {
+            font.names.join(", ")
+        };
+        let status = if !Path::new(&font.path).is_file() {
+            missing += 1;
+            EntryStatus::Missing
+        } else {
+            match state.loaded.entry(PathKey::new(&font.path)) {
+                std::collections::hash_map::Entry::Occupied(_) => {
+                    duplicates += 1;
+                    EntryStatus::Duplicate
+                }
+                std::collections::hash_map::Entry::Vacant(slot) => {
+                    if let Ok(faces) = add_font_resource(&font.path, mode) {
+                        slot.insert(LoadedFont {
+                            path: font.path.clone(),
+                            names: font.names.clone(),
+                            families: families.clone(),
+                            faces,
+                        });
+                        loaded += 1;
+                        EntryStatus::Loaded
+                    } else {
+                        failed += 1;
+                        EntryStatus::Failed
+                    }
+                }
+            }
+        };
+        logs.push(format!(
+            "{} {} > {}",
+            entry_status_label(status),
+            name,
+            font.path
+        ));
+        entries.push(FontEntry {
+            name,
+            path: Some(font.path.clone()),
+            status,
+            sources: Vec::new(),
+            families,
+            alternatives: Vec::new(),
+            conflicts: Vec::new(),
+            local_dir: None,
+            missing_variants: Vec::new(),
+        });
+    }
+
+    if loaded > 0 {
+        broadcast_font_change();
+    }
+
+    Ok(ProcessResult {
+        loaded,
+        failed,
+        missing,
+        duplicates,
+        installed: 0,
+        system_conflicts: 0,
+        missing_variants: 0,
+        subs: 0,
+        empty_subs: 0,
+        fonts: fonts.len(),
+        invalid_fonts: 0,
+        logs,
+        entries,
+        subtitles: Vec::new(),
+        cache: CacheStats::default(),
+        types: FontTypeStats::default(),
+        subs_only: false,
+        rule_hits: Vec::new(),
+        batch: LoadBatch::default(),
+        index: None,
+    })
+}
+
+/// 卸载已加载的字体，`only` 为 `None` 时卸载全部。
+///
+/// `verify` 为真时，卸载后逐个检查被移除字体的家族名是否仍可通过 GDI 枚举，排除
+/// 系统已安装的同名字体；仍存在的通常是被其他程序占用，记录在 `still_present` 中。
+fn unload_fonts_worker(
+    state: Arc<Mutex<AppState>>,
+    only: Option<Vec<String>>,
+    verify: bool,
+    mode: LoadMode,
+) -> Result<UnloadResult, String> {
+    unload_fonts_with(state, only, verify, &|path| {
+        remove_font_resource(path, mode)
+    })
+}
+
+/// [`unload_fonts_worker`] 的实现，`remove` 注销一次文件的注册，测试时可替换。
+fn unload_fonts_with(
+    state: Arc<Mutex<AppState>>,
+    only: Option<Vec<String>>,
+    verify: bool,
+    remove: &dyn Fn(&str) -> bool,
+) -> Result<UnloadResult, String> {
+    let mut state = state.lock().map_err(|_| "状态锁失败".to_string())?;
+    // 按登记时的原始路径卸载，传入路径的大小写可能不同；同一文件的不同写法只卸载一次，
+    // 否则会多减一次系统的注册计数
+    let targets: Vec<(PathKey, String)> = match only {
+        Some(paths) => {
+            let mut seen = HashSet::new();
+            paths
+                .iter()
+                .filter_map(|p| {
+                    let key = PathKey::new(p);
+                    let path = state.loaded.get(&key)?.path.clone();
+                    seen.insert(key.clone()).then_some((key, path))
+                })
+                .collect()
+        }
+        None => state
+            .loaded
+            .iter()
+            .map(|(key, font)| (key.clone(), font.path.clone()))
+            .collect(),
+    };
+    let mut count = 0;
+    let mut removed_names = Vec::new();
+    for (key, path) in targets.iter() {
+        if remove(path) {
+            count += 1;
+            if let Some(font) = state.loaded.remove(key) {
+                let names = if font.families.is_empty() {
+                    font.names
+                } else {
+                    font.families
+                };
+                push_unique_names(&mut removed_names, names);
+            }
+        }
+    }
+    if count > 0 {
+        broadcast_font_change();
+    }
+    let mut still_present = Vec::new();
+    if verify && !removed_names.is_empty() {
+        let installed = system_fonts();
+        for name in removed_names {
+            if !installed.contains_key(&NameKey::new(&name)) && font_family_present(&name) {
+                still_present.push(name);
+            }
+        }
+    }
+    Ok(UnloadResult {
+        count,
+        still_present,
+    })
+}
+
+/// 把会话中保留的字体列入已加载表，返回该表和释放的重复注册数。
+///
+/// 旧版会话文件中同一文件可能以不同大小写各注册过一次，已加载表按 [`PathKey`] 只能
+/// 记一项，之后也只会卸载一次，因此多出的注册在这里用 `remove` 立即释放。
+fn restore_kept_fonts(
+    kept: &[KeptFont],
+    remove: &dyn Fn(&str) -> bool,
+) -> (HashMap<PathKey, LoadedFont>, usize) {
+    let mut loaded = HashMap::new();
+    let mut released = 0;
+    for font in kept {
+        match loaded.entry(PathKey::new(&font.path)) {
+            std::collections::hash_map::Entry::Occupied(_) => {
+                if remove(&font.path) {
+                    released += 1;
+                }
+            }
+            std::collections::hash_map::Entry::Vacant(slot) => {
+                slot.insert(LoadedFont {
+                    path: font.path.clone(),
+                    faces: font.faces,
+                    ..LoadedFont::default()
+                });
+            }
+        }
+    }
+    (loaded, released)
+}
+
+/// 把来不及卸载的字体分成为播放器保留的（`keep` 中的）和下次启动时要卸载的两部分。
+fn outstanding_session(fonts: Vec<KeptFont>, keep: &HashSet<PathKey>) -> SessionState {
+    let registered_faces = fonts.iter().map(|font| font.faces).sum();
+    let (kept_fonts, orphaned_fonts) = fonts
+        .into_iter()
+        .partition(|font| keep.contains(&PathKey::new(&font.path)));
+    SessionState {
+        registered_faces,
+        kept_fonts,
+        orphaned_fonts,
+    }
+}
+
+/// 用 `remove` 卸载会话中记录的来不及卸载的字体，返回成功卸载的文件数和仍无法
+/// 卸载的路径。
+fn unload_orphaned_fonts(
+    orphaned: &[KeptFont],
+    remove: &dyn Fn(&str) -> bool,
+) -> (usize, Vec<String>) {
+    let mut unloaded = 0;
+    let mut failed = Vec::new();
+    for font in orphaned {
+        if remove(&font.path) {
+            unloaded += 1;
+        } else {
+            failed.push(font.path.clone());
+        }
+    }
+    (unloaded, failed)
+}
+
+/// 单个文件最多移除的注册次数，防止 RemoveFontResourceW 一直返回成功时陷入死循环。
+const MAX_FONT_RELEASES: usize = 1000;
+
+/// 反复移除文件的注册直到失败，返回成功移除的次数。
+fn release_font_references(path: &str) -> usize {
+    let mut count = 0;
+    while count < MAX_FONT_RELEASES && remove_font_resource(path, LoadMode::Public) {
+        count += 1;
+    }
+    count
+}
+
+fn clean_folder_worker(folder: PathBuf) -> Result<UnloadResult, String> {
+    let mut files = Vec::new();
+    let _ = walk_dir(&folder, &mut files, None);
+    let mut count = 0;
+    for path in files {
+        if is_font_file(&path) {
+            count += release_font_references(&path.to_string_lossy());
+        }
+    }
+    if count > 0 {
+        broadcast_font_change();
+    }
+    Ok(UnloadResult {
+        count,
+        still_present: Vec::new(),
+    })
+}
+
+/// 强制卸载 `paths` 的全部注册，返回每个文件释放的引用数。
+fn clean_files_worker(paths: Vec<String>) -> Result<Vec<(String, usize)>, String> {
+    let released: Vec<(String, usize)> = paths
+        .into_iter()
+        .map(|path| {
+            let count = release_font_references(&path);
+            (path, count)
+        })
+        .collect();
+    if released.iter().any(|(_, count)| *count > 0) {
+        broadcast_font_change();
+    }
+    Ok(released)
+}
+
+/// 查找解析出的字体名与 `name` 一致（按匹配时的规则规范化、不区分大小写）的字体文件：
+/// 依次查字体名缓存中的全部条目、会话累积的索引，以及重新索引 `dirs` 中的字体文件。
+/// 只返回仍然存在的文件，排序去重。
+fn find_font_files_worker(
+    name: &str,
+    dirs: &[PathBuf],
+    session: &FontIndex,
+    rules: &NameRules,
+    use_cache: bool,
+    progress: &dyn Fn(usize, usize),
+    cancel: &AtomicBool,
+) -> Result<Vec<String>, String> {
+    let name = normalize_font_name(name)
+        .and_then(|name| rules.apply(&name))
+        .ok_or_else(|| "字体名为空".to_string())?;
+    let key = NameKey::new(&name);
+    // 查找的是磁盘上的文件，过期的条目同样可以找到它们
+    let (mut cache, _) = load_cache_file(0);
+    let mut found: Vec<String> = cache
+        .entries
+        .iter()
+        .filter(|(_, entry)| {
+            entry
+                .names
+                .iter()
+                .chain(&entry.families)
+                .filter_map(|n| rules.apply(n))
+                .any(|n| NameKey::new(&n) == key)
+        })
+        .map(|(path, _)| path.clone())
+        .collect();
+    found.extend(
+        session
+            .names
+            .get(&key)
+            .into_iter()
+            .flatten()
+            .map(|path| path.to_string_lossy().to_string()),
+    );
+    let dirs: Vec<String> = dirs
+        .iter()
+        .filter(|dir| dir.is_dir())
+        .map(|dir| dir.to_string_lossy().to_string())
+        .collect();
+    let font_files: Vec<PathBuf> = collect_files(&dirs, None, &mut Vec::new(), &mut 0)?
+        .into_iter()
+        .filter(|path| is_font_file(path))
+        .collect();
+    let index = build_font_index(&font_files, use_cache, &mut cache, rules, progress, cancel);
+    if use_cache {
+        let _ = save_cache_file(&cache);
+    }
+    if cancel.load(Ordering::Relaxed) {
+        return Err("已取消".to_string());
+    }
+    found.extend(
+        index
+            .names
+            .get(&key)
+            .into_iter()
+            .flatten()
+            .map(|path| path.to_string_lossy().to_string()),
+    );
+    found.retain(|path| Path::new(path).is_file());
+    found.sort();
+    found.dedup();
+    Ok(found)
+}
+
+/// 为一组字体文件建立 "小写字体名 -> 文件路径列表" 的索引。
+///
+/// 每个文件的名称来自 `name` 表中的家族名 (ID 1) 和全名 (ID 4)。`use_cache` 为真时，
+/// 修改时间与 `cache` 中记录一致的文件直接复用缓存的名称，其余文件重新解析并写回
+/// `cache`（调用方负责随后把缓存保存到磁盘）。同名的多个文件按 `font_files` 的顺序
+/// 追加到同一个键下，匹配时取第一个。
+///
+/// `progress` 在处理每个文件前以 `(已处理, 总数)` 调用一次，结束时以 `(总数, 总数)`
+/// 再调用一次。`cancel` 在文件之间检查，置位后立即停止并返回已建立的部分索引。
+///
+/// 同时返回每个文件的各语言家族名（供界面显示）和包含的字形变体位。缺少家族名、
+/// 变体或 PostScript 名信息的旧缓存条目，以及未通过 [`verify_ttc_entry`] 的 TTC 条目视为失效，重新
+/// 解析后覆盖。解析不出字体名的文件同样写入缓存并记下原因，文件未变化前直接跳过，
+/// 计入 [`CacheStats::invalid`] 而不是 `hits`。
+///
+/// 缓存中保存原名，索引中的名称（包括 PostScript 名）套用 `rules` 之后的结果，改写
+/// 记录在 [`FontIndex::rule_hits`] 中。
+fn build_font_index(
+    font_files: &[PathBuf],
+    use_cache: bool,
+    cache: &mut CacheFile,
+    rules: &NameRules,
+    progress: &dyn Fn(usize, usize),
+    cancel: &AtomicBool,
+) -> FontIndex {
+    let mut index: HashMap<NameKey, Vec<PathBuf>> = HashMap::new();
+    let mut exact: HashMap<String, Vec<PathBuf>> = HashMap::new();
+    let mut families = HashMap::new();
+    let mut variants = HashMap::new();
+    let mut ps_names = HashMap::new();
+    let mut graphite = HashSet::new();
+    let mut cff = HashSet::new();
+    let mut invalid = Vec::new();
+    let mut rule_hits = BTreeSet::new();
+    let mut cache_stats = CacheStats::default();
+    let mut types = FontTypeStats::default();
+    let total = font_files.len();
+    for (done, path) in font_files.iter().enumerate() {
+        if cancel.load(Ordering::Relaxed) {
+            break;
+        }
+        progress(done, total);
+        let path_str = path.to_string_lossy().to_string();
+        // 压缩包成员没有修改时间，不进缓存
+        let use_cache = use_cache && archive_member(path).is_none();
+        let font_names = match cache.entries.get(&path_str) {
+            Some(entry)
+                if use_cache
+                    && entry.invalid.is_some()
+                    && metadata_mtime(path) == Some(entry.modified) =>
+            {
+                cache_stats.invalid += 1;
+                FontNames {
+                    invalid: entry.invalid.clone(),
+                    ..FontNames::default()
+                }
+            }
+            Some(entry)
+                if use_cache
+                    && !entry.families.is_empty()
+                    && entry.variants != 0
+                    && entry.ps_names.is_some()
+                    && entry.graphite_capable.is_some()
+                    && entry.cff_outlines.is_some()
+                    && metadata_mtime(path) == Some(entry.modified)
+                    && cached_ttc_entry_ok(path, entry) =>
+            {
+                cache_stats.hits += 1;
+                FontNames {
+                    names: entry.names.clone(),
+                    families: entry.families.clone(),
+                    variants: entry.variants,
+                    invalid: None,
+                    ps_names: entry.ps_names.clone().unwrap_or_default(),
+                    graphite: entry.graphite_capable.unwrap_or(false),
+                    cff: entry.cff_outlines.unwrap_or(false),
+                }
+            }
+            stale => {
+                let font_names = parse_font_names(path);
+                if use_cache {
+                    if stale.is_some() {
+                        cache_stats.stale += 1;
+                    } else {
+                        cache_stats.added += 1;
+                    }
+                    cache.entries.insert(
+                        path_str.clone(),
+                        CacheEntry {
+                            modified: metadata_mtime(path).unwrap_or(0),
+                            names: font_names.names.clone(),
+                            families: font_names.families.clone(),
+                            variants: font_names.variants,
+                            invalid: font_names.invalid.clone(),
+                            ps_names: Some(font_names.ps_names.clone()),
+                            cached_at: unix_now(),
+                            graphite_capable: Some(font_names.graphite),
+                            cff_outlines: Some(font_names.cff),
+                        },
+                    );
+                }
+                font_names
+            }
+        };
+        for name in font_names.names {
+            let Some(name) = rules.apply_logged(&name, &mut rule_hits) else {
+                continue;
+            };
+            index
+                .entry(NameKey::new(&name))
+                .or_default()
+                .push(path.clone());
+            exact.entry(name).or_default().push(path.clone());
+        }
+        if let Some(reason) = font_names.invalid {
+            invalid.push((path.clone(), reason));
+            continue;
+        }
+        types.count(path);
+        families.insert(path.clone(), font_names.families);
+        variants.insert(path.clone(), font_names.variants);
+        if font_names.graphite {
+            graphite.insert(path.clone());
+        }
+        if font_names.cff {
+            cff.insert(path.clone());
+        }
+        let font_ps_names: Vec<String> = font_names
+            .ps_names
+            .iter()
+            .filter_map(|name| rules.apply(name))
+            .collect();
+        if !font_ps_names.is_empty() {
+            ps_names.insert(path.clone(), font_ps_names);
+        }
+    }
+    progress(total, total);
+    FontIndex {
+        sorted_keys: sorted_name_keys(&index),
+        names: index,
+        exact,
+        families,
+        variants,
+        ps_names,
+        graphite,
+        cff,
+        invalid,
+        rule_hits,
+        cache_stats,
+        types,
+        source: FontSource::User,
+    }
+}
+
+/// 检查 TTC 缓存条目是否包含所有成员字体的名称：每个成员至少贡献一个名称，
+/// 因此名称数不应少于头部声明的成员数。非 TTC 数据总是通过。
+fn verify_ttc_entry(data: &[u8], entry: &CacheEntry) -> bool {
+    if data.len() < 12 || &data[0..4] != b"ttcf" {
+        return true;
+    }
+    let num_members = read_u32_be(data, 8).unwrap_or(0) as usize;
+    entry.names.len() >= num_members
+}
+
+/// 只读取 TTC 文件头交给 [`verify_ttc_entry`]，其他文件不读盘。
+fn cached_ttc_entry_ok(path: &Path, entry: &CacheEntry) -> bool {
+    let is_ttc = path
+        .extension()
+        .and_then(|v| v.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("ttc"));
+    if !is_ttc {
+        return true;
+    }
+    match read_ttc_header(path) {
+        Some(header) => verify_ttc_entry(&header, entry),
+        None => false,
+    }
+}
+
+fn read_ttc_header(path: &Path) -> Option<Vec<u8>> {
+    if let Some(data) = archive_member(path) {
+        return Some(data[..data.len().min(12)].to_vec());
+    }
+    let mut header = Vec::with_capacity(12);
+    fs::File::open(path)
+        .and_then(|file| file.take(12).read_to_end(&mut header))
+        .ok()?;
+    Some(header)
+}
+
+/// 读取 TTC 头部声明的成员字体数，不是 TTC 或无法读取时返回 `None`。
+fn ttc_member_count(path: &Path) -> Option<usize> {
+    let header = read_ttc_header(path)?;
+    if header.len() < 12 || &header[0..4] != b"ttcf" {
+        return None;
+    }
+    read_u32_be(&header, 8).map(|n| n as usize)
+}
+
+fn metadata_mtime(path: &Path) -> Option<u64> {
+    let metadata = fs::metadata(path).ok()?;
+    let modified = metadata.modified().ok()?;
+    let duration = modified.duration_since(UNIX_EPOCH).ok()?;
+    Some(duration.as_secs())
+}
+
+/// 读取字幕文件并收集所需的字体，文件无法读取、无法解码或内容为空时返回 `None`。
+///
+/// 超过 4 MiB 的文件通过 [`for_each_text_line`] 边读边解析，其余整体读入后交给
+/// [`parse_ass_fonts`]，两者结果相同。
+fn read_ass_fonts(path: &Path) -> Option<AssFonts> {
+    if let Some(data) = archive_member(path) {
+        let text = decode_text(data.to_vec()).filter(|text| !text.trim().is_empty())?;
+        return Some(parse_ass_fonts(&text));
+    }
+    let large = fs::metadata(path).is_ok_and(|m| m.len() > 4 * 1024 * 1024);
+    if !large {
+        let text = read_text(path).filter(|text| !text.trim().is_empty())?;
+        return Some(parse_ass_fonts(&text));
+    }
+    stream_ass_fonts(path)
+}
+
+/// [`read_ass_fonts`] 的流式路径：经 [`for_each_text_line`] 逐行喂给 [`AssFontParser`]。
+fn stream_ass_fonts(path: &Path) -> Option<AssFonts> {
+    let mut parser = AssFontParser::default();
+    let non_blank = for_each_text_line(path, &mut |line| parser.feed_line(line))?;
+    non_blank.then(|| parser.finish())
+}
+
+/// 读取字幕文件并解码为字符串。
+///
+/// 根据 BOM 识别 UTF-16 LE/BE 和 UTF-8。无 BOM 时若 `[Script Info]` 中声明了编码
+/// （见 [`detect_charset_hint`]）则按声明解码，否则按 UTF-8 解码。文件无法读取、
+/// 内容不是合法 UTF-8 或 UTF-16 长度为奇数时返回 `None`。
+fn read_text(path: &Path) -> Option<String> {
+    decode_text(fs::read(path).ok()?)
+}
+
+/// [`read_text`] 的解码部分，供已在内存中的字幕使用。
+fn decode_text(data: Vec<u8>) -> Option<String> {
+    if data.starts_with(&[0xFF, 0xFE]) {
+        return decode_utf16(&data[2..], true);
+    }
+    if data.starts_with(&[0xFE, 0xFF]) {
+        return decode_utf16(&data[2..], false);
+    }
+    if data.starts_with(&[0xEF, 0xBB, 0xBF]) {
+        return String::from_utf8(data[3..].to_vec()).ok();
+    }
+    if let Some(encoding) = detect_charset_hint(&data)
+        && encoding != encoding_rs::UTF_8
+    {
+        let (text, _) = encoding.decode_without_bom_handling(&data);
+        return Some(text.into_owned());
+    }
+    String::from_utf8(data).ok()
+}
+
+/// 在 `[Script Info]` 节中查找作者声明的编码，形如 `; charset: GBK` 或
+/// `; Encoding=Shift_JIS`，注释符可省略。标签按 WHATWG 编码名解析，无法识别时忽略。
+/// 只看第一个节：第一个节头不是 `[Script Info]` 时视为没有声明。
+///
+/// 节头和键名都是 ASCII，各类 ASCII 兼容编码下可以直接按字节逐行匹配。
+fn detect_charset_hint(data: &[u8]) -> Option<&'static encoding_rs::Encoding> {
+    let mut scanner = CharsetHintScanner::default();
+    data.split(|&b| b == b'\n')
+        .find_map(|raw| scanner.feed(raw))
+        .flatten()
+}
+
+/// [`detect_charset_hint`] 的逐行版本。
+#[derive(Default)]
+struct CharsetHintScanner {
+    in_script_info: bool,
+}
+
+impl CharsetHintScanner {
+    /// 处理一行原始字节。找到编码声明时返回 `Some(Some(_))`，`[Script Info]` 节结束
+    /// 或遇到其他节头时返回 `Some(None)`，尚无结论时返回 `None`。
+    fn feed(&mut self, raw: &[u8]) -> Option<Option<&'static encoding_rs::Encoding>> {
+        let line = String::from_utf8_lossy(raw);
+        let line = line.trim();
+        if line.starts_with('[') {
+            // 缺少 `[Script Info]` 的文件在第一个节头就得出结论，流式读取不必缓存全文
+            if self.in_script_info || !line.eq_ignore_ascii_case("[script info]") {
+                return Some(None);
+            }
+            self.in_script_info = true;
+            return None;
+        }
+        if !self.in_script_info {
+            return None;
+        }
+        let line = line.trim_start_matches([';', '!', ':']).trim_start();
+        let (key, value) = line.split_once([':', '='])?;
+        let key = key.trim();
+        if key.eq_ignore_ascii_case("charset") || key.eq_ignore_ascii_case("encoding") {
+            let encoding = encoding_rs::Encoding::for_label(value.trim().as_bytes())?;
+            return Some(Some(encoding));
+        }
+        None
+    }
+}
+
+/// 逐行读取并解码字幕文件，每行按 [`str::lines`] 的规则去掉换行符后交给 `on_line`。
+///
+/// 编码识别与 [`read_text`] 相同，结果逐行一致。无 BOM 时先缓存原始行直到
+/// [`CharsetHintScanner`] 得出结论（`[Script Info]` 节末尾或第一个其他节头），之后
+/// 边读边解码，峰值内存只取决于最长的行。文件无法读取、内容不是合法 UTF-8 或 UTF-16
+/// 长度为奇数时返回 `None`，此前 `on_line` 收到的行应作废；否则返回内容是否含有非空白
+/// 字符。
+fn for_each_text_line(path: &Path, on_line: &mut dyn FnMut(&str)) -> Option<bool> {
+    let mut reader = BufReader::new(fs::File::open(path).ok()?);
+    let head = reader.fill_buf().ok()?;
+    let mut non_blank = false;
+    let mut emit = |line: &str| {
+        non_blank |= !line.trim().is_empty();
+        on_line(line);
+    };
+    if head.starts_with(&[0xFF, 0xFE]) {
+        reader.consume(2);
+        stream_decoded_lines(reader, encoding_rs::UTF_16LE, true, &mut emit)?;
+    } else if head.starts_with(&[0xFE, 0xFF]) {
+        reader.consume(2);
+        stream_decoded_lines(reader, encoding_rs::UTF_16BE, true, &mut emit)?;
+    } else if head.starts_with(&[0xEF, 0xBB, 0xBF]) {
+        reader.consume(3);
+        stream_utf8_lines(reader, Vec::new(), &mut emit)?;
+    } else {
+        let mut scanner = CharsetHintScanner::default();
+        let mut buffered = Vec::new();
+        let mut hint = None;
+        loop {
+            let start = buffered.len();
+            if reader.read_until(b'\n', &mut buffered).ok()? == 0 {
+                break;
+            }
+            let raw = buffered[start..]
+                .strip_suffix(b"\n")
+                .unwrap_or(&buffered[start..]);
+            if let Some(found) = scanner.feed(raw) {
+                hint = found;
+                break;
+            }
+        }
+        match hint {
+            Some(encoding) if encoding != encoding_rs::UTF_8 => {
+                let chained = std::io::Cursor::new(buffered).chain(reader);
+                stream_decoded_lines(BufReader::new(chained), encoding, false, &mut emit)?;
+            }
+            _ => stream_utf8_lines(reader, buffered, &mut emit)?,
+        }
+    }
+    Some(non_blank)
+}
+
+/// 以严格 UTF-8 逐行解码，`buffered` 为已从 `reader` 读出的开头部分。
+fn stream_utf8_lines(
+    mut reader: impl BufRead,
+    buffered: Vec<u8>,
+    emit: &mut dyn FnMut(&str),
+) -> Option<()> {
+    // 合法 UTF-8 的多字节序列中不会出现 `\n`，按字节切行再逐行校验与整体校验等价
+    for raw in buffered.split_inclusive(|&b| b == b'\n') {
+        emit(strip_line_ending(std::str::from_utf8(raw).ok()?));
+    }
+    let mut raw = Vec::new();
+    loop {
+        raw.clear();
+        if reader.read_until(b'\n', &mut raw).ok()? == 0 {
+            return Some(());
+        }
+        emit(strip_line_ending(std::str::from_utf8(&raw).ok()?));
+    }
+}
+
+/// 用 `encoding` 的流式解码器逐块解码，再按 `\n` 切出完整的行。`utf16` 为真时
+/// 与 [`decode_utf16`] 一样拒绝奇数长度的内容。
+fn stream_decoded_lines(
+    mut reader: impl BufRead,
+    encoding: &'static encoding_rs::Encoding,
+    utf16: bool,
+    emit: &mut dyn FnMut(&str),
+) -> Option<()> {
+    let mut decoder = encoding.new_decoder_without_bom_handling();
+    let mut pending = String::new();
+    let mut total = 0usize;
+    loop {
+        let chunk = reader.fill_buf().ok()?;
+        let last = chunk.is_empty();
+        let len = chunk.len();
+        total += len;
+        if let Some(needed) = decoder.max_utf8_buffer_length(len) {
+            pending.reserve(needed);
+        }
+        let (_, read, _) = decoder.decode_to_string(chunk, &mut pending, last);
+        reader.consume(read);
+        while let Some(pos) = pending.find('\n') {
+            emit(strip_line_ending(&pending[..=pos]));
+            pending.drain(..=pos);
+        }
+        if last {
+            break;
+        }
+    }
+    if utf16 && !total.is_multiple_of(2) {
+        return None;
+    }
+    if !pending.is_empty() {
+        emit(strip_line_ending(&pending));
+    }
+    Some(())
+}
+
+/// 与 [`str::lines`] 相同：去掉末尾的 `\n` 或 `\r\n`，单独的 `\r` 保留。
+fn strip_line_ending(line: &str) -> &str {
+    match line.strip_suffix('\n') {
+        Some(line) => line.strip_suffix('\r').unwrap_or(line),
+        None => line,
+    }
+}
+
+fn decode_utf16(data: &[u8], little_endian: bool) -> Option<String> {
+    if !data.len().is_multiple_of(2) {
+        return None;
+    }
+    let mut buf = Vec::with_capacity(data.len() / 2);
+    let mut i = 0;
+    while i + 1 < data.len() {
+        let value = if little_endian {
+            u16::from_le_bytes([data[i], data[i + 1]])
+        } else {
+            u16::from_be_bytes([data[i], data[i + 1]])
+        };
+        buf.push(value);
+        i += 2;
+    }
+    Some(String::from_utf16_lossy(&buf))
+}
+
+/// 从 ASS/SSA 字幕全文中收集所需的字体名。
+///
+/// 两个来源：`[V4+ Styles]`/`[V4 Styles]` 节中每个 `Style:` 行的 Fontname 字段，以及
+/// `[Events]` 节中 `Dialogue:`/`Comment:` 行 Text 字段里的 `\fn` 覆盖标签。字段位置取自
+/// 各节的 `Format:` 行；缺少 `Format:` 时按标准 ASS 布局，Fontname 为第 2 个字段、
+/// Text 为第 10 个字段。节名只做包含匹配，其他节被忽略。
+///
+/// `[Fonts]`/`[Graphics]` 节中是 UUEncode 编码的附件，编码字符包含 `[`、`]` 和大写
+/// 字母，可能拼出形似节头或 `STYLE:` 的行。在这两节内只有含小写字母的 `[...]` 行
+/// 才被当作新节头（UUEncode 不使用小写字母），其余行一律跳过。
+///
+/// 返回的名称已经过 [`normalize_font_name`] 处理，按原样大小写去重。样式 Fontname
+/// 带有 `*` 前缀的名称另外记入 `starred`。
+///
+/// 同时记录各字体需要的字形变体：样式的 Bold/Italic 字段（缺少 `Format:` 时为第 8、9
+/// 个字段），以及事件正文中 `\b`、`\i`、`\fn`、`\r` 覆盖标签作用下每段文字的实际
+/// 组合，见 [`record_event_variants`]。
+///
+/// # Example
+///
+/// ```ignore
+/// let text = "[V4+ Styles]\n\
+///             Format: Name, Fontname, Fontsize\n\
+///             Style: Default,思源黑体,60\n\
+///             [Events]\n\
+///             Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n\
+///             Dialogue: 0,0:00:00.00,0:00:01.00,Default,,0,0,0,,{\\fnArial}Hi\n";
+/// let parsed = parse_ass_fonts(text);
+/// assert!(parsed.fonts.contains("思源黑体") && parsed.fonts.contains("Arial"));
+/// ```
+fn parse_ass_fonts(text: &str) -> AssFonts {
+    let mut parser = AssFontParser::default();
+    for line in text.lines() {
+        parser.feed_line(line);
+    }
+    parser.finish()
+}
+
+/// [`parse_ass_fonts`] 的逐行版本，按顺序喂入字幕的每一行（不含换行符），最后调用
+/// [`AssFontParser::finish`] 取得结果。
+#[derive(Default)]
+struct AssFontParser {
+    parsed: AssFonts,
+    section: String,
+    style_font_idx: Option<usize>,
+    style_name_idx: Option<usize>,
+    style_bold_idx: Option<usize>,
+    style_italic_idx: Option<usize>,
+    event_style_idx: Option<usize>,
+    event_text_idx: Option<usize>,
+    /// 小写样式名 -> 样式设置，供事件查找
+    styles: HashMap<String, StyleDef>,
+}
+
+impl AssFontParser {
+    fn feed_line(&mut self, raw: &str) {
+        let line = raw.trim();
+        let in_attachments = self.section == "fonts" || self.section == "graphics";
+        if in_attachments
+            && !(line.starts_with('[')
+                && line.ends_with(']')
+                && line.bytes().any(|b| b.is_ascii_lowercase()))
+        {
+            return;
+        }
+        if let Some(header) = line.strip_prefix('[') {
+            // 缺少 `]` 的残缺节头视为未知节，避免后续行沿用上一节的解析状态
+            self.section = match header.find(']') {
+                Some(end) => header[..end].trim().to_lowercase(),
+                None => String::new(),
+            };
+            return;
+        }
+        let lower = line.to_lowercase();
+        let parsed = &mut self.parsed;
+        if self.section == "script info" {
+            if let Some(lang) = lower.strip_prefix("language:")
+                && is_complex_script_language(lang.trim())
+            {
+                parsed.complex_script = true;
+            }
+        } else if self.section.contains("styles") {
+            if lower.starts_with("format:") {
+                let format = parse_format(line, 7);
+                self.style_font_idx = format.iter().position(|v| v == "fontname");
+                self.style_name_idx = format.iter().position(|v| v == "name");
+                self.style_bold_idx = format.iter().position(|v| v == "bold");
+                self.style_italic_idx = format.iter().position(|v| v == "italic");
+            } else if lower.starts_with("style:")
+                && let Some((font, starred)) = parse_style_font(line, self.style_font_idx)
+            {
+                if starred {
+                    parsed.starred.insert(font.clone());
+                }
+                let style = StyleDef {
+                    font: font.clone(),
+                    bold: style_flag(line, self.style_bold_idx, 7),
+                    italic: style_flag(line, self.style_italic_idx, 8),
+                };
+                *parsed.variants.entry(font.clone()).or_default() |=
+                    variant_bit(style.bold, style.italic);
+                if let Some(name) = style_field(line, self.style_name_idx, 0) {
+                    self.styles.insert(name.to_lowercase(), style);
+                }
+                parsed.fonts.insert(font);
+            }
+        } else if self.section.contains("events") {
+            if lower.starts_with("format:") {
+                let format = parse_format(line, 7);
+                self.event_text_idx = format.iter().position(|v| v == "text");
+                self.event_style_idx = format.iter().position(|v| v == "style");
+            } else if (lower.starts_with("dialogue:") || lower.starts_with("comment:"))
+                && let Some(text) = extract_event_text(line, self.event_text_idx)
+            {
+                for font in parse_fn_tags(&text) {
+                    parsed.fonts.insert(font);
+                }
+                if !parsed.complex_script && text.chars().any(is_complex_script_char) {
+                    parsed.complex_script = true;
+                }
+                let style = extract_event_style(line, self.event_style_idx)
+                    .and_then(|name| self.styles.get(&name.to_lowercase()));
+                record_event_variants(&text, style, &self.styles, &mut parsed.variants);
+            }
+        }
+    }
+
+    fn finish(self) -> AssFonts {
+        self.parsed
+    }
+}
+
+/// 按 BCP 47 主语言子标签判断是否为常用 Graphite 排版的复杂文字语言，如 `ur`
+/// （乌尔都语，Nastaliq 字体）、`my`、`km`。
+fn is_complex_script_language(tag: &str) -> bool {
+    const COMPLEX: &[&str] = &[
+        "ar", "fa", "ur", "ps", "sd", "ug", "ks", "pa-arab", "syr", "dv", "my", "km", "lo", "shn",
+        "kjp", "ksw",
+    ];
+    let tag = tag.replace('_', "-");
+    let primary = tag.split('-').next().unwrap_or("");
+    COMPLEX.contains(&tag.as_str()) || COMPLEX.contains(&primary)
+}
+
+/// 阿拉伯、叙利亚、它拿、缅甸、高棉、老挝文字的字符。
+fn is_complex_script_char(c: char) -> bool {
+    matches!(c,
+        '\u{0600}'..='\u{07BF}'
+        | '\u{0860}'..='\u{08FF}'
+        | '\u{0E80}'..='\u{0EFF}'
+        | '\u{1000}'..='\u{109F}'
+        | '\u{1780}'..='\u{17FF}'
+        | '\u{FB50}'..='\u{FDFF}'
+        | '\u{FE70}'..='\u{FEFF}')
+}
+
+fn parse_format(line: &str, start: usize) -> Vec<String> {
+    let content = line[start..].trim();
+    content
+        .split(',')
+        .map(|v| v.trim().to_lowercase())
+        .collect()
+}
+
+/// 取 `Style:` 行的第 `idx` 个字段，`idx` 为 `None` 时取第 `default` 个。
+fn style_field(line: &str, idx: Option<usize>, default: usize) -> Option<&str> {
+    line[6..]
+        .trim()
+        .split(',')
+        .nth(idx.unwrap_or(default))
+        .map(str::trim)
+}
+
+fn parse_style_font(line: &str, idx: Option<usize>) -> Option<(String, bool)> {
+    split_font_name(style_field(line, idx, 1)?)
+}
+
+/// 样式的 Bold/Italic 字段：`-1` 为开，`0` 为关，其他非零值也按开处理。
+fn style_flag(line: &str, idx: Option<usize>, default: usize) -> bool {
+    style_field(line, idx, default)
+        .and_then(|v| v.parse::<i32>().ok())
+        .is_some_and(|v| v != 0)
+}
+
+fn extract_event_text(line: &str, idx: Option<usize>) -> Option<String> {
+    // `Dialogue:` 与 `Comment:` 前缀长度不同，按第一个冒号切分
+    let (_, content) = line.split_once(':')?;
+    let index = idx.unwrap_or(9);
+    // Text 是最后一个字段，本身可以包含逗号，只切出前 index 个字段
+    let text = content
+        .trim_start()
+        .splitn(index + 1, ',')
+        .nth(index)
+        .unwrap_or("");
+    Some(text.to_string())
+}
+
+fn extract_event_style(line: &str, idx: Option<usize>) -> Option<&str> {
+    let (_, content) = line.split_once(':')?;
+    content
+        .trim_start()
+        .split(',')
+        .nth(idx.unwrap_or(3))
+        .map(str::trim)
+}
+
+/// 按顺序应用事件文本中的 `\fn`、`\b`、`\i`、`\r` 覆盖标签，把每段正文实际使用的
+/// 字体与粗体/斜体组合记入 `variants`。
+///
+/// 初始状态取事件所用的样式 `style`；`\rName` 在 `styles` 中按名称查找，找不到或
+/// 不带名称时回到事件样式，空的 `\fn` 只恢复样式字体。`\b` 取 1 或不小于 600 的字重
+/// 为粗体。样式未知且尚未出现 `\fn` 时字体不确定，不记录。
+fn record_event_variants(
+    text: &str,
+    style: Option<&StyleDef>,
+    styles: &HashMap<String, StyleDef>,
+    variants: &mut HashMap<String, u8>,
+) {
+    let style_font = style.map(|s| s.font.clone());
+    let mut font = style_font.clone();
+    let mut bold = style.is_some_and(|s| s.bold);
+    let mut italic = style.is_some_and(|s| s.italic);
+    let mut rest = text;
+    while !rest.is_empty() {
+        let (plain, block) = rest.split_at(rest.find('{').unwrap_or(rest.len()));
+        if !plain.is_empty()
+            && let Some(font) = &font
+        {
+            *variants.entry(font.clone()).or_default() |= variant_bit(bold, italic);
+        }
+        if block.is_empty() {
+            break;
+        }
+        let end = block.find('}').map(|i| i + 1).unwrap_or(block.len());
+        // 按 `\` 切分后，`\t(...)` 内的标签带有多余的右括号
+        for tag in block[1..end].trim_end_matches('}').split('\\').skip(1) {
+            let tag = tag.trim().trim_end_matches(')').trim_end();
+            if let Some(name) = tag.strip_prefix("fn") {
+                let name = name.trim_start().trim_start_matches('(');
+                font = normalize_font_name(name).or_else(|| style_font.clone());
+            } else if let Some(value) = tag.strip_prefix('b')
+                && let Ok(value) = value.parse::<u32>()
+            {
+                bold = value == 1 || value >= 600;
+            } else if let Some(value) = tag.strip_prefix('i')
+                && let Ok(value) = value.parse::<u32>()
+            {
+                italic = value != 0;
+            } else if let Some(name) = tag.strip_prefix('r') {
+                let target = styles.get(&name.trim().to_lowercase()).or(style);
+                font = target.map(|s| s.font.clone());
+                bold = target.is_some_and(|s| s.bold);
+                italic = target.is_some_and(|s| s.italic);
+            }
+        }
+        rest = &block[end..];
+    }
+}
+
+/// 提取一段事件文本中所有 `\fn` 标签指定的字体名。
+///
+/// 支持 `\fnName` 与 `\fn(Name)` 两种写法：前者的名称截止到下一个 `\` 或 `}`，
+/// 位于 `\t(...)` 等带括号的标签内时也截止到闭合该标签的右括号；后者截止到右括号。
+/// 括号只在 `{}` 覆盖块内计数，正文中的括号不影响结果。
+///
+/// 转义序列 `\h`、`\n`、`\N` 在两种写法中都结束名称，不计入名称：`\fnArial\h`
+/// 与 `\fn(Arial\h)` 都得到 `Arial`，名称处紧跟转义的 `\fn\h` 视为空的 `\fn`。名称经过
+/// [`normalize_font_name`] 处理，空的 `\fn`（恢复样式字体）不产生结果。结果按出现
+/// 顺序返回，可能包含重复项。
+///
+/// # Example
+///
+/// ```ignore
+/// let names = parse_fn_tags("{\\fnArial\\b1}A{\\fn(Source Han Sans)}B{\\fn}C");
+/// assert_eq!(names, vec!["Arial", "Source Han Sans"]);
+/// let names = parse_fn_tags("{\\k20\\t(0,200,\\fnSimHei)}卡{\\k30}拉");
+/// assert_eq!(names, vec!["SimHei"]);
+/// let names = parse_fn_tags("{\\fn(Arial\\h)}A{\\fn\\hSimHei}B");
+/// assert_eq!(names, vec!["Arial"]);
+/// ```
+fn parse_fn_tags(text: &str) -> Vec<String> {
+    let mut res = Vec::new();
+    let mut in_block = false;
+    // 当前覆盖块内未闭合的括号层数，如 `\t(` 之后为 1
+    let mut depth = 0usize;
+    let mut i = 0;
+    while let Some(ch) = text[i..].chars().next() {
+        if let Some(after) = text[i..].strip_prefix("\\fn") {
+            let s = after.trim_start();
+            let skipped = i + 3 + (after.len() - s.len());
+            if let Some(inner) = s.strip_prefix('(')
+                && let Some(end) = inner.find(')')
+            {
+                let name = &inner[..end];
+                let name_end = ["\\h", "\\n", "\\N"]
+                    .iter()
+                    .filter_map(|escape| name.find(escape))
+                    .min()
+                    .unwrap_or(name.len());
+                if let Some(normalized) = normalize_font_name(&name[..name_end]) {
+                    res.push(normalized);
+                }
+                i = skipped + 1 + end + 1;
+                continue;
+            }
+            let end = s
+                .find(|c: char| c == '\\' || c == '}' || (c == ')' && depth > 0))
+                .unwrap_or(s.len());
+            if let Some(normalized) = normalize_font_name(&s[..end]) {
+                res.push(normalized);
+            }
+            i = skipped + end;
+            continue;
+        }
+        match ch {
+            '{' => {
+                in_block = true;
+                depth = 0;
+            }
+            '}' => {
+                in_block = false;
+                depth = 0;
+            }
+            '(' if in_block => depth += 1,
+            ')' if in_block => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        i += ch.len_utf8();
+    }
+    res
+}
+
+/// 规范化单个字体名：去掉首尾空白和 NUL，并去掉表示竖排的前导 `@` 和旧式
+/// "不替换"标记 `*`。
+///
+/// 各只去掉一个，`@*Name` 与 `*@Name` 都得到 `Name`；大小写保持不变，结果为空时
+/// 返回 `None`。字幕与字体文件两侧的名称都经过这里，保证比较时处理一致。
+///
+/// # Example
+///
+/// ```ignore
+/// assert_eq!(normalize_font_name(" @微软雅黑 ").as_deref(), Some("微软雅黑"));
+/// assert_eq!(normalize_font_name("@*Tahoma").as_deref(), Some("Tahoma"));
+/// assert_eq!(normalize_font_name("\0\0"), None);
+/// ```
+fn normalize_font_name(name: &str) -> Option<String> {
+    split_font_name(name).map(|(s, _)| s)
+}
+
+/// 同 [`normalize_font_name`]，另外返回名称是否带有 `*` 前缀。
+fn split_font_name(name: &str) -> Option<(String, bool)> {
+    let mut s = name.trim().trim_matches('\u{0}');
+    let vertical = s.strip_prefix('@');
+    s = vertical.unwrap_or(s);
+    let starred = s.strip_prefix('*');
+    s = starred.unwrap_or(s);
+    if vertical.is_none() && starred.is_some() {
+        s = s.strip_prefix('@').unwrap_or(s);
+    }
+    let s = s.trim();
+    if s.is_empty() {
+        None
+    } else {
+        Some((s.to_string(), starred.is_some()))
+    }
+}
+
+/// 一条字体名改写规则。`pattern` 是 `regex` crate 语法的正则表达式，名称中所有匹配处
+/// 替换为 `replace`，其中可用 `$1`、`${1}` 引用分组。区分大小写。
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+struct NameRule {
+    pattern: String,
+    replace: String,
+}
+
+impl NameRule {
+    fn compile(&self) -> Result<regex::Regex, String> {
+        regex::Regex::new(&self.pattern).map_err(|e| e.to_string())
+    }
+}
+
+/// 设置中无法解析的规则及原因，这些规则不会生效。
+fn name_rule_errors(rules: &[NameRule]) -> Vec<String> {
+    rules
+        .iter()
+        .filter_map(|rule| {
+            let err = rule.compile().err()?;
+            Some(format!("{}: {}", rule.pattern, err))
+        })
+        .collect()
+}
+
+/// 编译好的字体名改写规则，随 [`LoadOptions::name_rules`] 传给每次处理。
+///
+/// 字幕中的字体名在解析后、字体文件中的名称在建立索引时套用，缓存中保存的是原名。
+/// 系统已安装字体的注册表名称不套用。
+#[derive(Clone, Default)]
+struct NameRules(Vec<(NameRule, regex::Regex)>);
+
+impl NameRules {
+    /// 无法解析的规则被跳过，见 [`name_rule_errors`]。
+    fn new(rules: &[NameRule]) -> Self {
+        NameRules(
+            rules
+                .iter()
+                .filter_map(|rule| Some((rule.clone(), rule.compile().ok()?)))
+                .collect(),
+        )
+    }
+
+    /// 按顺序套用全部规则，每条规则作用在上一条的结果上；改写后为空时返回 `None`。
+    fn apply(&self, name: &str) -> Option<String> {
+        let mut result = name.to_string();
+        for (rule, regex) in &self.0 {
+            if regex.is_match(&result) {
+                result = regex
+                    .replace_all(&result, rule.replace.as_str())
+                    .into_owned();
+            }
+        }
+        let result = result.trim();
+        (!result.is_empty()).then(|| result.to_string())
+    }
+
+    /// 同 [`Self::apply`]，名称被改写时把 `(原名, 改写后)` 记入 `hits`，改写为空的
+    /// 记为空字符串。
+    fn apply_logged(&self, name: &str, hits: &mut BTreeSet<(String, String)>) -> Option<String> {
+        let result = self.apply(name);
+        if result.as_deref() != Some(name) {
+            hits.insert((name.to_string(), result.clone().unwrap_or_default()));
+        }
+        result
+    }
+}
+
+/// 解析设置页中的规则，每行 `正则 => 替换`，替换可以为空；没有 `=>` 的行忽略。
+fn parse_name_rules(text: &str) -> Vec<NameRule> {
+    text.lines()
+        .filter_map(|line| {
+            let (pattern, replace) = line.split_once("=>")?;
+            let pattern = pattern.trim();
+            (!pattern.is_empty()).then(|| NameRule {
+                pattern: pattern.to_string(),
+                replace: replace.trim().to_string(),
+            })
+        })
+        .collect()
+}
+
+fn format_name_rules(rules: &[NameRule]) -> String {
+    rules
+        .iter()
+        .map(|rule| format!("{} => {}", rule.pattern, rule.replace))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// 读取字体文件并返回其中的全部名称，文件无法读取时返回空列表。
+///
+/// 详见 [`parse_font_names_from_bytes`]。
+fn parse_font_names(path: &Path) -> FontNames {
+    if let Some(data) = archive_member(path) {
+        return parse_font_names_from_bytes(&data);
+    }
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(err) => {
+            return FontNames {
+                invalid: Some(format!("无法读取: {}", err)),
+                ..FontNames::default()
+            };
+        }
+    };
+    parse_font_names_from_bytes(&data)
+}
+
+/// 从 TrueType/OpenType 字体或 TTC 字体集合的完整内容中解析字体名。
+///
+/// 以 `ttcf` 开头的数据按字体集合处理，合并每个成员字体的名称；否则按单个 sfnt
+/// 处理。只收集 Windows 平台 (platform 3) 的家族名 (ID 1)、全名 (ID 4) 和 PostScript
+/// 名 (ID 6)，不同语言的记录（如 0x0409 英文与 0x0804 简体中文）全部保留，去重后
+/// [`build_font_index`] 为其中每个名称建立索引。数据截断或格式错误时不会 panic，
+/// 只返回能读到的部分（可能为空）。
+fn parse_font_names_from_bytes(data: &[u8]) -> FontNames {
+    if data.len() < 4 {
+        return FontNames {
+            invalid: Some(invalid_font_reason(data, 0, None)),
+            ..FontNames::default()
+        };
+    }
+    let offsets = if &data[0..4] == b"ttcf" {
+        parse_ttc_offsets(data)
+    } else {
+        vec![0]
+    };
+    // 保持各成员给出的顺序，系统区域的名称在前
+    let mut names: Vec<String> = Vec::new();
+    let mut ps_names = HashSet::new();
+    // 各成员的家族名与全名（小写）；TTC 中某个成员的 PostScript 名可能是另一个成员
+    // 的家族名或全名
+    let mut plain = HashSet::new();
+    let mut families = Vec::new();
+    let mut variants = 0;
+    let mut graphite = false;
+    let mut cff = false;
+    let mut damaged = None;
+    for offset in &offsets {
+        let offset = *offset;
+        let member = parse_otf_names_at(data, offset);
+        if member.invalid.is_some() {
+            damaged = member.invalid;
+        }
+        plain.extend(
+            member
+                .names
+                .iter()
+                .filter(|name| !member.ps_names.contains(name))
+                .map(|name| name.to_lowercase()),
+        );
+        for name in member.names {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        ps_names.extend(member.ps_names);
+        push_unique_names(&mut families, member.families);
+        variants |= parse_face_variant(data, offset);
+        graphite |= find_sfnt_table(data, offset, b"SILf").is_some();
+        cff |= find_sfnt_table(data, offset, b"glyf").is_none()
+            && (find_sfnt_table(data, offset, b"CFF ").is_some()
+                || find_sfnt_table(data, offset, b"CFF2").is_some());
+    }
+    let invalid = names
+        .is_empty()
+        .then(|| invalid_font_reason(data, offsets.len(), damaged));
+    let ps_names = ps_names
+        .into_iter()
+        .filter(|name| !plain.contains(&name.to_lowercase()))
+        .collect();
+    FontNames {
+        names,
+        families,
+        variants,
+        invalid,
+        ps_names,
+        graphite,
+        cff,
+    }
+}
+
+/// 没有解析出任何字体名时给出的原因，按文件头区分扩展名不符与字体损坏；
+/// `damaged` 为 [`parse_otf_names_at`] 检出的表目录或 `name` 表结构问题。
+fn invalid_font_reason(data: &[u8], members: usize, damaged: Option<String>) -> String {
+    match data.get(0..4) {
+        None => "文件过短".to_string(),
+        Some(b"ttcf") if members == 0 => "TTC 头部没有成员字体".to_string(),
+        Some(b"ttcf" | [0, 1, 0, 0] | b"OTTO" | b"true") => {
+            damaged.unwrap_or_else(|| "没有可用的 Windows 字体名".to_string())
+        }
+        Some(b"wOFF" | b"wOF2") => "WOFF 字体，不支持".to_string(),
+        Some(_) => "不是 TrueType/OpenType 字体".to_string(),
+    }
+}
+
+/// 按顺序追加不重复（忽略大小写）的名称。
+fn push_unique_names(list: &mut Vec<String>, names: Vec<String>) {
+    for name in names {
+        if !list.iter().any(|n| n.eq_ignore_ascii_case(&name)) {
+            list.push(name);
+        }
+    }
+}
+
+/// 读取 TTC 头部中各成员字体表目录的偏移，`data` 必须从 `ttcf` 标记开始。
+///
+/// 越界的偏移记录会被跳过，因此返回数量可能少于头部声明的字体数。
+fn parse_ttc_offsets(data: &[u8]) -> Vec<usize> {
+    if data.len() < 12 {
+        return Vec::new();
+    }
+    let num_fonts = read_u32_be(data, 8).unwrap_or(0) as usize;
+    let mut offsets = Vec::new();
+    let mut pos = 12;
+    for _ in 0..num_fonts {
+        if let Some(val) = read_u32_be(data, pos) {
+            offsets.push(val as usize);
+        }
+        pos += 4;
+    }
+    offsets
+}
+
+/// 解析位于 `offset` 处的 sfnt 表目录所属字体的 `name` 表。
+///
+/// `data` 必须是整个字体文件（或整个 TTC 容器）的内容，不能是从成员偏移处切出的
+/// 片段；单个字体传入 `0`，TTC 成员传入 [`parse_ttc_offsets`] 返回的偏移。返回已规范化
+/// 的 Windows 平台家族名、全名与 PostScript 名（与前两者相同的不单独记入 `ps_names`），
+/// 以及按语言区分的家族名（英文在前）；找不到 `name` 表
+/// 或数据越界时返回空结果，表目录或 `name` 表头不可信时在 `invalid` 中给出原因。
+fn parse_otf_names_at(data: &[u8], offset: usize) -> FontNames {
+    if data.len() < offset + 12 {
+        return FontNames::default();
+    }
+    if let Err(reason) = sfnt_num_tables(data, offset) {
+        return FontNames {
+            invalid: Some(reason),
+            ..FontNames::default()
+        };
+    }
+    let Some((table_offset, length)) = find_sfnt_table(data, offset, b"name") else {
+        return FontNames::default();
+    };
+    // 表目录中的偏移相对于整个文件，TTC 成员也不例外
+    let table_pos = table_offset;
+    if data.len() < table_pos + length || data.len() < table_pos + 6 {
+        return FontNames::default();
+    }
+    let count = read_u16_be(data, table_pos + 2).unwrap_or(0) as usize;
+    let string_offset = read_u16_be(data, table_pos + 4).unwrap_or(0) as usize;
+    // 字符串区必须位于全部记录之后，否则按偏移读出的是记录本身
+    if string_offset < 6 + count * 12 {
+        return FontNames {
+            invalid: Some(format!(
+                "name 表损坏: 字符串区偏移 {} 与 {} 条名称记录重叠",
+                string_offset, count
+            )),
+            ..FontNames::default()
+        };
+    }
+    let lcid = get_system_lcid();
+    // 同一名称 ID 有多种语言时，系统区域的记录排在最前，其次 0x0409 (美式英语)
+    let rank = |language: u16| match language {
+        _ if language == lcid => 0,
+        0x0409 => 1,
+        _ => 2,
+    };
+    let mut ranked = Vec::new();
+    let mut ps = Vec::new();
+    let mut ranked_families = Vec::new();
+    for (language, name_id, name) in windows_name_records(data, table_pos, &[1, 4, 6]) {
+        if let Some(normalized) = normalize_font_name(&name) {
+            if name_id == 6 {
+                ps.push(normalized);
+                continue;
+            }
+            if name_id == 1 {
+                // 主语言 ID 0x09 为英语，不区分地区
+                let family_rank = match language {
+                    _ if language == lcid => 0,
+                    _ if language & 0x3FF == 0x09 => 1,
+                    _ => 2,
+                };
+                ranked_families.push((family_rank, normalized.clone()));
+            }
+            ranked.push((rank(language), normalized));
+        }
+    }
+    ranked.sort_by_key(|(rank, _)| *rank);
+    ranked_families.sort_by_key(|(rank, _)| *rank);
+    let mut result: Vec<String> = Vec::new();
+    for (_, name) in ranked {
+        if !result.contains(&name) {
+            result.push(name);
+        }
+    }
+    let mut families = Vec::new();
+    push_unique_names(
+        &mut families,
+        ranked_families.into_iter().map(|(_, name)| name).collect(),
+    );
+    // 与家族名或全名相同的 PostScript 名不单独记录
+    let ps_names: Vec<String> = ps
+        .into_iter()
+        .filter(|name| !result.iter().any(|n| n.eq_ignore_ascii_case(name)))
+        .collect();
+    result.extend(ps_names.iter().cloned());
+    FontNames {
+        names: result,
+        families,
+        variants: 0,
+        invalid: None,
+        ps_names,
+        graphite: false,
+        cff: false,
+    }
+}
+
+/// 遍历位于 `table_pos` 的 `name` 表中 Windows 平台、UTF-16 编码、名称 ID 属于 `ids`
+/// 的记录，返回 `(语言, 名称 ID, 解码后的字符串)`；空记录和越界的记录跳过。
+fn windows_name_records(data: &[u8], table_pos: usize, ids: &[u16]) -> Vec<(u16, u16, String)> {
+    let count = read_u16_be(data, table_pos + 2).unwrap_or(0) as usize;
+    let string_offset = read_u16_be(data, table_pos + 4).unwrap_or(0) as usize;
+    let records_start = table_pos + 6;
+    let mut records = Vec::new();
+    for i in 0..count {
+        let rec = records_start + i * 12;
+        if data.len() < rec + 12 {
+            break;
+        }
+        let platform = read_u16_be(data, rec).unwrap_or(0);
+        let encoding = read_u16_be(data, rec + 2).unwrap_or(0);
+        let language = read_u16_be(data, rec + 4).unwrap_or(0);
+        let name_id = read_u16_be(data, rec + 6).unwrap_or(0);
+        let length = read_u16_be(data, rec + 8).unwrap_or(0) as usize;
+        let offset_str = read_u16_be(data, rec + 10).unwrap_or(0) as usize;
+        // 编码 0 (Symbol)、1 (BMP) 和 10 (完整 Unicode) 都是 UTF-16BE，后者含代理对；
+        // 2~6 是旧式东亚代码页，不按 UTF-16 解码
+        if platform != 3 || !matches!(encoding, 0 | 1 | 10) {
+            continue;
+        }
+        if !ids.contains(&name_id) {
+            continue;
+        }
+        let str_start = table_pos + string_offset + offset_str;
+        let str_end = str_start + length;
+        if data.len() < str_end || length == 0 {
+            continue;
+        }
+        records.push((language, name_id, decode_utf16be(&data[str_start..str_end])));
+    }
+    records
+}
+
+/// 从 `cache` 取 `path` 的版本信息摘要，没有时读取文件并记下；读不到时为空字符串。
+fn cached_font_meta(cache: &mut HashMap<String, Option<FontMeta>>, path: &str) -> String {
+    cache
+        .entry(path.to_string())
+        .or_insert_with(|| read_font_meta(Path::new(path)))
+        .as_ref()
+        .map(FontMeta::summary)
+        .unwrap_or_default()
+}
+
+/// 读取字体文件（TTC 取第一个成员）的版本、厂商与修订号，文件无法读取时返回 `None`。
+fn read_font_meta(path: &Path) -> Option<FontMeta> {
+    let data = match archive_member(path) {
+        Some(data) => data.to_vec(),
+        None => fs::read(path).ok()?,
+    };
+    let offset = if data.starts_with(b"ttcf") {
+        *parse_ttc_offsets(&data).first()?
+    } else {
+        0
+    };
+    sfnt_num_tables(&data, offset).ok()?;
+    let mut meta = FontMeta::default();
+    if let Some((table_pos, _)) = find_sfnt_table(&data, offset, b"name")
+        && data.len() >= table_pos + 6
+    {
+        let lcid = get_system_lcid();
+        let mut records = windows_name_records(&data, table_pos, &[5, 8]);
+        records.sort_by_key(|(language, _, _)| match *language {
+            language if language == lcid => 0,
+            0x0409 => 1,
+            _ => 2,
+        });
+        for (_, name_id, text) in records {
+            let text = text.trim().to_string();
+            let slot = if name_id == 5 {
+                &mut meta.version
+            } else {
+                &mut meta.manufacturer
+            };
+            if slot.is_none() && !text.is_empty() {
+                *slot = Some(text);
+            }
+        }
+    }
+    meta.revision = find_sfnt_table(&data, offset, b"head")
+        .and_then(|(pos, _)| read_u32_be(&data, pos + 4))
+        .map(|fixed| fixed as i32 as f64 / 65536.0);
+    Some(meta)
+}
+
+/// 当前用户的区域设置 ID，用于在 `name` 表中优先选用该语言的记录。进程内只读取一次。
+#[cfg(target_os = "windows")]
+fn get_system_lcid() -> u16 {
+    static LCID: OnceLock<u16> = OnceLock::new();
+    // LCID 的低 16 位即 name 表记录使用的语言 ID
+    *LCID.get_or_init(|| unsafe { GetUserDefaultLCID() } as u16)
+}
+
+#[cfg(not(target_os = "windows"))]
+fn get_system_lcid() -> u16 {
+    0x0409
+}
+
+/// 在位于 `offset` 处的 sfnt 表目录中查找标签为 `tag` 的表，返回其偏移（相对于整个
+/// 文件）和长度。
+fn find_sfnt_table(data: &[u8], offset: usize, tag: &[u8; 4]) -> Option<(usize, usize)> {
+    let num_tables = sfnt_num_tables(data, offset).ok()?;
+    let table_start = offset + 12;
+    for i in 0..num_tables {
+        let rec = table_start + i * 16;
+        if &data[rec..rec + 4] == tag {
+            let table_offset = read_u32_be(data, rec + 8).unwrap_or(0) as usize;
+            let length = read_u32_be(data, rec + 12).unwrap_or(0) as usize;
+            return Some((table_offset, length));
+        }
+    }
+    None
+}
+
+/// 表目录损坏时原因的前缀，日志中据此标记为 `[bad-table-dir]`。
+const BAD_TABLE_DIR: &str = "表目录损坏";
+
+/// 读取位于 `offset` 处的 sfnt 表目录声明的表数，并检查其是否可信：声明的表记录必须
+/// 完整落在数据内，且记录指向的表不能与表目录本身重叠。`numTables` 虚高时后面的
+/// "记录"其实是表数据，而真实的表会落在声明的目录范围内，因此能被检出。
+fn sfnt_num_tables(data: &[u8], offset: usize) -> Result<usize, String> {
+    let num_tables = read_u16_be(data, offset + 4).ok_or("文件过短")? as usize;
+    if num_tables == 0 {
+        return Err(format!("{}: numTables 为 0", BAD_TABLE_DIR));
+    }
+    let dir_end = offset + 12 + num_tables * 16;
+    if data.len() < dir_end {
+        return Err(format!(
+            "{}: numTables 为 {}，表目录需要 {} 字节，超出文件长度 {}",
+            BAD_TABLE_DIR,
+            num_tables,
+            dir_end - offset,
+            data.len()
+        ));
+    }
+    for i in 0..num_tables {
+        let rec = offset + 12 + i * 16;
+        let table_offset = read_u32_be(data, rec + 8).unwrap_or(0) as usize;
+        let length = read_u32_be(data, rec + 12).unwrap_or(0) as usize;
+        if length > 0 && table_offset < dir_end && table_offset.saturating_add(length) > offset {
+            return Err(format!(
+                "{}: numTables 为 {}，但第 {} 条记录指向表目录内部",
+                BAD_TABLE_DIR,
+                num_tables,
+                i + 1
+            ));
+        }
+    }
+    Ok(num_tables)
+}
+
+/// 无效字体文件的日志行，表目录损坏的单独标记。
+fn invalid_font_log(path: &Path, reason: &str) -> String {
+    let label = if reason.starts_with(BAD_TABLE_DIR) {
+        "[bad-table-dir]"
+    } else {
+        "[invalid]"
+    };
+    format!("{} {}: {}", label, path.display(), reason)
+}
+
+/// 常规、粗体、斜体、粗斜体四种字形变体各占一位。
+fn variant_bit(bold: bool, italic: bool) -> u8 {
+    1 << (bold as u8 | (italic as u8) << 1)
+}
+
+/// 变体位对应的名称，按常规、粗体、斜体、粗斜体的顺序。
+fn variant_labels(bits: u8) -> Vec<&'static str> {
+    ["常规", "粗体", "斜体", "粗斜体"]
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| bits & (1 << i) != 0)
+        .map(|(_, label)| label)
+        .collect()
+}
+
+/// 读取位于 `offset` 处字体的粗体/斜体属性并返回对应的变体位。优先取 `OS/2` 表的
+/// fsSelection，没有该表时取 `head` 表的 macStyle，都读不到时视为常规。
+fn parse_face_variant(data: &[u8], offset: usize) -> u8 {
+    let os2 = find_sfnt_table(data, offset, b"OS/2")
+        .and_then(|(pos, _)| read_u16_be(data, pos + 62))
+        .map(|sel| (sel & 0x20 != 0, sel & 0x01 != 0));
+    let flags = os2.or_else(|| {
+        find_sfnt_table(data, offset, b"head")
+            .and_then(|(pos, _)| read_u16_be(data, pos + 44))
+            .map(|style| (style & 0x01 != 0, style & 0x02 != 0))
+    });
+    let (bold, italic) = flags.unwrap_or((false, false));
+    variant_bit(bold, italic)
+}
+
+fn decode_utf16be(data: &[u8]) -> String {
+    let mut buf = Vec::with_capacity(data.len() / 2);
+    let mut i = 0;
+    while i + 1 < data.len() {
+        buf.push(u16::from_be_bytes([data[i], data[i + 1]]));
+        i += 2;
+    }
+    String::from_utf16_lossy(&buf)
+}
+
+fn read_u16_be(data: &[u8], offset: usize) -> Option<u16> {
+    if data.len() < offset + 2 {
+        None
+    } else {
+        Some(u16::from_be_bytes([data[offset], data[offset + 1]]))
+    }
+}
+
+fn read_u32_be(data: &[u8], offset: usize) -> Option<u32> {
+    if data.len() < offset + 4 {
+        None
+    } else {
+        Some(u32::from_be_bytes([
+            data[offset],
+            data[offset + 1],
+            data[offset + 2],
+            data[offset + 3],
+        ]))
+    }
+}
+
+fn is_sub_file(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|v| v.to_str()).map(|v| v.to_lowercase()),
+        Some(ext)
+            if ext == "ass"
+                || ext == "ssa"
+                || ext == "srt"
+                || ext == "vtt"
+                || ext == "sub"
+                || ext == "idx"
+                || ext == "sup"
+    )
+}
+
+fn is_ass_file(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|v| v.to_str()).map(|v| v.to_lowercase()),
+        Some(ext) if ext == "ass" || ext == "ssa"
+    )
+}
+
+/// 拖入的 text/uri-list 中的链接，内容在 `bytes` 中，没有时取 `name`；跳过空行和 `#`
+/// 开头的注释行。
+fn dropped_uris(file: &egui::DroppedFile) -> Vec<String> {
+    let text = match &file.bytes {
+        Some(bytes) => String::from_utf8_lossy(bytes).to_string(),
+        None => file.name.clone(),
+    };
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect()
+}
+
+/// 链接是否为 HTTP/HTTPS 上的字体文件（`.ttf`、`.otf`、`.ttc`）或 ASS/SSA 字幕，
+/// 不看查询串和片段。
+fn is_download_url(url: &str) -> bool {
+    let lower = url.to_lowercase();
+    if !(lower.starts_with("http://") || lower.starts_with("https://")) {
+        return false;
+    }
+    let path = lower.split(['?', '#']).next().unwrap_or(&lower);
+    let path = percent_decode(path);
+    is_font_file(Path::new(&path)) || is_ass_file(Path::new(&path))
+}
+
+/// 解码 URL 中的 `%XX` 转义，无效的转义原样保留。
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = (bytes[i] == b'%')
+            .then(|| text.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match hex {
+            Some(byte) => {
+                out.push(byte);
+                i += 3;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+/// Windows 默认的每进程 GDI 对象上限（注册表 GDIProcessHandleQuota）
+const GDI_HANDLE_LIMIT: u32 = 10_000;
+
+/// GDI 对象数达到该值时警告
+const GDI_WARN_OBJECTS: u32 = 8_000;
+
+const GDI_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 本进程的 GDI 对象数 `(当前, 峰值)`。
+#[cfg(target_os = "windows")]
+fn gdi_object_counts() -> Option<(u32, u32)> {
+    let (current, peak) = unsafe {
+        let process = GetCurrentProcess();
+        (
+            GetGuiResources(process, GR_GDIOBJECTS),
+            GetGuiResources(process, GR_GDIOBJECTS_PEAK),
+        )
+    };
+    // 有窗口的进程总会占用 GDI 对象，0 表示调用失败
+    (current > 0).then_some((current, peak))
+}
+
+#[cfg(not(target_os = "windows"))]
+fn gdi_object_counts() -> Option<(u32, u32)> {
+    None
+}
+
+fn player_dialog() -> rfd::FileDialog {
+    rfd::FileDialog::new().add_filter("程序", &["exe"])
+}
+
+/// 下载到临时目录的文件。
+struct DownloadedFile {
+    path: PathBuf,
+    size: u64,
+}
+
+/// 单个下载文件的大小上限
+const MAX_DOWNLOAD_BYTES: u64 = 64 * 1024 * 1024;
+
+/// 把字幕和字体链接逐个下载到临时目录下本次任务的子目录，文件名取链接的最后一段，
+/// 成功时返回保存的文件及大小。字体必须能解析出字体名，字幕必须能解码并带有 ASS
+/// 的段落标记，否则视为失败。
+fn download_files_worker(
+    urls: Vec<String>,
+    progress: &dyn Fn(ProgressPhase, usize, usize),
+    cancel: &AtomicBool,
+) -> Vec<(String, Result<DownloadedFile, String>)> {
+    let dir = std::env::temp_dir()
+        .join("fontloader-downloads")
+        .join(file_timestamp());
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(60))
+        .build();
+    let mut results = Vec::new();
+    let total = urls.len();
+    for (done, url) in urls.into_iter().enumerate() {
+        progress(ProgressPhase::Download, done, total);
+        if cancel.load(Ordering::Relaxed) {
+            results.push((url, Err("已取消".to_string())));
+            continue;
+        }
+        let result = match &client {
+            Ok(client) => download_file(client, &url, &dir),
+            Err(err) => Err(err.to_string()),
+        };
+        results.push((url, result));
+    }
+    progress(ProgressPhase::Download, total, total);
+    results
+}
+
+fn download_file(
+    client: &reqwest::blocking::Client,
+    url: &str,
+    dir: &Path,
+) -> Result<DownloadedFile, String> {
+    let response = client
+        .get(url)
+        .send()
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| e.to_string())?;
+    if response
+        .content_length()
+        .is_some_and(|len| len > MAX_DOWNLOAD_BYTES)
+    {
+        return Err(format!("文件超过 {}", format_bytes(MAX_DOWNLOAD_BYTES)));
+    }
+    let mut data = Vec::new();
+    response
+        .take(MAX_DOWNLOAD_BYTES + 1)
+        .read_to_end(&mut data)
+        .map_err(|e| e.to_string())?;
+    if data.len() as u64 > MAX_DOWNLOAD_BYTES {
+        return Err(format!("文件超过 {}", format_bytes(MAX_DOWNLOAD_BYTES)));
+    }
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    let name: String = percent_decode(path.rsplit('/').next().unwrap_or_default())
+        .chars()
+        .map(|c| if r#"<>:"/\|?*"#.contains(c) { '_' } else { c })
+        .collect();
+    if is_ass_file(Path::new(&name)) {
+        let is_ass = decode_text(data.clone()).is_some_and(|text| {
+            let lower = text.to_lowercase();
+            lower.contains("[script info]") || lower.contains("[events]")
+        });
+        if !is_ass {
+            return Err("下载的内容不是 ASS/SSA 字幕".to_string());
+        }
+    } else if let Some(reason) = parse_font_names_from_bytes(&data).invalid {
+        return Err(format!("下载的内容不是可用的字体: {}", reason));
+    }
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    let dest = dir.join(name);
+    let size = data.len() as u64;
+    fs::write(&dest, data).map_err(|e| e.to_string())?;
+    Ok(DownloadedFile { path: dest, size })
+}
+
+/// Everything 每次查询最多返回的结果数。
+const EVERYTHING_MAX_RESULTS: usize = 50;
+
+#[derive(Deserialize)]
+struct EverythingResponse {
+    #[serde(default)]
+    results: Vec<EverythingResult>,
+}
+
+#[derive(Deserialize)]
+struct EverythingResult {
+    #[serde(rename = "type", default)]
+    kind: String,
+    name: String,
+    #[serde(default)]
+    path: String,
+}
+
+/// 由缺失字体名推导 Everything 的查询词：字体名按空白、`-`、`_` 拆开后用 `*` 连接，
+/// 以及字体名缓存中同名字体的文件名。
+///
+/// # Example
+///
+/// ```ignore
+/// assert_eq!(everything_terms("FZLanTingHei R", &CacheFile::default()), vec!["*FZLanTingHei*R*"]);
+/// ```
+fn everything_terms(name: &str, cache: &CacheFile) -> Vec<String> {
+    let mut terms = Vec::new();
+    let words: Vec<&str> = name
+        .split(|c: char| c.is_whitespace() || c == '-' || c == '_')
+        .filter(|word| !word.is_empty())
+        .collect();
+    if !words.is_empty() {
+        terms.push(format!("*{}*", words.join("*")));
+    }
+    let key = NameKey::new(name);
+    for (path, entry) in &cache.entries {
+        if !entry
+            .names
+            .iter()
+            .chain(&entry.families)
+            .any(|n| NameKey::new(n) == key)
+        {
+            continue;
+        }
+        if let Some(file) = Path::new(path).file_name().and_then(|f| f.to_str()) {
+            let term = format!("\"{}\"", file);
+            if !terms.contains(&term) {
+                terms.push(term);
+            }
+        }
+    }
+    terms
+}
+
+/// 通过本机 Everything 的 HTTP 服务器按文件名查找 `names` 的候选字体文件，返回
+/// `(字体名, 路径)`，同一文件只列一次。结果只按文件名得来，须由用户确认后再加载。
+fn everything_search_worker(
+    names: &[String],
+    port: u16,
+    cancel: &AtomicBool,
+) -> Result<Vec<(String, String)>, String> {
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(5))
+        .build()
+        .map_err(|e| e.to_string())?;
+    let url = format!("http://127.0.0.1:{}/", port);
+    let (cache, _) = load_cache_file(0);
+    let mut seen = HashSet::new();
+    let mut hits = Vec::new();
+    for name in names {
+        for term in everything_terms(name, &cache) {
+            if cancel.load(Ordering::Relaxed) {
+                return Err("已取消".to_string());
+            }
+            let count = EVERYTHING_MAX_RESULTS.to_string();
+            let response = client
+                .get(&url)
+                .query(&[
+                    ("search", format!("ext:ttf;otf;ttc {}", term).as_str()),
+                    ("json", "1"),
+                    ("path_column", "1"),
+                    ("count", count.as_str()),
+                ])
+                .send()
+                .map_err(|e| {
+                    if e.is_connect() || e.is_timeout() {
+                        format!(
+                            "无法连接 Everything (127.0.0.1:{})，请确认 Everything 正在运行，并已在 工具 > 选项 > HTTP 服务器 中启用",
+                            port
+                        )
+                    } else {
+                        e.to_string()
+                    }
+                })?
+                .error_for_status()
+                .map_err(|e| format!("Everything 查询失败: {}", e))?;
+            let body: EverythingResponse = response
+                .bytes()
+                .map_err(|e| e.to_string())
+                .and_then(|bytes| serde_json::from_slice(&bytes).map_err(|e| e.to_string()))
+                .map_err(|e| format!("Everything 返回的内容无法解析: {}", e))?;
+            for result in body.results {
+                if result.kind == "folder" {
+                    continue;
+                }
+                let path = Path::new(&result.path).join(&result.name);
+                let path_str = path.to_string_lossy().to_string();
+                if is_font_file(&path) && seen.insert(normalize_path_key(&path_str)) {
+                    hits.push((name.clone(), path_str));
+                }
+            }
+        }
+    }
+    Ok(hits)
+}
+
+fn is_font_file(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|v| v.to_str()).map(|v| v.to_lowercase()),
+        Some(ext) if ext == "ttf" || ext == "otf" || ext == "ttc"
+    )
+}
+
+#[cfg(target_os = "windows")]
+fn to_wide(value: &str) -> Vec<u16> {
+    value.encode_utf16().chain(std::iter::once(0)).collect()
+}
+
+/// 字体文件的注册方式。
+#[derive(Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+enum LoadMode {
+    /// `AddFontResourceW`，所有程序都能使用，播放器需要这种方式
+    #[default]
+    Public,
+    /// `AddFontResourceExW` 带 `FR_PRIVATE`，只有本进程能使用，进程退出时自动注销
+    Private,
+}
+
+/// 注册字体文件，成功时返回新增的字体面数，失败时返回 Win32 错误码（取不到时为 0）。
+#[cfg(target_os = "windows")]
+fn add_font_resource(path: &str, mode: LoadMode) -> Result<usize, u32> {
+    let wide = to_wide(path);
+    // 先清除之前调用留下的错误码，否则失败时可能读到与本次无关的旧值
+    let faces = unsafe {
+        SetLastError(ERROR_SUCCESS);
+        match mode {
+            LoadMode::Public => AddFontResourceW(PCWSTR(wide.as_ptr())),
+            LoadMode::Private => AddFontResourceExW(PCWSTR(wide.as_ptr()), FR_PRIVATE, None),
+        }
+    };
+    if faces > 0 {
+        return Ok(faces as usize);
+    }
+    let code = unsafe { GetLastError() }.0;
+    if code != 0 {
+        return Err(code);
+    }
+    // AddFontResourceW 失败时不一定设置错误码，打开文件探测是否被其他程序占用
+    Err(fs::File::open(path)
+        .err()
+        .and_then(|err| err.raw_os_error())
+        .map_or(0, |code| code as u32))
+}
+
+// 非 Windows 平台没有 GDI 字体注册，仅保留解析逻辑以便编译和测试
+#[cfg(not(target_os = "windows"))]
+fn add_font_resource(_path: &str, _mode: LoadMode) -> Result<usize, u32> {
+    Err(0)
+}
+
+/// 文件仍被其他程序写入或锁定（下载完成前、复制中），稍后重试可能成功。
+fn is_transient_load_error(code: u32) -> bool {
+    // ERROR_SHARING_VIOLATION, ERROR_LOCK_VIOLATION
+    matches!(code, 32 | 33)
+}
+
+/// 注册字体，遇到暂时性错误时按 0.5、1、2、4 秒退避，最多重试 `retries` 次。
+/// 返回注册结果和实际重试次数；`cancel` 置位时立即停止等待并返回最后一次的结果。
+fn add_font_resource_with_retry(
+    path: &str,
+    register: &dyn Fn(&str) -> Result<usize, u32>,
+    retries: usize,
+    cancel: &AtomicBool,
+) -> (Result<usize, u32>, usize) {
+    let mut retried = 0;
+    loop {
+        let result = register(path);
+        match result {
+            Err(code) if retried < retries && is_transient_load_error(code) => {
+                let delay = Duration::from_millis(500 << retried.min(3));
+                if !sleep_unless_cancelled(delay, cancel) {
+                    return (result, retried);
+                }
+                retried += 1;
+            }
+            _ => return (result, retried),
+        }
+    }
+}
+
+/// 分段等待 `delay`，期间检查 `cancel`；被取消时返回 `false`。
+fn sleep_unless_cancelled(delay: Duration, cancel: &AtomicBool) -> bool {
+    let deadline = Instant::now() + delay;
+    loop {
+        if cancel.load(Ordering::Relaxed) {
+            return false;
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return true;
+        }
+        thread::sleep((deadline - now).min(Duration::from_millis(50)));
+    }
+}
+
+/// 加载失败条目的日志后缀，附上错误码和重试次数。
+fn load_error_note(code: u32, retried: usize) -> String {
+    match (code, retried) {
+        (0, 0) => String::new(),
+        (0, _) => format!(" (已重试 {} 次)", retried),
+        (_, 0) => format!(" (错误码 {})", code),
+        _ => format!(" (错误码 {}，已重试 {} 次)", code, retried),
+    }
+}
+
+/// 只有 CFF/CFF2 轮廓的字体注册失败时的说明，`alternatives` 中有 TrueType 轮廓的
+/// 同名文件时建议改用。
+fn cff_failure_note(font: &str, path: &str, alternatives: &[String], index: &FontIndex) -> String {
+    let truetype = alternatives.iter().find(|other| {
+        let other = Path::new(other.as_str());
+        index.families.contains_key(other) && !index.cff.contains(other)
+    });
+    let advice = match truetype {
+        Some(other) => format!("可改用同名的 TrueType 版本: {}", other),
+        None => "建议换用该字体的 TrueType (.ttf) 版本".to_string(),
+    };
+    format!(
+        "[cff-note] {} > {} 是 CFF/CFF2 轮廓的 OpenType 字体，部分 Windows 版本的 GDI 无法注册这类字体或注册后没有可用字形，{}",
+        font, path, advice
+    )
+}
+
+/// 注销一次文件的注册，`mode` 须与注册时一致。
+#[cfg(target_os = "windows")]
+fn remove_font_resource(path: &str, mode: LoadMode) -> bool {
+    let wide = to_wide(path);
+    let removed = unsafe {
+        match mode {
+            LoadMode::Public => RemoveFontResourceW(PCWSTR(wide.as_ptr())),
+            LoadMode::Private => RemoveFontResourceExW(PCWSTR(wide.as_ptr()), FR_PRIVATE.0, None),
+        }
+    };
+    removed.0 != 0
+}
+
+#[cfg(not(target_os = "windows"))]
+fn remove_font_resource(_path: &str, _mode: LoadMode) -> bool {
+    false
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum TaskbarStatus {
+    Idle(usize),
+    Busy,
+    Progress(usize, usize),
+}
+
+/// 任务栏按钮上的进度条与已加载数量角标，窗口最小化时也能看到处理状态。
+#[derive(Default)]
+struct TaskbarProgress {
+    last: Option<TaskbarStatus>,
+    #[cfg(target_os = "windows")]
+    list: Option<ITaskbarList3>,
+}
+
+impl TaskbarProgress {
+    fn update(&mut self, frame: &eframe::Frame, status: TaskbarStatus) {
+        if self.last == Some(status) {
+            return;
+        }
+        // 任务栏按钮创建之前调用会失败，失败时下一帧重试
+        if self.apply(frame, status) {
+            self.last = Some(status);
+        }
+    }
+
+    #[cfg(target_os = "windows")]
+    fn apply(&mut self, frame: &eframe::Frame, status: TaskbarStatus) -> bool {
+        use raw_window_handle::{HasWindowHandle, RawWindowHandle};
+        let Ok(handle) = frame.window_handle() else {
+            return false;
+        };
+        let RawWindowHandle::Win32(win32) = handle.as_raw() else {
+            return false;
+        };
+        let hwnd = HWND(win32.hwnd.get());
+        if self.list.is_none() {
+            self.list = unsafe {
+                let _ = CoInitializeEx(None, COINIT_APARTMENTTHREADED);
+                CoCreateInstance::<_, ITaskbarList3>(&TaskbarList, None, CLSCTX_INPROC_SERVER)
+                    .ok()
+                    .filter(|list| list.HrInit().is_ok())
+            };
+        }
+        let Some(list) = &self.list else {
+            return false;
+        };
+        unsafe {
+            match status {
+                TaskbarStatus::Busy => list.SetProgressState(hwnd, TBPF_INDETERMINATE).is_ok(),
+                TaskbarStatus::Progress(done, total) => {
+                    list.SetProgressState(hwnd, TBPF_NORMAL).is_ok()
+                        && list
+                            .SetProgressValue(hwnd, done as u64, total as u64)
+                            .is_ok()
+                }
+                TaskbarStatus::Idle(count) => {
+                    let _ = list.SetProgressState(hwnd, TBPF_NOPROGRESS);
+                    set_count_overlay(list, hwnd, count)
+                }
+            }
+        }
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    fn apply(&mut self, _frame: &eframe::Frame, _status: TaskbarStatus) -> bool {
+        true
+    }
+}
+
+#[cfg(target_os = "windows")]
+unsafe fn set_count_overlay(list: &ITaskbarList3, hwnd: HWND, count: usize) -> bool {
+    if count == 0 {
+        return unsafe { list.SetOverlayIcon(hwnd, HICON::default(), PCWSTR::null()) }.is_ok();
+    }
+    let Some(icon) = create_count_icon(count) else {
+        return false;
+    };
+    let desc = to_wide(&format!("已加载 {} 个字体", count));
+    unsafe {
+        let ok = list
+            .SetOverlayIcon(hwnd, icon, PCWSTR(desc.as_ptr()))
+            .is_ok();
+        // 任务栏会复制图标，设置后即可销毁
+        let _ = DestroyIcon(icon);
+        ok
+    }
+}
+
+/// 生成 16x16 的数字角标图标：圆角红底白字，超过 99 显示 99。
+#[cfg(target_os = "windows")]
+fn create_count_icon(count: usize) -> Option<HICON> {
+    const SIZE: usize = 16;
+    const DIGITS: [[u8; 5]; 10] = [
+        [0b111, 0b101, 0b101, 0b101, 0b111],
+        [0b010, 0b110, 0b010, 0b010, 0b111],
+        [0b111, 0b001, 0b111, 0b100, 0b111],
+        [0b111, 0b001, 0b111, 0b001, 0b111],
+        [0b101, 0b101, 0b111, 0b001, 0b001],
+        [0b111, 0b100, 0b111, 0b001, 0b111],
+        [0b111, 0b100, 0b111, 0b101, 0b111],
+        [0b111, 0b001, 0b001, 0b001, 0b001],
+        [0b111, 0b101, 0b111, 0b101, 0b111],
+        [0b111, 0b101, 0b111, 0b001, 0b111],
+    ];
+    let text = count.min(99).to_string();
+    // BGRA，自上而下逐行
+    let mut pixels = [0u8; SIZE * SIZE * 4];
+    let mut put = |x: usize, y: usize, bgra: [u8; 4]| {
+        let i = (y * SIZE + x) * 4;
+        pixels[i..i + 4].copy_from_slice(&bgra);
+    };
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            let corner = |v: usize| v.min(SIZE - 1 - v);
+            if corner(x) + corner(y) >= 2 {
+                put(x, y, [0x2B, 0x2B, 0xD9, 0xFF]);
+            }
+        }
+    }
+    let scale = 2;
+    let glyph_w = 3 * scale;
+    let width = text.len() * glyph_w + (text.len() - 1);
+    let left = (SIZE - width) / 2;
+    let top = (SIZE - 5 * scale) / 2;
+    for (n, ch) in text.bytes().enumerate() {
+        let glyph = DIGITS[(ch - b'0') as usize];
+        let gx = left + n * (glyph_w + 1);
+        for (row, bits) in glyph.iter().enumerate() {
+            for col in 0..3 {
+                if bits & (0b100 >> col) == 0 {
+                    continue;
+                }
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        put(
+                            gx + col * scale + dx,
+                            top + row * scale + dy,
+                            [0xFF, 0xFF, 0xFF, 0xFF],
+                        );
+                    }
+                }
+            }
+        }
+    }
+    unsafe {
+        let color = CreateBitmap(
+            SIZE as i32,
+            SIZE as i32,
+            1,
+            32,
+            Some(pixels.as_ptr() as *const _),
+        );
+        let mask = CreateBitmap(SIZE as i32, SIZE as i32, 1, 1, None);
+        let info = ICONINFO {
+            fIcon: true.into(),
+            xHotspot: 0,
+            yHotspot: 0,
+            hbmMask: mask,
+            hbmColor: color,
+        };
+        let icon = CreateIconIndirect(&info).ok();
+        let _ = DeleteObject(color);
+        let _ = DeleteObject(mask);
+        icon
+    }
+}
+
+fn open_containing_folder(path: &str) {
+    let _ = std::process::Command::new("explorer")
+        .arg(format!("/select,{}", path))
+        .spawn();
+}
+
+/// 字体家族名当前是否能通过 GDI 枚举到。超过 31 个字符的名称无法按名称枚举，视为
+/// 不存在。
+#[cfg(target_os = "windows")]
+fn font_family_present(name: &str) -> bool {
+    unsafe extern "system" fn found(
+        _font: *const LOGFONTW,
+        _metric: *const TEXTMETRICW,
+        _font_type: u32,
+        lparam: LPARAM,
+    ) -> i32 {
+        unsafe { *(lparam.0 as *mut bool) = true };
+        0
+    }
+    let wide: Vec<u16> = name.encode_utf16().collect();
+    let mut logfont = LOGFONTW {
+        lfCharSet: DEFAULT_CHARSET,
+        ..Default::default()
+    };
+    if wide.len() >= logfont.lfFaceName.len() {
+        return false;
+    }
+    logfont.lfFaceName[..wide.len()].copy_from_slice(&wide);
+    let mut present = false;
+    unsafe {
+        let hdc = GetDC(None);
+        EnumFontFamiliesExW(
+            hdc,
+            &logfont,
+            Some(found),
+            LPARAM(&mut present as *mut bool as isize),
+            0,
+        );
+        ReleaseDC(None, hdc);
+    }
+    present
+}
+
+#[cfg(not(target_os = "windows"))]
+fn font_family_present(_name: &str) -> bool {
+    false
+}
+
+/// 通过 GDI 枚举当前可用的全部字体，返回去重后的家族名与全名，包括竖排用的 `@` 名。
+#[cfg(target_os = "windows")]
+fn enumerate_font_families() -> Vec<String> {
+    unsafe extern "system" fn collect(
+        font: *const LOGFONTW,
+        _metric: *const TEXTMETRICW,
+        _font_type: u32,
+        lparam: LPARAM,
+    ) -> i32 {
+        // 传入的实际是 ENUMLOGFONTEXW，LOGFONTW 是其第一个字段
+        let (names, font) = unsafe {
+            (
+                &mut *(lparam.0 as *mut Vec<String>),
+                &*(font as *const ENUMLOGFONTEXW),
+            )
+        };
+        for raw in [&font.elfLogFont.lfFaceName[..], &font.elfFullName[..]] {
+            let len = raw.iter().position(|&c| c == 0).unwrap_or(raw.len());
+            if len > 0 {
+                names.push(String::from_utf16_lossy(&raw[..len]));
+            }
+        }
+        1
+    }
+    let logfont = LOGFONTW {
+        lfCharSet: DEFAULT_CHARSET,
+        ..Default::default()
+    };
+    let mut names: Vec<String> = Vec::new();
+    unsafe {
+        let hdc = GetDC(None);
+        EnumFontFamiliesExW(
+            hdc,
+            &logfont,
+            Some(collect),
+            LPARAM(&mut names as *mut Vec<String> as isize),
+            0,
+        );
+        ReleaseDC(None, hdc);
+    }
+    names.sort();
+    names.dedup();
+    names
+}
+
+#[cfg(not(target_os = "windows"))]
+fn enumerate_font_families() -> Vec<String> {
+    Vec::new()
+}
+
+/// 注册表 `Fonts` 键中登记的字体，取自本机和当前用户两处，返回 `(值名, 文件)`。
+///
+/// 值名形如 `Arial (TrueType)` 或 `MS Gothic & MS UI Gothic (TrueType)`；文件通常是
+/// 相对 Fonts 目录的文件名，按用户安装的字体则是完整路径。
+#[cfg(target_os = "windows")]
+fn registry_fonts() -> Vec<(String, String)> {
+    let mut fonts = Vec::new();
+    let subkey = to_wide(r"SOFTWARE\Microsoft\Windows NT\CurrentVersion\Fonts");
+    for root in [HKEY_LOCAL_MACHINE, HKEY_CURRENT_USER] {
+        let mut key = HKEY::default();
+        if unsafe { RegOpenKeyExW(root, PCWSTR(subkey.as_ptr()), 0, KEY_READ, &mut key) }
+            != ERROR_SUCCESS
+        {
+            continue;
+        }
+        let mut index = 0;
+        loop {
+            let mut name = [0u16; 512];
+            let mut name_len = name.len() as u32;
+            let mut data = [0u8; 1024];
+            let mut data_len = data.len() as u32;
+            let status = unsafe {
+                RegEnumValueW(
+                    key,
+                    index,
+                    windows::core::PWSTR(name.as_mut_ptr()),
+                    &mut name_len,
+                    None,
+                    None,
+                    Some(data.as_mut_ptr()),
+                    Some(&mut data_len),
+                )
+            };
+            if status != ERROR_SUCCESS {
+                break;
+            }
+            index += 1;
+            let value = String::from_utf16_lossy(&name[..name_len as usize]);
+            let file: Vec<u16> = data[..data_len as usize]
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .take_while(|&c| c != 0)
+                .collect();
+            fonts.push((value, String::from_utf16_lossy(&file)));
+        }
+        unsafe {
+            let _ = RegCloseKey(key);
+        }
+    }
+    fonts
+}
+
+#[cfg(not(target_os = "windows"))]
+fn registry_fonts() -> Vec<(String, String)> {
+    Vec::new()
+}
+
+/// 系统 Fonts 目录，通过 `SHGetKnownFolderPath(FOLDERID_Fonts)` 获取，失败时取
+/// `%WINDIR%\Fonts`。
+fn system_fonts_dir() -> PathBuf {
+    #[cfg(target_os = "windows")]
+    if let Some(dir) = known_folder(&FOLDERID_Fonts) {
+        return dir;
+    }
+    let windir = std::env::var_os("WINDIR")
+        .or_else(|| std::env::var_os("SystemRoot"))
+        .unwrap_or_else(|| "C:\\Windows".into());
+    PathBuf::from(windir).join("Fonts")
+}
+
+/// 当前用户安装的字体所在目录 `%LOCALAPPDATA%\Microsoft\Windows\Fonts`。
+#[cfg(target_os = "windows")]
+fn user_fonts_dir() -> Option<PathBuf> {
+    known_folder(&FOLDERID_LocalAppData).map(|dir| dir.join("Microsoft\\Windows\\Fonts"))
+}
+
+#[cfg(not(target_os = "windows"))]
+fn user_fonts_dir() -> Option<PathBuf> {
+    None
+}
+
+#[cfg(target_os = "windows")]
+fn known_folder(id: &windows::core::GUID) -> Option<PathBuf> {
+    unsafe {
+        let path = SHGetKnownFolderPath(id, KF_FLAG_DEFAULT, None).ok()?;
+        let text = path.to_string();
+        CoTaskMemFree(Some(path.0 as *const _));
+        text.ok().map(PathBuf::from)
+    }
+}
+
+/// 已安装字体的 "小写名称 -> 文件" 表，合并两个来源：注册表登记的字体名（去掉
+/// ` (TrueType)` 等后缀、按 ` & ` 拆分），以及 Fonts 目录中字体文件的文件名（不含
+/// 扩展名）。注册表中的相对文件名按 Fonts 目录解析。
+fn scan_system_fonts() -> HashMap<NameKey, PathBuf> {
+    let dir = system_fonts_dir();
+    let mut fonts = HashMap::new();
+    let mut files = Vec::new();
+    let _ = walk_dir(&dir, &mut files, Some(0));
+    for path in files {
+        if is_font_file(&path)
+            && let Some(stem) = path.file_stem()
+        {
+            fonts.insert(NameKey::new(&stem.to_string_lossy()), path);
+        }
+    }
+    for (value, file) in registry_fonts() {
+        let names = match value.rfind(" (") {
+            Some(pos) => &value[..pos],
+            None => value.as_str(),
+        };
+        let path = dir.join(&file);
+        for name in names.split(" & ") {
+            if let Some(normalized) = normalize_font_name(name) {
+                fonts.insert(NameKey::new(&normalized), path.clone());
+            }
+        }
+    }
+    fonts
+}
+
+/// 注册表登记的字体文件，相对文件名按 Fonts 目录解析，去掉重复和非字体文件。
+fn registry_font_files() -> Vec<PathBuf> {
+    let dir = system_fonts_dir();
+    let mut seen = HashSet::new();
+    registry_fonts()
+        .into_iter()
+        .map(|(_, file)| dir.join(file))
+        .filter(|path| is_font_file(path) && seen.insert(path.to_string_lossy().to_lowercase()))
+        .collect()
+}
+
+/// 用 [`build_font_index`] 索引注册表登记的系统字体，结果标记为
+/// [`FontSource::System`]，与用户提供的字体分开保存。
+fn index_system_fonts_worker(
+    use_cache: bool,
+    cache_ttl_days: u64,
+    rules: &NameRules,
+    progress: &dyn Fn(usize, usize),
+    cancel: &AtomicBool,
+) -> Result<FontIndex, String> {
+    let files = registry_font_files();
+    if files.is_empty() {
+        return Err("注册表中没有登记字体".to_string());
+    }
+    let mut cache = if use_cache {
+        load_cache_file(cache_ttl_days).0
     } else {
         CacheFile::default()
     };
-    let font_index = build_font_index(&font_files, use_cache, &mut cache);
+    let index = build_font_index(&files, use_cache, &mut cache, rules, progress, cancel);
     if use_cache {
         let _ = save_cache_file(&cache);
     }
-
-    let mut logs = Vec::new();
-    for sub in unsupported_subs {
-        logs.push(format!("[i] 跳过不支持解析的字幕: {}", sub));
+    if cancel.load(Ordering::Relaxed) {
+        return Err("已取消".to_string());
     }
-    let mut loaded = 0;
-    let mut failed = 0;
-    let mut missing = 0;
-    let mut duplicates = 0;
+    Ok(FontIndex {
+        source: FontSource::System,
+        ..index
+    })
+}
 
-    let mut state = state.lock().map_err(|_| "状态锁失败".to_string())?;
-    for font in required_fonts.iter() {
-        let key = font.to_lowercase();
-        if let Some(files) = font_index.get(&key) {
-            if let Some(path) = files.first() {
-                let path_str = path.to_string_lossy().to_string();
-                if state.loaded.contains(&path_str) {
-                    duplicates += 1;
-                    logs.push(format!("[^] {} > {}", font, path_str));
-                } else if add_font_resource(&path_str) {
-                    state.loaded.insert(path_str.clone());
-                    loaded += 1;
-                    logs.push(format!("[ok] {} > {}", font, path_str));
-                } else {
-                    failed += 1;
-                    logs.push(format!("[X] {} > {}", font, path_str));
-                }
-            } else {
-                missing += 1;
-                logs.push(format!("[??] {}", font));
-            }
-        } else {
-            missing += 1;
-            logs.push(format!("[??] {}", font));
-        }
+/// [`scan_system_fonts`] 的结果，进程内只扫描一次。
+fn system_fonts() -> &'static HashMap<NameKey, PathBuf> {
+    static SYSTEM_FONTS: OnceLock<HashMap<NameKey, PathBuf>> = OnceLock::new();
+    SYSTEM_FONTS.get_or_init(scan_system_fonts)
+}
+
+#[cfg(target_os = "windows")]
+fn broadcast_font_change() {
+    unsafe {
+        SendMessageW(HWND_BROADCAST, WM_FONTCHANGE, WPARAM(0), LPARAM(0));
     }
+}
 
-    if loaded > 0 {
-        broadcast_font_change();
+#[cfg(not(target_os = "windows"))]
+fn broadcast_font_change() {}
+
+/// 缓存文件位置：程序所在目录下的 `cache.json`，无法确定程序路径时返回 `None`。
+fn cache_file_path() -> Option<PathBuf> {
+    let exe_path = std::env::current_exe().ok()?;
+    let exe_dir = exe_path.parent()?;
+    Some(exe_dir.join("cache.json"))
+}
+
+/// 读取字体名缓存，同时返回因过期丢弃的条目数。
+///
+/// 文件不存在、无法读取或内容无法解析时返回空缓存，不会报错，缓存损坏只会导致
+/// 下一次全部重新解析。`ttl_days` 大于 0 时丢弃写入超过该天数的条目，见
+/// [`Config::cache_ttl_days`]；只读取缓存、不写回的调用方传 0。
+fn load_cache_file(ttl_days: u64) -> (CacheFile, usize) {
+    let Some(path) = cache_file_path() else {
+        return (CacheFile::default(), 0);
+    };
+    let data = fs::read(path).ok();
+    let mut cache: CacheFile = data
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .unwrap_or_default();
+    if cache.name_rules != 0 {
+        return (CacheFile::default(), 0);
     }
+    let before = cache.entries.len();
+    if ttl_days > 0 {
+        let now = unix_now();
+        let max_age = ttl_days.saturating_mul(86_400);
+        cache
+            .entries
+            .retain(|_, entry| now.saturating_sub(entry.cached_at) <= max_age);
+    }
+    let expired = before - cache.entries.len();
+    (cache, expired)
+}
 
-    Ok(ProcessResult {
-        loaded,
-        failed,
-        missing,
-        duplicates,
-        subs: sub_files.len(),
-        fonts: font_files.len(),
-        logs,
+/// 删除缓存中文件已不存在、修改时间与记录不一致或已过期的条目，并重写缓存文件。
+///
+/// 缓存文件不存在时视为空缓存，不会新建文件。
+fn compact_cache_file(ttl_days: u64) -> Result<CacheCompaction, String> {
+    let path = cache_file_path().ok_or("无法确定缓存文件位置")?;
+    let Ok(before) = fs::metadata(&path).map(|m| m.len()) else {
+        return Ok(CacheCompaction {
+            removed: 0,
+            kept: 0,
+            before: 0,
+            after: 0,
+        });
+    };
+    let (mut cache, expired) = load_cache_file(ttl_days);
+    let total = cache.entries.len() + expired;
+    cache
+        .entries
+        .retain(|file, entry| metadata_mtime(Path::new(file)) == Some(entry.modified));
+    let kept = cache.entries.len();
+    save_cache_file(&cache)?;
+    let after = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+    Ok(CacheCompaction {
+        removed: total - kept,
+        kept,
+        before,
+        after,
     })
 }
 
-fn unload_fonts_worker(state: Arc<Mutex<AppState>>) -> Result<UnloadResult, String> {
-    let mut state = state.lock().map_err(|_| "状态锁失败".to_string())?;
-    let mut count = 0;
-    let mut removed = Vec::new();
-    for path in state.loaded.iter() {
-        if remove_font_resource(path) {
-            count += 1;
-            removed.push(path.clone());
+/// 以 B/KiB/MiB 显示字节数。
+fn format_bytes(bytes: u64) -> String {
+    const KIB: f64 = 1024.0;
+    let value = bytes as f64;
+    if value < KIB {
+        format!("{} B", bytes)
+    } else if value < KIB * KIB {
+        format!("{:.1} KiB", value / KIB)
+    } else {
+        format!("{:.1} MiB", value / (KIB * KIB))
+    }
+}
+
+/// 把字体名缓存整体写回 [`cache_file_path`]，必要时创建父目录。
+///
+/// 无法确定缓存路径时什么也不做并返回 `Ok(())`；创建目录、序列化或写入失败时返回
+/// 错误描述。
+fn save_cache_file(cache: &CacheFile) -> Result<(), String> {
+    let Some(path) = cache_file_path() else {
+        return Ok(());
+    };
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let data = serde_json::to_vec_pretty(cache).map_err(|e| e.to_string())?;
+    fs::write(path, data).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn config_file_path() -> Option<PathBuf> {
+    let exe_path = std::env::current_exe().ok()?;
+    let exe_dir = exe_path.parent()?;
+    Some(exe_dir.join("config.json"))
+}
+
+fn load_config() -> Config {
+    let Some(path) = config_file_path() else {
+        return Config::default();
+    };
+    let data = fs::read(path).ok();
+    if let Some(bytes) = data {
+        serde_json::from_slice(&bytes).unwrap_or_default()
+    } else {
+        Config::default()
+    }
+}
+
+/// 读取并校验配置文件，供运行中重载；与 [`load_config`] 不同，文件不存在或无法解析
+/// 时返回错误而不是默认设置。
+fn read_config_file() -> Result<Config, String> {
+    let path = config_file_path().ok_or("无法确定配置文件位置")?;
+    let data = fs::read(&path).map_err(|e| format!("{}: {}", path.to_string_lossy(), e))?;
+    let config: Config = serde_json::from_slice(&data).map_err(|e| format!("设置项无效: {}", e))?;
+    validate_config(&config)?;
+    Ok(config)
+}
+
+/// 检查反序列化后仍可能无效的设置项。
+/// 设置中在本机不存在的路径：播放器、界面字体、排除的字体文件、别名表，以及结构化
+/// 日志所在的目录（日志文件本身会在写入时创建）。
+fn config_missing_paths(config: &Config) -> Vec<String> {
+    let log_dir = config
+        .structured_log_path
+        .as_ref()
+        .and_then(|path| path.parent())
+        .filter(|dir| !dir.as_os_str().is_empty());
+    [&config.player_path, &config.ui_font_path]
+        .into_iter()
+        .chain(&config.excluded_fonts)
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+        .chain(config.alias_map_path.clone())
+        .chain(log_dir.map(Path::to_path_buf))
+        .filter(|path| !path.exists())
+        .map(|path| path.to_string_lossy().to_string())
+        .collect()
+}
+
+fn validate_config(config: &Config) -> Result<(), String> {
+    if config.http_port < 1024 {
+        return Err(format!("HTTP 端口 {} 无效", config.http_port));
+    }
+    if config.cache_ttl_days > MAX_CACHE_TTL_DAYS {
+        return Err(format!(
+            "缓存有效期 {} 天超出范围 (0-{})",
+            config.cache_ttl_days, MAX_CACHE_TTL_DAYS
+        ));
+    }
+    Ok(())
+}
+
+fn save_config(config: &Config) -> Result<(), String> {
+    let Some(path) = config_file_path() else {
+        return Ok(());
+    };
+    let data = serde_json::to_vec_pretty(config).map_err(|e| e.to_string())?;
+    fs::write(path, data).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// 读取 [`ConfigBundle`]。版本比本程序新时拒绝导入；缺少的设置项取默认值，
+/// 不认识的设置项忽略。
+fn load_config_bundle(path: &Path) -> Result<Config, String> {
+    let data = fs::read(path).map_err(|e| e.to_string())?;
+    let value: serde_json::Value = serde_json::from_slice(&data).map_err(|e| e.to_string())?;
+    let Some(version) = value.get("version").and_then(|v| v.as_u64()) else {
+        return Err("不是本程序导出的配置文件".to_string());
+    };
+    if version > CONFIG_BUNDLE_VERSION as u64 {
+        return Err(format!(
+            "配置文件版本 {} 高于本程序支持的 {}，请先更新程序",
+            version, CONFIG_BUNDLE_VERSION
+        ));
+    }
+    let Some(config) = value.get("config").filter(|v| v.is_object()) else {
+        return Err("配置文件缺少 config".to_string());
+    };
+    let config: Config =
+        serde_json::from_value(config.clone()).map_err(|e| format!("设置项无效: {}", e))?;
+    validate_config(&config)?;
+    Ok(config)
+}
+
+/// 逐项比较两份设置，返回不同的项 `(设置项, 旧值, 新值)`，值为 JSON 文本。
+fn config_changes(old: &Config, new: &Config) -> Vec<(String, String, String)> {
+    let (Ok(serde_json::Value::Object(old)), Ok(serde_json::Value::Object(new))) =
+        (serde_json::to_value(old), serde_json::to_value(new))
+    else {
+        return Vec::new();
+    };
+    new.iter()
+        .filter(|(key, value)| old.get(*key) != Some(value))
+        .map(|(key, value)| {
+            let before = old.get(key).map(|v| v.to_string()).unwrap_or_default();
+            (key.clone(), before, value.to_string())
+        })
+        .collect()
+}
+
+fn session_file_path() -> Option<PathBuf> {
+    let exe_path = std::env::current_exe().ok()?;
+    let exe_dir = exe_path.parent()?;
+    Some(exe_dir.join("session.json"))
+}
+
+fn load_session() -> SessionState {
+    let Some(path) = session_file_path() else {
+        return SessionState::default();
+    };
+    let data = fs::read(path).ok();
+    if let Some(bytes) = data {
+        serde_json::from_slice(&bytes).unwrap_or_default()
+    } else {
+        SessionState::default()
+    }
+}
+
+fn save_session(session: &SessionState) -> Result<(), String> {
+    let Some(path) = session_file_path() else {
+        return Ok(());
+    };
+    let data = serde_json::to_vec_pretty(session).map_err(|e| e.to_string())?;
+    fs::write(path, data).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn load_manifest(path: &Path) -> Result<RestoreManifest, String> {
+    let data = fs::read(path).map_err(|e| e.to_string())?;
+    serde_json::from_slice(&data).map_err(|e| e.to_string())
+}
+
+/// 写出加载清单。目标为 `.bat` 时在同名位置写 JSON 清单，并生成调用
+/// `--restore` 的脚本；返回实际写入的文件。
+fn export_manifest(path: &Path, manifest: &RestoreManifest) -> Result<Vec<PathBuf>, String> {
+    let is_bat = path
+        .extension()
+        .and_then(|v| v.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("bat"));
+    let json_path = if is_bat {
+        path.with_extension("json")
+    } else {
+        path.to_path_buf()
+    };
+    let data = serde_json::to_vec_pretty(manifest).map_err(|e| e.to_string())?;
+    fs::write(&json_path, data).map_err(|e| e.to_string())?;
+    if !is_bat {
+        return Ok(vec![json_path]);
+    }
+    let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+    // 路径可能含中文，脚本以 UTF-8 保存并切换代码页
+    let script = format!(
+        "@echo off\r\nchcp 65001 >nul\r\n\"{}\" --restore \"{}\"\r\n",
+        exe.to_string_lossy(),
+        json_path.to_string_lossy()
+    );
+    fs::write(path, script).map_err(|e| e.to_string())?;
+    Ok(vec![json_path, path.to_path_buf()])
+}
+
+/// 缺失字体报告中每个字体附带的搜索链接。
+fn font_search_links(name: &str) -> [(&'static str, String); 2] {
+    let query = url_encode(name);
+    [
+        (
+            "Google",
+            format!("https://www.google.com/search?q={}%20font", query),
+        ),
+        (
+            "百度",
+            format!("https://www.baidu.com/s?wd={}%20%E5%AD%97%E4%BD%93", query),
+        ),
+    ]
+}
+
+/// 写出缺失字体报告，扩展名为 `.md` 时输出 Markdown，其余输出 HTML。
+/// 把 `fonts` 中的字体文件写入 ZIP，并附上列出字幕和字体的 `README.txt`。
+///
+/// 不同目录下的同名文件在包内依次改名为 `name (2).ttf` 等。
+fn write_font_pack(
+    dest: &Path,
+    fonts: &[(String, Vec<String>)],
+    subs: &[String],
+) -> Result<(), String> {
+    let file = fs::File::create(dest).map_err(|e| e.to_string())?;
+    let mut zip = zip::ZipWriter::new(file);
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated);
+    let mut readme = String::from("字幕文件:\r\n");
+    for sub in subs {
+        readme.push_str(&format!("  {}\r\n", sub));
+    }
+    readme.push_str("\r\n字体文件:\r\n");
+    let mut used = HashSet::new();
+    for (path, names) in fonts {
+        let path = Path::new(path);
+        let stem = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let ext = path
+            .extension()
+            .map(|s| format!(".{}", s.to_string_lossy()))
+            .unwrap_or_default();
+        let mut entry_name = format!("{}{}", stem, ext);
+        let mut n = 2;
+        while !used.insert(entry_name.to_lowercase()) {
+            entry_name = format!("{} ({}){}", stem, n, ext);
+            n += 1;
+        }
+        let mut src = fs::File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        zip.start_file(entry_name.as_str(), options)
+            .map_err(|e| e.to_string())?;
+        std::io::copy(&mut src, &mut zip).map_err(|e| format!("{}: {}", path.display(), e))?;
+        readme.push_str(&format!("  {}  ({})\r\n", entry_name, names.join(", ")));
+    }
+    zip.start_file("README.txt", options)
+        .map_err(|e| e.to_string())?;
+    std::io::Write::write_all(&mut zip, readme.as_bytes()).map_err(|e| e.to_string())?;
+    zip.finish().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// 当前时间的 Unix 秒数。
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// 当前 UTC 时间，形如 `20261015_083000`，用于默认文件名。
+fn file_timestamp() -> String {
+    let secs = unix_now();
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+    // 公历日期换算，见 Howard Hinnant 的 civil_from_days
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}{:02}{:02}_{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+fn export_missing_report(path: &Path, missing: &[&FontEntry]) -> Result<(), String> {
+    let markdown = path
+        .extension()
+        .and_then(|v| v.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("md"));
+    let mut out = String::new();
+    if markdown {
+        out.push_str(&format!("# 缺失字体 ({})\n\n", missing.len()));
+        for entry in missing {
+            let links: Vec<String> = font_search_links(&entry.name)
+                .iter()
+                .map(|(site, url)| format!("[{}]({})", site, url))
+                .collect();
+            out.push_str(&format!(
+                "## {}\n\n搜索: {}\n\n",
+                entry.name,
+                links.join(" · ")
+            ));
+            for source in &entry.sources {
+                out.push_str(&format!("- `{}`\n", source));
+            }
+            out.push('\n');
+        }
+    } else {
+        out.push_str("<!DOCTYPE html>\n<html lang=\"zh-CN\">\n<head>\n<meta charset=\"utf-8\">\n");
+        out.push_str(&format!(
+            "<title>缺失字体 ({})</title>\n</head>\n<body>\n<h1>缺失字体 ({})</h1>\n",
+            missing.len(),
+            missing.len()
+        ));
+        for entry in missing {
+            let links: Vec<String> = font_search_links(&entry.name)
+                .iter()
+                .map(|(site, url)| format!("<a href=\"{}\">{}</a>", url, site))
+                .collect();
+            out.push_str(&format!(
+                "<h2>{}</h2>\n<p>搜索: {}</p>\n<ul>\n",
+                html_escape(&entry.name),
+                links.join(" · ")
+            ));
+            for source in &entry.sources {
+                out.push_str(&format!("<li><code>{}</code></li>\n", html_escape(source)));
+            }
+            out.push_str("</ul>\n");
         }
+        out.push_str("</body>\n</html>\n");
+    }
+    fs::write(path, out).map_err(|e| e.to_string())
+}
+
+/// HTML 报告中各状态的样式类名。
+fn entry_status_class(status: EntryStatus) -> &'static str {
+    match status {
+        EntryStatus::Loaded => "loaded",
+        EntryStatus::Duplicate => "duplicate",
+        EntryStatus::Failed => "failed",
+        EntryStatus::Missing => "missing",
+        EntryStatus::System => "system",
+        EntryStatus::Conflict => "conflict",
+        EntryStatus::MissingVariant => "variant",
+    }
+}
+
+const HTML_REPORT_STYLE: &str = "body{font-family:system-ui,\"Microsoft YaHei\",\"PingFang SC\",sans-serif;margin:2em;color:#222}
+table{border-collapse:collapse;margin-bottom:2em}
+th,td{border:1px solid #ccc;padding:4px 8px;text-align:left;vertical-align:top}
+th{background:#f0f0f0}
+code{font-size:90%;word-break:break-all}
+.counters{display:flex;flex-wrap:wrap;gap:8px;list-style:none;padding:0}
+.counters li{border:1px solid #ccc;border-radius:4px;padding:4px 10px}
+.loaded{color:#1a6b2c}
+.duplicate{color:#1f5fbf}
+.failed,.conflict,.variant{color:#b3261e}
+.missing{color:#a15c00}
+.system{color:#777}
+tr.missing{background:#fff4e0}
+.missing-list{background:#fff4e0;border:1px solid #e0b060;padding:0.5em 2em}";
+
+/// 把一次处理的结果渲染为自带样式的 HTML：摘要计数、缺失字体列表、每个字体的结果
+/// 和各字幕的覆盖率。所有文本都经过转义。
+fn render_html_report(result: &ProcessResult) -> String {
+    let mut out = String::new();
+    out.push_str("<!DOCTYPE html>\n<html lang=\"zh-CN\">\n<head>\n<meta charset=\"utf-8\">\n");
+    out.push_str(&format!(
+        "<title>字体加载报告</title>\n<style>\n{}\n</style>\n</head>\n<body>\n<h1>字体加载报告</h1>\n",
+        HTML_REPORT_STYLE
+    ));
+    let counters = [
+        ("字幕", result.subs),
+        ("字体文件", result.fonts),
+        ("已载入", result.loaded),
+        ("失败", result.failed),
+        ("缺失", result.missing),
+        ("重复", result.duplicates),
+        ("系统已安装", result.installed),
+        ("覆盖系统字体", result.system_conflicts),
+        ("缺少变体", result.missing_variants),
+        ("空字幕", result.empty_subs),
+        ("无效字体", result.invalid_fonts),
+    ];
+    out.push_str("<ul class=\"counters\">\n");
+    for (label, count) in counters {
+        out.push_str(&format!("<li>{} <strong>{}</strong></li>\n", label, count));
     }
-    for path in removed {
-        state.loaded.remove(&path);
+    out.push_str("</ul>\n");
+    if result.subs_only {
+        out.push_str(&format!("<p class=\"missing\">{}</p>\n", SUBS_ONLY_HINT));
     }
-    if count > 0 {
-        broadcast_font_change();
+
+    let missing: Vec<&FontEntry> = result
+        .entries
+        .iter()
+        .filter(|e| e.status == EntryStatus::Missing)
+        .collect();
+    if !missing.is_empty() {
+        out.push_str(&format!(
+            "<h2>缺失字体 ({})</h2>\n<ul class=\"missing-list\">\n",
+            missing.len()
+        ));
+        for entry in &missing {
+            let sources: Vec<String> = entry
+                .sources
+                .iter()
+                .map(|s| format!("<code>{}</code>", html_escape(s)))
+                .collect();
+            out.push_str(&format!(
+                "<li><strong>{}</strong> {}</li>\n",
+                html_escape(&entry.name),
+                sources.join(" ")
+            ));
+        }
+        out.push_str("</ul>\n");
     }
-    Ok(UnloadResult { count })
-}
 
-fn clean_folder_worker(folder: PathBuf) -> Result<UnloadResult, String> {
-    let mut files = Vec::new();
-    let _ = walk_dir(&folder, &mut files);
-    let mut count = 0;
-    for path in files {
-        if is_font_file(&path) {
-            let path_str = path.to_string_lossy().to_string();
-            while remove_font_resource(&path_str) {
-                count += 1;
-            }
+    out.push_str(&format!(
+        "<h2>字体 ({})</h2>\n<table>\n<tr><th>状态</th><th>字体名</th><th>文件</th><th>备注</th></tr>\n",
+        result.entries.len()
+    ));
+    for entry in &result.entries {
+        let class = entry_status_class(entry.status);
+        let mut notes = Vec::new();
+        if !entry.conflicts.is_empty() {
+            notes.push(format!("将覆盖系统字体: {}", entry.conflicts.join("、")));
+        }
+        if !entry.missing_variants.is_empty() {
+            notes.push(format!("缺少{}", entry.missing_variants.join("、")));
+        }
+        if !entry.alternatives.is_empty() {
+            notes.push(format!("另有 {} 个匹配文件", entry.alternatives.len()));
+        }
+        if let Some(dir) = &entry.local_dir {
+            notes.push(format!("未使用字幕旁的字体目录 {}", dir));
         }
+        let notes: Vec<String> = notes.iter().map(|n| html_escape(n)).collect();
+        out.push_str(&format!(
+            "<tr class=\"{}\"><td class=\"{}\">{}</td><td>{}</td><td><code>{}</code></td><td>{}</td></tr>\n",
+            class,
+            class,
+            html_escape(entry_status_label(entry.status)),
+            html_escape(&display_font_names(
+                std::slice::from_ref(&entry.name),
+                &entry.families
+            )),
+            html_escape(entry.path.as_deref().unwrap_or("-")),
+            notes.join("<br>")
+        ));
     }
-    if count > 0 {
-        broadcast_font_change();
+    out.push_str("</table>\n");
+
+    if !result.subtitles.is_empty() {
+        out.push_str(&format!(
+            "<h2>字幕 ({})</h2>\n<table>\n<tr><th>字幕</th><th>所需字体</th><th>可用</th><th>覆盖率</th></tr>\n",
+            result.subtitles.len()
+        ));
+        for sub in &result.subtitles {
+            let class = if sub.available < sub.required {
+                " class=\"missing\""
+            } else {
+                ""
+            };
+            out.push_str(&format!(
+                "<tr{}><td><code>{}</code></td><td>{}</td><td>{}</td><td>{:.0}%</td></tr>\n",
+                class,
+                html_escape(&sub.path),
+                sub.required,
+                sub.available,
+                sub.coverage_pct
+            ));
+        }
+        out.push_str("</table>\n");
     }
-    Ok(UnloadResult { count })
+    out.push_str("</body>\n</html>\n");
+    out
 }
 
-fn build_font_index(
-    font_files: &[PathBuf],
-    use_cache: bool,
-    cache: &mut CacheFile,
-) -> HashMap<String, Vec<PathBuf>> {
-    let mut index: HashMap<String, Vec<PathBuf>> = HashMap::new();
-    for path in font_files {
-        let path_str = path.to_string_lossy().to_string();
-        let names = if use_cache {
-            if let Some(entry) = cache.entries.get(&path_str) {
-                if metadata_mtime(path) == Some(entry.modified) {
-                    entry.names.clone()
-                } else {
-                    let names = parse_font_names(path);
-                    cache.entries.insert(
-                        path_str.clone(),
-                        CacheEntry {
-                            modified: metadata_mtime(path).unwrap_or(0),
-                            names: names.clone(),
-                        },
-                    );
-                    names
-                }
-            } else {
-                let names = parse_font_names(path);
-                cache.entries.insert(
-                    path_str.clone(),
-                    CacheEntry {
-                        modified: metadata_mtime(path).unwrap_or(0),
-                        names: names.clone(),
-                    },
-                );
-                names
-            }
+fn export_html_report(path: &Path, result: &ProcessResult) -> Result<(), String> {
+    fs::write(path, render_html_report(result)).map_err(|e| e.to_string())
+}
+
+fn url_encode(text: &str) -> String {
+    let mut out = String::new();
+    for b in text.bytes() {
+        if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.' | b'~') {
+            out.push(b as char);
         } else {
-            parse_font_names(path)
-        };
-        for name in names {
-            let key = name.to_lowercase();
-            index.entry(key).or_default().push(path.clone());
+            out.push_str(&format!("%{:02X}", b));
         }
     }
-    index
+    out
 }
 
-fn metadata_mtime(path: &Path) -> Option<u64> {
-    let metadata = fs::metadata(path).ok()?;
-    let modified = metadata.modified().ok()?;
-    let duration = modified.duration_since(UNIX_EPOCH).ok()?;
-    Some(duration.as_secs())
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
 }
 
-fn read_text(path: &Path) -> Option<String> {
-    let data = fs::read(path).ok()?;
-    if data.starts_with(&[0xFF, 0xFE]) {
-        return decode_utf16(&data[2..], true);
-    }
-    if data.starts_with(&[0xFE, 0xFF]) {
-        return decode_utf16(&data[2..], false);
+/// 无界面恢复：按清单加载字体后直接退出，返回进程退出码。
+fn restore_headless(manifest_path: &Path) -> i32 {
+    let Ok(manifest) = load_manifest(manifest_path) else {
+        return 1;
+    };
+    let state = Arc::new(Mutex::new(AppState::default()));
+    let result = restore_fonts_worker(manifest.fonts, LoadMode::Public, state.clone());
+    // 命令行恢复的字体在进程退出后仍保持注册，计入会话状态
+    if let Ok(state) = state.lock() {
+        let mut session = load_session();
+        session.registered_faces += state.registered_faces();
+        let _ = save_session(&session);
     }
-    if data.starts_with(&[0xEF, 0xBB, 0xBF]) {
-        return String::from_utf8(data[3..].to_vec()).ok();
+    match result {
+        Ok(res) if res.failed == 0 && res.missing == 0 => 0,
+        _ => 2,
     }
-    String::from_utf8(data).ok()
 }
 
-fn decode_utf16(data: &[u8], little_endian: bool) -> Option<String> {
-    if data.len() % 2 != 0 {
-        return None;
+/// 无界面处理：加载 `paths` 所需的字体并把结果写成 HTML 报告后退出，返回进程退出码。
+/// 字体在进程退出后仍保持注册，与 [`restore_headless`] 相同。
+fn report_headless(report: &Path, paths: &[String]) -> i32 {
+    if paths.is_empty() {
+        return 1;
     }
-    let mut buf = Vec::with_capacity(data.len() / 2);
-    let mut i = 0;
-    while i + 1 < data.len() {
-        let value = if little_endian {
-            u16::from_le_bytes([data[i], data[i + 1]])
-        } else {
-            u16::from_be_bytes([data[i], data[i + 1]])
-        };
-        buf.push(value);
-        i += 2;
+    let state = Arc::new(Mutex::new(AppState::default()));
+    let loader = FontLoaderBuilder::new()
+        .with_cache(true)
+        .with_state(state.clone())
+        .build();
+    let result = loader.process(paths.iter().map(PathBuf::from).collect());
+    if let Ok(state) = state.lock() {
+        let mut session = load_session();
+        session.registered_faces += state.registered_faces();
+        let _ = save_session(&session);
+    }
+    let Ok(result) = result else {
+        return 1;
+    };
+    if export_html_report(report, &result).is_err() {
+        return 1;
+    }
+    if result.failed == 0 && result.missing == 0 {
+        0
+    } else {
+        2
     }
-    Some(String::from_utf16_lossy(&buf))
 }
 
-fn parse_ass_fonts(text: &str) -> HashSet<String> {
-    let mut fonts = HashSet::new();
-    let mut section = String::new();
-    let mut style_font_idx: Option<usize> = None;
-    let mut event_text_idx: Option<usize> = None;
+/// 路径比较用的规范形式：统一使用 `\` 分隔、去掉末尾分隔符并转为小写
+/// （Windows 路径不区分大小写）。
+fn normalize_path_key(path: &str) -> String {
+    let mut key = path.replace('/', "\\").to_lowercase();
+    // 盘符根目录保留分隔符，`C:\` 与 `C:` 含义不同
+    while key.ends_with('\\') && !key.ends_with(":\\") {
+        key.pop();
+    }
+    key
+}
 
-    for raw in text.lines() {
-        let line = raw.trim();
-        if line.starts_with('[') && line.ends_with(']') {
-            section = line[1..line.len() - 1].to_lowercase();
-            continue;
+/// `child` 是否等于 `ancestor` 或位于其下。只做字符串比较，不访问文件系统。
+fn is_descendant_path(child: &str, ancestor: &str) -> bool {
+    let child = normalize_path_key(child);
+    let ancestor = normalize_path_key(ancestor);
+    if child == ancestor {
+        return true;
+    }
+    if ancestor.ends_with('\\') {
+        return child.starts_with(&ancestor);
+    }
+    child.len() > ancestor.len()
+        && child.starts_with(&ancestor)
+        && child.as_bytes()[ancestor.len()] == b'\\'
+}
+
+/// 展开用户给出的路径：文件原样保留，目录递归遍历其下所有文件。
+///
+/// `max_depth` 限制进入子目录的层数，`Some(0)` 只取目录本身的文件，`None` 不限。
+/// 不存在的路径和无法读取的子目录会被静默跳过，返回的列表未按类型过滤。
+///
+/// `.7z` 压缩包（直接给出或在目录中找到）由 [`extract_7z_fonts_and_subs`] 解压到临时
+/// 目录，以其中的字幕和字体文件代替压缩包本身；`.zip` 压缩包由 [`read_zip_members`]
+/// 读入内存，不落盘。解压或读取失败时返回错误。
+///
+/// 位于另一个输入目录之下的输入（不限层数时）会被合并，最终列表再按
+/// [`normalize_path_key`] 去重，两者合计的条数累加到 `redundant`。
+fn collect_files(
+    paths: &[String],
+    max_depth: Option<usize>,
+    shortcuts: &mut Vec<(PathBuf, Option<PathBuf>)>,
+    redundant: &mut usize,
+) -> Result<Vec<PathBuf>, String> {
+    let mut roots: Vec<PathBuf> = Vec::new();
+    clear_archive_members();
+    for raw in paths {
+        let mut path = PathBuf::from(raw);
+        // 只解析直接传入的快捷方式，目录中的快捷方式可能指回上级目录
+        if is_shortcut_file(&path) {
+            let target = resolve_shortcut(&path);
+            shortcuts.push((path, target.clone()));
+            let Some(target) = target else {
+                continue;
+            };
+            path = target;
         }
-        let lower = line.to_lowercase();
-        if section.contains("styles") {
-            if lower.starts_with("format:") {
-                let format = parse_format(line, 7);
-                style_font_idx = format.iter().position(|v| v == "fontname");
-            } else if lower.starts_with("style:") {
-                if let Some(font) = parse_style_font(line, style_font_idx) {
-                    fonts.insert(font);
-                }
+        if max_depth.is_none() {
+            let path_str = path.to_string_lossy();
+            if roots
+                .iter()
+                .any(|root| root.is_dir() && is_descendant_path(&path_str, &root.to_string_lossy()))
+            {
+                *redundant += 1;
+                continue;
             }
-        } else if section.contains("events") {
-            if lower.starts_with("format:") {
-                let format = parse_format(line, 7);
-                event_text_idx = format.iter().position(|v| v == "text");
-            } else if lower.starts_with("dialogue:") || lower.starts_with("comment:") {
-                if let Some(text) = extract_event_text(line, event_text_idx) {
-                    for font in parse_fn_tags(&text) {
-                        fonts.insert(font);
-                    }
-                }
+            if path.is_dir() {
+                let before = roots.len();
+                roots.retain(|root| !is_descendant_path(&root.to_string_lossy(), &path_str));
+                *redundant += before - roots.len();
             }
         }
+        roots.push(path);
+    }
+    let mut files = Vec::new();
+    for path in roots {
+        if path.is_file() {
+            files.push(path);
+        } else if path.is_dir() {
+            let _ = walk_dir(&path, &mut files, max_depth);
+        }
+    }
+    let mut seen = HashSet::new();
+    let before = files.len();
+    files.retain(|path| seen.insert(normalize_path_key(&path.to_string_lossy())));
+    *redundant += before - files.len();
+    let mut expanded = Vec::with_capacity(files.len());
+    for path in files {
+        if is_7z_file(&path) {
+            let extracted = extract_7z_fonts_and_subs(&path, &archive_extract_dir(&path))
+                .map_err(|err| format!("解压 {} 失败: {}", path.to_string_lossy(), err))?;
+            expanded.extend(extracted);
+        } else if is_zip_file(&path) {
+            let members = read_zip_members(&path)
+                .map_err(|err| format!("读取 {} 失败: {}", path.to_string_lossy(), err))?;
+            expanded.extend(members);
+        } else {
+            expanded.push(path);
+        }
     }
+    Ok(expanded)
+}
 
-    fonts
+fn is_shortcut_file(path: &Path) -> bool {
+    path.is_file()
+        && path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("lnk"))
 }
 
-fn parse_format(line: &str, start: usize) -> Vec<String> {
-    let content = line[start..].trim();
-    content
-        .split(',')
-        .map(|v| v.trim().to_lowercase())
-        .collect()
+/// 通过 `IShellLinkW` 读取 `.lnk` 快捷方式指向的路径，失败或目标不是文件系统路径时
+/// 返回 `None`。
+#[cfg(target_os = "windows")]
+fn resolve_shortcut(path: &Path) -> Option<PathBuf> {
+    use windows::core::Interface;
+    let wide = to_wide(&path.to_string_lossy());
+    let mut buf = [0u16; 1024];
+    unsafe {
+        // 工作线程每个任务新建一次，在此初始化 COM 即可；已初始化时返回 S_FALSE
+        let _ = CoInitializeEx(None, COINIT_APARTMENTTHREADED);
+        let link: IShellLinkW = CoCreateInstance(&ShellLink, None, CLSCTX_INPROC_SERVER).ok()?;
+        link.cast::<IPersistFile>()
+            .ok()?
+            .Load(PCWSTR(wide.as_ptr()), STGM_READ)
+            .ok()?;
+        link.GetPath(&mut buf, std::ptr::null_mut(), 0).ok()?;
+    }
+    let len = buf.iter().position(|&c| c == 0).unwrap_or(buf.len());
+    (len > 0).then(|| PathBuf::from(String::from_utf16_lossy(&buf[..len])))
 }
 
-fn parse_style_font(line: &str, idx: Option<usize>) -> Option<String> {
-    let content = line[6..].trim();
-    let parts: Vec<&str> = content.split(',').collect();
-    let raw = if let Some(i) = idx {
-        parts.get(i)
-    } else {
-        parts.get(1)
-    }?;
-    normalize_font_name(raw)
+#[cfg(not(target_os = "windows"))]
+fn resolve_shortcut(_path: &Path) -> Option<PathBuf> {
+    None
 }
 
-fn extract_event_text(line: &str, idx: Option<usize>) -> Option<String> {
-    let content = line[9..].trim();
-    let index = idx.unwrap_or(9);
-    let mut count = 0;
-    let mut split_at = None;
-    for (pos, ch) in content.char_indices() {
-        if ch == ',' {
-            if count == index {
-                split_at = Some(pos + 1);
-                break;
-            }
-            count += 1;
-        }
-    }
-    let text = match split_at {
-        Some(pos) => &content[pos..],
-        None => "",
-    };
-    Some(text.to_string())
+/// 同一目录两次自动加载的最短间隔，播放器反复重启时不会堆积任务
+const AUTO_LOAD_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// 进程监视线程发现的新进程。
+struct StartedProcess {
+    pid: u32,
+    name: String,
+    command_line: Option<String>,
 }
 
-fn parse_fn_tags(text: &str) -> Vec<String> {
-    let mut res = Vec::new();
-    let mut start = 0;
-    while let Some(pos) = text[start..].find("\\fn") {
-        let idx = start + pos + 3;
-        let mut s = &text[idx..];
-        s = s.trim_start();
-        if s.starts_with('(') {
-            if let Some(end) = s[1..].find(')') {
-                let name = &s[1..1 + end];
-                if let Some(normalized) = normalize_font_name(name) {
-                    res.push(normalized);
+/// HTTP 接口收到的一次请求，界面线程处理后经 `reply` 回复 `(状态码, JSON 正文)`。
+struct HttpRequest {
+    command: HttpCommand,
+    reply: mpsc::Sender<(u16, String)>,
+}
+
+enum HttpCommand {
+    Load(Vec<String>),
+    Unload,
+    Status,
+}
+
+#[derive(Deserialize)]
+struct HttpLoadBody {
+    paths: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct HttpStatus<'a> {
+    busy: bool,
+    /// 排队中的任务数，不含正在执行的
+    queued: usize,
+    files: usize,
+    faces: usize,
+    fonts: Vec<HttpFont<'a>>,
+}
+
+#[derive(Serialize)]
+struct HttpFont<'a> {
+    path: &'a str,
+    names: &'a [String],
+    faces: usize,
+}
+
+#[derive(Serialize)]
+struct HttpError<'a> {
+    error: &'a str,
+}
+
+/// 请求正文的上限
+const MAX_HTTP_BODY: usize = 1024 * 1024;
+
+/// 等待界面线程处理请求的最长时间
+const HTTP_REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+fn http_json(value: &impl Serialize) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
+
+fn http_error(message: &str) -> String {
+    http_json(&HttpError { error: message })
+}
+
+/// HTTP 接口线程：逐个处理连接，解析出的命令交给界面线程执行，`stop` 置位后退出。
+fn serve_http(
+    listener: TcpListener,
+    token: String,
+    tx: mpsc::Sender<HttpRequest>,
+    repaint: RepaintSignal,
+    stop: Arc<AtomicBool>,
+) {
+    while !stop.load(Ordering::Relaxed) {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                let _ = handle_http_connection(stream, &token, &tx, &repaint);
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => {
+                if !sleep_unless_cancelled(Duration::from_millis(100), &stop) {
+                    return;
                 }
-                start = idx + 1 + end + 1;
-                continue;
             }
-        }
-        let mut end = s.len();
-        for (i, ch) in s.char_indices() {
-            if ch == '\\' || ch == '}' {
-                end = i;
-                break;
+            Err(_) => {
+                if !sleep_unless_cancelled(Duration::from_secs(1), &stop) {
+                    return;
+                }
             }
         }
-        let name = &s[..end];
-        if let Some(normalized) = normalize_font_name(name) {
-            res.push(normalized);
-        }
-        start = idx + end;
     }
-    res
 }
 
-fn normalize_font_name(name: &str) -> Option<String> {
-    let mut s = name.trim().trim_matches('\u{0}').to_string();
-    if s.starts_with('@') {
-        s.remove(0);
-    }
-    if s.is_empty() {
-        None
-    } else {
-        Some(s)
-    }
+fn handle_http_connection(
+    mut stream: TcpStream,
+    token: &str,
+    tx: &mpsc::Sender<HttpRequest>,
+    repaint: &RepaintSignal,
+) -> std::io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(HTTP_REPLY_TIMEOUT))?;
+    let (status, body) = match read_http_request(&stream) {
+        Ok(request) => route_http_request(&request, token, tx, repaint),
+        Err(err) => (400, http_error(&err)),
+    };
+    let reason = match status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "Service Unavailable",
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason,
+        body.len(),
+        body
+    )?;
+    stream.flush()
 }
 
-fn parse_font_names(path: &Path) -> Vec<String> {
-    let data = match fs::read(path) {
-        Ok(data) => data,
-        Err(_) => return Vec::new(),
-    };
-    parse_font_names_from_bytes(&data)
+/// 解析后的 HTTP 请求，头部名称已转为小写。
+struct ParsedHttpRequest {
+    method: String,
+    /// 去掉查询串的路径
+    path: String,
+    headers: HashMap<String, String>,
+    body: Vec<u8>,
 }
 
-fn parse_font_names_from_bytes(data: &[u8]) -> Vec<String> {
-    let mut names = HashSet::new();
-    if data.len() < 4 {
-        return Vec::new();
-    }
-    if &data[0..4] == b"ttcf" {
-        for offset in parse_ttc_offsets(data) {
-            for name in parse_otf_names_at(data, offset) {
-                names.insert(name);
-            }
+/// 读取请求行、头部和按 `Content-Length` 读取的正文。
+fn read_http_request(stream: &TcpStream) -> Result<ParsedHttpRequest, String> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line).map_err(|e| e.to_string())?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err("请求行无效".to_string());
+    };
+    let method = method.to_string();
+    let path = target.split('?').next().unwrap_or(target).to_string();
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).map_err(|e| e.to_string())? == 0 {
+            break;
         }
-    } else {
-        for name in parse_otf_names_at(data, 0) {
-            names.insert(name);
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
         }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+        }
+    }
+    let length: usize = match headers.get("content-length") {
+        Some(value) => value
+            .parse()
+            .map_err(|_| "Content-Length 无效".to_string())?,
+        None => 0,
+    };
+    if length > MAX_HTTP_BODY {
+        return Err(format!("请求正文超过 {} 字节", MAX_HTTP_BODY));
     }
-    names.into_iter().collect()
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).map_err(|e| e.to_string())?;
+    Ok(ParsedHttpRequest {
+        method,
+        path,
+        headers,
+        body,
+    })
 }
 
-fn parse_ttc_offsets(data: &[u8]) -> Vec<usize> {
-    if data.len() < 12 {
-        return Vec::new();
-    }
-    let num_fonts = read_u32_be(data, 8).unwrap_or(0) as usize;
-    let mut offsets = Vec::new();
-    let mut pos = 12;
-    for _ in 0..num_fonts {
-        if let Some(val) = read_u32_be(data, pos) {
-            offsets.push(val as usize);
+/// 校验请求并转成 [`HttpCommand`] 交给界面线程，返回要回复的状态码和正文。
+///
+/// 设置了密钥时要求 `X-Fontloader-Token` 一致；未设置时拒绝带 `Origin` 头的请求，
+/// 以免任意网页借浏览器向本机接口发请求。
+fn route_http_request(
+    request: &ParsedHttpRequest,
+    token: &str,
+    tx: &mpsc::Sender<HttpRequest>,
+    repaint: &RepaintSignal,
+) -> (u16, String) {
+    let headers = &request.headers;
+    if !token.is_empty() {
+        if headers.get("x-fontloader-token").map(String::as_str) != Some(token) {
+            return (401, http_error("密钥不正确"));
         }
-        pos += 4;
+    } else if headers.contains_key("origin") {
+        return (403, http_error("未设置密钥时不接受来自网页的请求"));
     }
-    offsets
+    let command = match (request.method.as_str(), request.path.as_str()) {
+        ("POST", "/load") => match serde_json::from_slice::<HttpLoadBody>(&request.body) {
+            Ok(load) if !load.paths.is_empty() => HttpCommand::Load(load.paths),
+            Ok(_) => return (400, http_error("paths 为空")),
+            Err(err) => return (400, http_error(&format!("正文无效: {}", err))),
+        },
+        ("POST", "/unload") => HttpCommand::Unload,
+        ("GET", "/status") => HttpCommand::Status,
+        (_, "/load" | "/unload" | "/status") => return (405, http_error("请求方法不支持")),
+        _ => return (404, http_error("没有这个接口")),
+    };
+    let (reply, reply_rx) = mpsc::channel();
+    if tx.send(HttpRequest { command, reply }).is_err() {
+        return (503, http_error("程序正在退出"));
+    }
+    repaint.request();
+    reply_rx
+        .recv_timeout(HTTP_REPLY_TIMEOUT)
+        .unwrap_or_else(|_| (503, http_error("界面无响应")))
 }
 
-fn parse_otf_names_at(data: &[u8], offset: usize) -> Vec<String> {
-    if data.len() < offset + 12 {
-        return Vec::new();
-    }
-    let num_tables = read_u16_be(data, offset + 4).unwrap_or(0) as usize;
-    let table_start = offset + 12;
-    let mut name_table = None;
-    for i in 0..num_tables {
-        let rec = table_start + i * 16;
-        if data.len() < rec + 16 {
-            break;
-        }
-        let tag = &data[rec..rec + 4];
-        if tag == b"name" {
-            let table_offset = read_u32_be(data, rec + 8).unwrap_or(0) as usize;
-            let length = read_u32_be(data, rec + 12).unwrap_or(0) as usize;
-            name_table = Some((table_offset, length));
-            break;
+/// 每隔两秒对比进程列表，把新出现的、名称在 `names` 中（不区分大小写）的进程连同
+/// 命令行发回界面线程。启动监视时已在运行的进程不算新进程。
+fn watch_processes(
+    names: Vec<String>,
+    tx: mpsc::Sender<StartedProcess>,
+    repaint: RepaintSignal,
+    stop: Arc<AtomicBool>,
+) {
+    let names: HashSet<String> = names.iter().map(|name| name.to_lowercase()).collect();
+    let mut seen: HashSet<u32> = list_processes().into_iter().map(|(pid, _)| pid).collect();
+    while sleep_unless_cancelled(Duration::from_secs(2), &stop) {
+        let current = list_processes();
+        for (pid, name) in &current {
+            if seen.contains(pid) || !names.contains(&name.to_lowercase()) {
+                continue;
+            }
+            let started = StartedProcess {
+                pid: *pid,
+                name: name.clone(),
+                command_line: process_command_line(*pid),
+            };
+            if tx.send(started).is_err() {
+                return;
+            }
+            repaint.request();
         }
+        seen = current.into_iter().map(|(pid, _)| pid).collect();
     }
-    let Some((table_offset, length)) = name_table else {
-        return Vec::new();
+}
+
+/// 当前所有进程的 `(PID, 可执行文件名)`。
+#[cfg(target_os = "windows")]
+fn list_processes() -> Vec<(u32, String)> {
+    let mut processes = Vec::new();
+    let Ok(snapshot) = (unsafe { CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0) }) else {
+        return processes;
     };
-    let table_pos = offset + table_offset;
-    if data.len() < table_pos + length || data.len() < table_pos + 6 {
-        return Vec::new();
-    }
-    let count = read_u16_be(data, table_pos + 2).unwrap_or(0) as usize;
-    let string_offset = read_u16_be(data, table_pos + 4).unwrap_or(0) as usize;
-    let records_start = table_pos + 6;
-    let mut result = HashSet::new();
-    for i in 0..count {
-        let rec = records_start + i * 12;
-        if data.len() < rec + 12 {
-            break;
-        }
-        let platform = read_u16_be(data, rec).unwrap_or(0);
-        let name_id = read_u16_be(data, rec + 6).unwrap_or(0);
-        let length = read_u16_be(data, rec + 8).unwrap_or(0) as usize;
-        let offset_str = read_u16_be(data, rec + 10).unwrap_or(0) as usize;
-        if platform != 3 {
-            continue;
-        }
-        if name_id != 1 && name_id != 4 {
-            continue;
-        }
-        let str_start = table_pos + string_offset + offset_str;
-        let str_end = str_start + length;
-        if data.len() < str_end || length == 0 {
-            continue;
-        }
-        let name = decode_utf16be(&data[str_start..str_end]);
-        if let Some(normalized) = normalize_font_name(&name) {
-            result.insert(normalized);
-        }
+    let mut entry = PROCESSENTRY32W {
+        dwSize: std::mem::size_of::<PROCESSENTRY32W>() as u32,
+        ..Default::default()
+    };
+    let mut ok = unsafe { Process32FirstW(snapshot, &mut entry) }.is_ok();
+    while ok {
+        let len = entry
+            .szExeFile
+            .iter()
+            .position(|&c| c == 0)
+            .unwrap_or(entry.szExeFile.len());
+        processes.push((
+            entry.th32ProcessID,
+            String::from_utf16_lossy(&entry.szExeFile[..len]),
+        ));
+        ok = unsafe { Process32NextW(snapshot, &mut entry) }.is_ok();
     }
-    result.into_iter().collect()
+    let _ = unsafe { CloseHandle(snapshot) };
+    processes
 }
 
-fn decode_utf16be(data: &[u8]) -> String {
-    let mut buf = Vec::with_capacity(data.len() / 2);
-    let mut i = 0;
-    while i + 1 < data.len() {
-        buf.push(u16::from_be_bytes([data[i], data[i + 1]]));
-        i += 2;
+#[cfg(not(target_os = "windows"))]
+fn list_processes() -> Vec<(u32, String)> {
+    Vec::new()
+}
+
+/// 通过 PowerShell 查询进程的命令行。进程快照不含命令行，读取其他进程的 PEB 又需要
+/// 未公开的接口，每个新进程只查询一次，开销可以接受。
+#[cfg(target_os = "windows")]
+fn process_command_line(pid: u32) -> Option<String> {
+    use std::os::windows::process::CommandExt;
+    let script = format!(
+        "[Console]::OutputEncoding=[Text.Encoding]::UTF8; (Get-CimInstance Win32_Process -Filter 'ProcessId={}').CommandLine",
+        pid
+    );
+    let output = std::process::Command::new("powershell")
+        .args(["-NoProfile", "-NonInteractive", "-Command", &script])
+        // CREATE_NO_WINDOW，避免闪出控制台窗口
+        .creation_flags(0x0800_0000)
+        .output()
+        .ok()?;
+    let line = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !line.is_empty()).then_some(line)
+}
+
+#[cfg(not(target_os = "windows"))]
+fn process_command_line(_pid: u32) -> Option<String> {
+    None
+}
+
+/// 按 Windows 规则拆分命令行：空白分隔，双引号内的空白不分隔，引号本身去掉。
+fn split_command_line(command_line: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut has_arg = false;
+    for c in command_line.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                has_arg = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if has_arg {
+                    args.push(std::mem::take(&mut current));
+                    has_arg = false;
+                }
+            }
+            c => {
+                current.push(c);
+                has_arg = true;
+            }
+        }
     }
-    String::from_utf16_lossy(&buf)
+    if has_arg {
+        args.push(current);
+    }
+    args
 }
 
-fn read_u16_be(data: &[u8], offset: usize) -> Option<u16> {
-    if data.len() < offset + 2 {
-        None
-    } else {
-        Some(u16::from_be_bytes([data[offset], data[offset + 1]]))
-    }
+/// 从播放器命令行中找出第一个存在的本地路径参数（跳过程序本身），返回该参数和对应
+/// 目录：文件取所在目录，目录取其本身。`--sub-file=路径` 形式的选项取等号后的部分。
+fn command_line_folder(command_line: &str) -> Option<(String, PathBuf)> {
+    split_command_line(command_line)
+        .into_iter()
+        .skip(1)
+        .find_map(|arg| {
+            let value = match arg.split_once('=') {
+                Some((option, value)) if option.starts_with(['-', '/']) => value,
+                _ => arg.as_str(),
+            };
+            let path = PathBuf::from(value);
+            let dir = if path.is_file() {
+                path.parent()?.to_path_buf()
+            } else if path.is_dir() {
+                path
+            } else {
+                return None;
+            };
+            Some((arg.clone(), dir))
+        })
 }
 
-fn read_u32_be(data: &[u8], offset: usize) -> Option<u32> {
-    if data.len() < offset + 4 {
-        None
-    } else {
-        Some(u32::from_be_bytes([
-            data[offset],
-            data[offset + 1],
-            data[offset + 2],
-            data[offset + 3],
-        ]))
-    }
+/// 目录（不递归）中的 ASS/SSA 字幕数。
+fn count_ass_files(dir: &Path) -> usize {
+    fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| is_ass_file(&entry.path()))
+        .count()
 }
 
-fn is_sub_file(path: &Path) -> bool {
-    matches!(
-        path.extension().and_then(|v| v.to_str()).map(|v| v.to_lowercase()),
-        Some(ext)
-            if ext == "ass"
-                || ext == "ssa"
-                || ext == "srt"
-                || ext == "vtt"
-                || ext == "sub"
-                || ext == "idx"
-                || ext == "sup"
-    )
+/// 最多保留 `max` 个字符，截断时加省略号。
+fn truncate_chars(text: &str, max: usize) -> String {
+    match text.char_indices().nth(max) {
+        Some((pos, _)) => format!("{}…", &text[..pos]),
+        None => text.to_string(),
+    }
 }
 
-fn is_ass_file(path: &Path) -> bool {
-    matches!(
-        path.extension().and_then(|v| v.to_str()).map(|v| v.to_lowercase()),
-        Some(ext) if ext == "ass" || ext == "ssa"
-    )
+/// 逗号（全角或半角）分隔的名称列表，去掉空白和空项。
+fn parse_name_list(text: &str) -> Vec<String> {
+    text.split([',', '，'])
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(String::from)
+        .collect()
 }
 
-fn is_font_file(path: &Path) -> bool {
-    matches!(
-        path.extension().and_then(|v| v.to_str()).map(|v| v.to_lowercase()),
-        Some(ext) if ext == "ttf" || ext == "otf" || ext == "ttc"
-    )
+/// "用播放器打开"可选的文件类型
+const PLAYER_FILE_EXTENSIONS: [&str; 11] = [
+    "mkv", "mp4", "avi", "m2ts", "ts", "webm", "mov", "flv", "wmv", "ass", "ssa",
+];
+
+/// 视频同目录下文件名以视频名（不含扩展名）开头的 ASS 字幕；没有时返回整个目录。
+fn companion_subtitles(video: &Path) -> Vec<String> {
+    let Some(dir) = video.parent() else {
+        return vec![video.to_string_lossy().to_string()];
+    };
+    let stem = video
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let mut subs: Vec<String> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            is_ass_file(path)
+                && path
+                    .file_name()
+                    .is_some_and(|name| name.to_string_lossy().to_lowercase().starts_with(&stem))
+        })
+        .map(|path| path.to_string_lossy().to_string())
+        .collect();
+    if subs.is_empty() {
+        subs.push(dir.to_string_lossy().to_string());
+    }
+    subs.sort();
+    subs
 }
 
-fn to_wide(value: &str) -> Vec<u16> {
-    value.encode_utf16().chain(std::iter::once(0)).collect()
+/// 按空格拆分参数模板，把每个参数中的 `%f` 替换为 `file`；模板中没有 `%f` 时把文件
+/// 追加为最后一个参数。参数逐个传给播放器，路径含空格也无需加引号。
+fn player_command_args(template: &str, file: &Path) -> Vec<String> {
+    let file = file.to_string_lossy();
+    let mut args: Vec<String> = template
+        .split_whitespace()
+        .map(|arg| arg.replace("%f", &file))
+        .collect();
+    if !template.contains("%f") {
+        args.push(file.to_string());
+    }
+    args
 }
 
-fn add_font_resource(path: &str) -> bool {
-    let wide = to_wide(path);
-    unsafe { AddFontResourceW(PCWSTR(wide.as_ptr())) > 0 }
+fn is_7z_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|v| v.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("7z"))
 }
 
-fn remove_font_resource(path: &str) -> bool {
-    let wide = to_wide(path);
-    unsafe { RemoveFontResourceW(PCWSTR(wide.as_ptr())).0 != 0 }
+fn is_zip_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|v| v.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("zip"))
 }
 
-fn broadcast_font_change() {
-    unsafe {
-        SendMessageW(HWND_BROADCAST, WM_FONTCHANGE, WPARAM(0), LPARAM(0));
-    }
+/// 从 `.zip` 读入内存的字幕和字体，以 [`archive_extract_dir`] 下的虚拟路径为键。
+static ARCHIVE_MEMBERS: Mutex<BTreeMap<PathBuf, Arc<[u8]>>> = Mutex::new(BTreeMap::new());
+
+fn archive_member(path: &Path) -> Option<Arc<[u8]>> {
+    ARCHIVE_MEMBERS.lock().ok()?.get(path).cloned()
 }
 
-fn cache_file_path() -> Option<PathBuf> {
-    let exe_path = std::env::current_exe().ok()?;
-    let exe_dir = exe_path.parent()?;
-    Some(exe_dir.join("cache.json"))
+fn clear_archive_members() {
+    if let Ok(mut members) = ARCHIVE_MEMBERS.lock() {
+        members.clear();
+    }
 }
 
-fn load_cache_file() -> CacheFile {
-    let Some(path) = cache_file_path() else {
-        return CacheFile::default();
-    };
-    let data = fs::read(path).ok();
-    if let Some(bytes) = data {
-        serde_json::from_slice(&bytes).unwrap_or_default()
-    } else {
-        CacheFile::default()
+/// 把 `.zip` 中的字幕和字体读入内存，返回它们的虚拟路径。
+///
+/// 虚拟路径即解压后应在的位置，字幕解析和字体索引直接读内存中的数据，只有真正要
+/// 加载的字体才由 [`materialize_archive_member`] 写到该位置。包内带 `..` 或绝对路径
+/// 的条目会被跳过。
+fn read_zip_members(path: &Path) -> Result<Vec<PathBuf>, String> {
+    let file = fs::File::open(path).map_err(|e| e.to_string())?;
+    let mut archive = zip::ZipArchive::new(BufReader::new(file)).map_err(|e| e.to_string())?;
+    let out_dir = archive_extract_dir(path);
+    let mut members = Vec::new();
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i).map_err(|e| e.to_string())?;
+        let Some(name) = entry.enclosed_name() else {
+            continue;
+        };
+        let dest = out_dir.join(name);
+        if entry.is_dir() || !(is_sub_file(&dest) || is_font_file(&dest)) {
+            continue;
+        }
+        let mut data = Vec::with_capacity(entry.size() as usize);
+        entry.read_to_end(&mut data).map_err(|e| e.to_string())?;
+        if let Ok(mut registry) = ARCHIVE_MEMBERS.lock() {
+            registry.insert(dest.clone(), data.into());
+        }
+        members.push(dest);
     }
+    Ok(members)
 }
 
-fn save_cache_file(cache: &CacheFile) -> Result<(), String> {
-    let Some(path) = cache_file_path() else {
+/// 把压缩包成员写到它的虚拟路径上，供 `AddFontResourceW` 使用。不是压缩包成员或
+/// 已经写过时什么都不做。
+fn materialize_archive_member(path: &Path) -> Result<(), String> {
+    let Some(data) = archive_member(path) else {
         return Ok(());
     };
+    if path.is_file() {
+        return Ok(());
+    }
     if let Some(parent) = path.parent() {
         fs::create_dir_all(parent).map_err(|e| e.to_string())?;
     }
-    let data = serde_json::to_vec_pretty(cache).map_err(|e| e.to_string())?;
-    fs::write(path, data).map_err(|e| e.to_string())?;
-    Ok(())
+    fs::write(path, &data).map_err(|e| format!("写入 {} 失败: {}", path.display(), e))
+}
+
+/// 压缩包的解压目录：临时目录下按文件名、完整路径和修改时间区分，同一个未改动的
+/// 压缩包重复处理时解压到同一位置。解压出的字体加载后仍被占用，因此不主动删除。
+fn archive_extract_dir(archive: &Path) -> PathBuf {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    archive.hash(&mut hasher);
+    metadata_mtime(archive).hash(&mut hasher);
+    let stem = archive
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    std::env::temp_dir()
+        .join("fontloader-7z")
+        .join(format!("{}-{:016x}", stem, hasher.finish()))
+}
+
+/// 把 `.7z` 压缩包中的字幕和字体文件解压到 `out_dir`，保留包内的目录结构，返回解压出
+/// 的文件。
+///
+/// 先用 `sevenz-rust` 解压；遇到它不支持的压缩方法（如 BCJ2、PPMd）或加密时改为调用
+/// `PATH` 中的 `7z`，两者都失败时返回 `sevenz-rust` 的错误。包内带 `..` 或绝对路径的
+/// 条目会被跳过。
+fn extract_7z_fonts_and_subs(path: &Path, out_dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut extracted = Vec::new();
+    let result =
+        sevenz_rust::decompress_file_with_extract_fn(path, out_dir, |entry, reader, dest| {
+            let name = Path::new(entry.name());
+            let safe = name
+                .components()
+                .all(|c| matches!(c, std::path::Component::Normal(_)));
+            if entry.is_directory() || !safe || !(is_sub_file(dest) || is_font_file(dest)) {
+                std::io::copy(reader, &mut std::io::sink()).map_err(sevenz_rust::Error::io)?;
+                return Ok(true);
+            }
+            if let Some(parent) = dest.parent() {
+                fs::create_dir_all(parent).map_err(sevenz_rust::Error::io)?;
+            }
+            let mut file = fs::File::create(dest).map_err(sevenz_rust::Error::io)?;
+            std::io::copy(reader, &mut file).map_err(sevenz_rust::Error::io)?;
+            extracted.push(dest.clone());
+            Ok(true)
+        });
+    match result {
+        Ok(()) => Ok(extracted),
+        Err(err) => extract_7z_with_cli(path, out_dir).map_err(|_| err.to_string()),
+    }
 }
 
-fn collect_files(paths: &[String]) -> Result<Vec<PathBuf>, String> {
+/// 调用 `7z x` 解压整个压缩包，再从 `out_dir` 中挑出字幕和字体文件。
+fn extract_7z_with_cli(path: &Path, out_dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut command = std::process::Command::new("7z");
+    command
+        .arg("x")
+        .arg("-y")
+        .arg(format!("-o{}", out_dir.to_string_lossy()))
+        .arg(path);
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        // CREATE_NO_WINDOW，避免闪出控制台窗口
+        command.creation_flags(0x0800_0000);
+    }
+    let output = command.output().map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
     let mut files = Vec::new();
+    walk_dir(out_dir, &mut files, None)?;
+    files.retain(|file| is_sub_file(file) || is_font_file(file));
+    Ok(files)
+}
+
+/// 输入目录（输入文件则取其所在目录）下名称与 `names` 之一相同（不区分大小写）的
+/// 直接子目录，按发现顺序去重。
+fn font_folder_dirs(paths: &[String], names: &[String]) -> Vec<PathBuf> {
+    let names: Vec<String> = names.iter().map(|n| n.to_lowercase()).collect();
+    let mut dirs = Vec::new();
+    if names.is_empty() {
+        return dirs;
+    }
     for raw in paths {
-        let path = PathBuf::from(raw);
-        if path.is_file() {
-            files.push(path);
-        } else if path.is_dir() {
-            let _ = walk_dir(&path, &mut files);
+        let path = Path::new(raw);
+        let base = if path.is_dir() {
+            Some(path)
+        } else {
+            path.parent()
+        };
+        let Some(entries) = base.and_then(|base| fs::read_dir(base).ok()) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let dir = entry.path();
+            let matched = dir
+                .file_name()
+                .is_some_and(|name| names.contains(&name.to_string_lossy().to_lowercase()));
+            if matched && dir.is_dir() && !dirs.contains(&dir) {
+                dirs.push(dir);
+            }
         }
     }
-    Ok(files)
+    dirs
 }
 
-fn walk_dir(dir: &Path, out: &mut Vec<PathBuf>) -> Result<(), String> {
+fn walk_dir(dir: &Path, out: &mut Vec<PathBuf>, max_depth: Option<usize>) -> Result<(), String> {
     if let Ok(entries) = fs::read_dir(dir) {
         for entry in entries.flatten() {
             let path = entry.path();
             if path.is_dir() {
-                let _ = walk_dir(&path, out);
+                match max_depth {
+                    Some(0) => {}
+                    depth => {
+                        let _ = walk_dir(&path, out, depth.map(|d| d - 1));
+                    }
+                }
             } else if path.is_file() {
                 out.push(path);
             }
@@ -981,34 +11260,140 @@ fn walk_dir(dir: &Path, out: &mut Vec<PathBuf>) -> Result<(), String> {
     Ok(())
 }
 
-fn setup_custom_fonts(ctx: &egui::Context) {
+/// 可显示中文的界面字体，按优先级排列，在系统和当前用户的 Fonts 目录中查找。
+const UI_FONT_CANDIDATES: &[&str] = &[
+    "msyh.ttc",
+    "msyh.ttf",
+    "simhei.ttf",
+    "msjh.ttc",
+    "msjh.ttf",
+    "NotoSansSC-VF.ttf",
+    "NotoSansSC-Regular.otf",
+    "NotoSansCJK-Regular.ttc",
+    "NotoSansCJKsc-Regular.otf",
+    "simsun.ttc",
+];
+
+/// 按 [`UI_FONT_CANDIDATES`] 的顺序找到第一个通过 [`read_ui_font`] 检查的界面字体。
+/// 存在但不可用的候选记入 `logs` 后跳过。
+fn find_ui_font(logs: &mut Vec<String>) -> Option<(PathBuf, Vec<u8>)> {
+    let dirs: Vec<PathBuf> = std::iter::once(system_fonts_dir())
+        .chain(user_fonts_dir())
+        .collect();
+    UI_FONT_CANDIDATES.iter().find_map(|name| {
+        dirs.iter().find_map(|dir| {
+            let path = dir.join(name);
+            if !path.is_file() {
+                return None;
+            }
+            match read_ui_font(&path) {
+                Ok(data) => Some((path, data)),
+                Err(err) => {
+                    logs.push(format!(
+                        "[X] 界面字体 {} 无法使用（{}），已跳过",
+                        path.to_string_lossy(),
+                        err
+                    ));
+                    None
+                }
+            }
+        })
+    })
+}
+
+/// 读取用户指定的界面字体并检查能否使用：名称解析见 [`parse_font_names_from_bytes`]，
+/// 另外 egui 遇到无法解析的字体数据会直接 panic，需先用 `ab_glyph` 试解析。
+fn read_ui_font(path: &Path) -> Result<Vec<u8>, String> {
+    let data = fs::read(path).map_err(|e| e.to_string())?;
+    if let Some(reason) = parse_font_names_from_bytes(&data).invalid {
+        return Err(reason);
+    }
+    ab_glyph::FontRef::try_from_slice(&data).map_err(|e| e.to_string())?;
+    Ok(data)
+}
+
+/// 设置界面字体，返回设置后的字体定义，供之后追加字体时作为基础，以及说明选用了
+/// 哪个界面字体的日志。
+///
+/// `custom` 非空时优先使用该文件，文件不存在或不可用时记一条警告并改为自动选择。
+fn setup_custom_fonts(ctx: &egui::Context, custom: &str) -> (egui::FontDefinitions, Vec<String>) {
     let mut fonts = egui::FontDefinitions::default();
+    let mut logs = Vec::new();
 
-    // 1. 微软雅黑 (主字体)
-    let msyh_path = PathBuf::from("C:\\Windows\\Fonts\\msyh.ttc");
-    if let Ok(font_data) = fs::read(&msyh_path) {
-        fonts.font_data.insert(
-            "msyh".to_owned(),
-            std::sync::Arc::new(egui::FontData::from_owned(font_data)),
-        );
-        fonts.families.get_mut(&egui::FontFamily::Proportional).unwrap().insert(0, "msyh".to_owned());
-        fonts.families.get_mut(&egui::FontFamily::Monospace).unwrap().push("msyh".to_owned());
+    // 1. 中文主字体
+    let chosen = if custom.is_empty() {
+        None
+    } else {
+        match read_ui_font(Path::new(custom)) {
+            Ok(data) => Some((PathBuf::from(custom), data)),
+            Err(err) => {
+                logs.push(format!(
+                    "[X] 界面字体 {} 不可用（{}），改为自动选择",
+                    custom, err
+                ));
+                None
+            }
+        }
+    };
+    match chosen.or_else(|| find_ui_font(&mut logs)) {
+        Some((path, font_data)) => {
+            fonts.font_data.insert(
+                "ui_cjk".to_owned(),
+                std::sync::Arc::new(egui::FontData::from_owned(font_data)),
+            );
+            fonts
+                .families
+                .entry(egui::FontFamily::Proportional)
+                .or_default()
+                .insert(0, "ui_cjk".to_owned());
+            fonts
+                .families
+                .entry(egui::FontFamily::Monospace)
+                .or_default()
+                .push("ui_cjk".to_owned());
+            logs.push(format!("[i] 界面字体: {}", path.to_string_lossy()));
+        }
+        None => logs.push("[X] 未找到可显示中文的界面字体，界面文字可能显示为方框".to_string()),
     }
 
     // 2. Segoe UI Symbol (符号备选)
-    let symbol_path = PathBuf::from("C:\\Windows\\Fonts\\seguisym.ttf");
+    let symbol_path = system_fonts_dir().join("seguisym.ttf");
     if let Ok(font_data) = fs::read(&symbol_path) {
         fonts.font_data.insert(
             "symbols".to_owned(),
             std::sync::Arc::new(egui::FontData::from_owned(font_data)),
         );
-        fonts.families.get_mut(&egui::FontFamily::Proportional).unwrap().push("symbols".to_owned());
+        fonts
+            .families
+            .entry(egui::FontFamily::Proportional)
+            .or_default()
+            .push("symbols".to_owned());
     }
 
-    ctx.set_fonts(fonts);
+    ctx.set_fonts(fonts.clone());
+    (fonts, logs)
 }
 
 fn main() -> eframe::Result<()> {
+    // 日志时间从这里起算
+    log_clock_ms();
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(pos) = args.iter().position(|a| a == "--restore") {
+        let code = match args.get(pos + 1) {
+            Some(path) => restore_headless(Path::new(path)),
+            None => 1,
+        };
+        std::process::exit(code);
+    }
+    // --report <报告.html> <路径>...
+    if let Some(pos) = args.iter().position(|a| a == "--report") {
+        let code = match args.get(pos + 1) {
+            Some(report) => report_headless(Path::new(report), &args[pos + 2..]),
+            None => 1,
+        };
+        std::process::exit(code);
+    }
+
     let mut options = eframe::NativeOptions::default();
     options.viewport.min_inner_size = Some(egui::vec2(400.0, 400.0));
     eframe::run_native(
@@ -1017,3 +11402,694 @@ fn main() -> eframe::Result<()> {
         Box::new(|cc| Ok(Box::new(FontLoaderApp::new(cc)))),
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures")
+            .join(name)
+    }
+
+    fn fixtures_with_extension(ext: &str) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = fs::read_dir(fixture(""))
+            .expect("fixtures 目录")
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|e| e == ext))
+            .collect();
+        files.sort();
+        files
+    }
+
+    /// 每个测试独占的临时目录，开始时清空。
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("fontloader-test-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("创建临时目录");
+        dir
+    }
+
+    fn encode_utf16(text: &str, little_endian: bool) -> Vec<u8> {
+        let mut data = if little_endian {
+            vec![0xFF, 0xFE]
+        } else {
+            vec![0xFE, 0xFF]
+        };
+        for unit in text.encode_utf16() {
+            data.extend(if little_endian {
+                unit.to_le_bytes()
+            } else {
+                unit.to_be_bytes()
+            });
+        }
+        data
+    }
+
+    /// 解析 fixtures 中的字幕，返回按名称排序的 (字体名, 变体位)。
+    fn fixture_font_variants(name: &str) -> Vec<(String, u8)> {
+        let text = read_text(&fixture(name)).expect("读取字幕");
+        let mut fonts: Vec<_> = parse_ass_fonts(&text).variants.into_iter().collect();
+        fonts.sort();
+        fonts
+    }
+
+    fn owned(fonts: &[(&str, u8)]) -> Vec<(String, u8)> {
+        fonts.iter().map(|&(n, v)| (n.to_string(), v)).collect()
+    }
+
+    #[test]
+    fn streaming_parser_matches_whole_file_parser() {
+        let subs = fixtures_with_extension("ass");
+        assert!(!subs.is_empty());
+        let dir = scratch_dir("streaming");
+        for sub in &subs {
+            let whole = parse_ass_fonts(&read_text(sub).expect("读取字幕"));
+            assert_eq!(stream_ass_fonts(sub).as_ref(), Some(&whole), "{:?}", sub);
+
+            // 同一内容换成各种 BOM 与换行符后两条路径仍一致
+            let Ok(text) = String::from_utf8(fs::read(sub).expect("读取字幕")) else {
+                continue;
+            };
+            let stem = sub.file_stem().expect("文件名").to_string_lossy();
+            let crlf = text.replace("\r\n", "\n").replace('\n', "\r\n");
+            let variants = [
+                (
+                    "utf8bom",
+                    [&[0xEF, 0xBB, 0xBF][..], text.as_bytes()].concat(),
+                ),
+                ("utf16le", encode_utf16(&text, true)),
+                ("utf16be", encode_utf16(&text, false)),
+                ("crlf", crlf.into_bytes()),
+            ];
+            for (kind, data) in variants {
+                let path = dir.join(format!("{}-{}.ass", stem, kind));
+                fs::write(&path, data).expect("写入临时字幕");
+                let whole = parse_ass_fonts(&read_text(&path).expect("读取字幕"));
+                assert_eq!(stream_ass_fonts(&path), Some(whole), "{:?}", path);
+            }
+        }
+    }
+
+    fn report_entry(name: &str, path: Option<&str>, status: EntryStatus) -> FontEntry {
+        FontEntry {
+            name: name.to_string(),
+            path: path.map(String::from),
+            status,
+            sources: vec![r#"D:\字幕\<第1话> & "特典".ass"#.to_string()],
+            families: Vec::new(),
+            alternatives: Vec::new(),
+            conflicts: Vec::new(),
+            local_dir: None,
+            missing_variants: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn html_report_matches_snapshot() {
+        let mut variant = report_entry(
+            "方正兰亭圆_GBK",
+            Some(r#"D:\字体\<方正> & "兰亭".ttf"#),
+            EntryStatus::MissingVariant,
+        );
+        variant.missing_variants = vec!["粗体".to_string()];
+        variant.alternatives = vec![r"E:\备份\兰亭.ttf".to_string()];
+        let mut conflict =
+            report_entry("微软雅黑", Some(r"D:\字体\msyh.ttc"), EntryStatus::Conflict);
+        conflict.conflicts = vec!["微软雅黑".to_string(), "Microsoft YaHei".to_string()];
+        conflict.local_dir = Some(r"D:\字幕\Fonts & <附件>".to_string());
+        let result = ProcessResult {
+            loaded: 1,
+            failed: 0,
+            missing: 1,
+            duplicates: 0,
+            installed: 0,
+            system_conflicts: 1,
+            missing_variants: 1,
+            subs: 1,
+            empty_subs: 0,
+            fonts: 3,
+            invalid_fonts: 0,
+            logs: LogLines::default(),
+            entries: vec![
+                report_entry(
+                    "思源黑体 <Bold>",
+                    Some(r#"D:\字体\"思源" & 黑体.otf"#),
+                    EntryStatus::Loaded,
+                ),
+                report_entry("華康&lt;少女", None, EntryStatus::Missing),
+                variant,
+                conflict,
+            ],
+            subtitles: vec![SubtitleReport {
+                path: r#"D:\字幕\<第1话> & "特典".ass"#.to_string(),
+                required: 4,
+                available: 3,
+                coverage_pct: 75.0,
+            }],
+            cache: CacheStats::default(),
+            types: FontTypeStats::default(),
+            subs_only: false,
+            rule_hits: Vec::new(),
+            batch: LoadBatch::default(),
+            index: None,
+        };
+        let html = render_html_report(&result);
+        let snapshot = fixture("html_report.snapshot.html");
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            fs::write(&snapshot, &html).expect("写入快照");
+        }
+        let expected = fs::read_to_string(&snapshot).expect("读取快照");
+        assert_eq!(
+            html, expected,
+            "报告与快照不同，确认改动后用 UPDATE_SNAPSHOTS=1 重新生成"
+        );
+    }
+
+    fn loaded_state(paths: &[&str]) -> Arc<Mutex<AppState>> {
+        let mut state = AppState::default();
+        for path in paths {
+            state.loaded.insert(
+                PathKey::new(path),
+                LoadedFont {
+                    path: path.to_string(),
+                    faces: 1,
+                    ..LoadedFont::default()
+                },
+            );
+        }
+        Arc::new(Mutex::new(state))
+    }
+
+    #[test]
+    fn unload_removes_case_variants_of_one_file_once() {
+        let state = loaded_state(&[r"D:\Fonts\A.ttf", r"D:\Fonts\B.ttf"]);
+        let removed = std::cell::RefCell::new(Vec::new());
+        let only = vec![
+            r"D:\Fonts\A.ttf".to_string(),
+            r"d:\fonts\a.TTF".to_string(),
+            "D:/FONTS/A.ttf".to_string(),
+        ];
+        let result = unload_fonts_with(state.clone(), Some(only), false, &|path| {
+            removed.borrow_mut().push(path.to_string());
+            true
+        })
+        .expect("卸载");
+        assert_eq!(result.count, 1);
+        assert_eq!(removed.into_inner(), vec![r"D:\Fonts\A.ttf".to_string()]);
+        let state = state.lock().expect("状态锁");
+        assert_eq!(state.loaded.len(), 1);
+        assert!(state.loaded.contains_key(&PathKey::new(r"D:\Fonts\B.ttf")));
+    }
+
+    #[test]
+    fn same_file_in_different_case_registers_once() {
+        let registered = std::cell::RefCell::new(Vec::new());
+        let register = |path: &str| {
+            registered.borrow_mut().push(path.to_string());
+            Ok(1)
+        };
+        let options = LoadOptions::default();
+        let mut state = AppState::default();
+        let cancel = AtomicBool::new(false);
+        // 两次处理分别在不同大小写写法的目录中找到同一个文件
+        let results: Vec<ProcessResult> = [r"D:\Fonts\A.ttf", r"d:\fonts\a.ttf"]
+            .into_iter()
+            .map(|path| {
+                let mut index = FontIndex::default();
+                index
+                    .names
+                    .insert(NameKey::new("Font A"), vec![PathBuf::from(path)]);
+                let required = vec![("Font A".to_string(), vec!["ep01.ass".to_string()])];
+                load_required_fonts(
+                    required,
+                    &index,
+                    &options,
+                    &mut state,
+                    &|_, _, _| {},
+                    &cancel,
+                    &register,
+                )
+            })
+            .collect();
+        assert_eq!(registered.into_inner(), vec![r"D:\Fonts\A.ttf".to_string()]);
+        assert_eq!((results[0].loaded, results[0].duplicates), (1, 0));
+        assert_eq!((results[1].loaded, results[1].duplicates), (0, 1));
+        assert!(results[1].entries[0].status == EntryStatus::Duplicate);
+        assert_eq!(state.loaded.len(), 1);
+    }
+
+    #[test]
+    fn mixed_case_kept_fonts_are_released_once_per_registration() {
+        // 旧版会话：同一文件以两种大小写各注册了一次，另有一个普通文件
+        let kept = [
+            KeptFont {
+                path: r"D:\A.ttf".to_string(),
+                faces: 1,
+            },
+            KeptFont {
+                path: r"d:\a.ttf".to_string(),
+                faces: 1,
+            },
+            KeptFont {
+                path: r"D:\B.ttf".to_string(),
+                faces: 2,
+            },
+        ];
+        let removed = std::cell::RefCell::new(Vec::new());
+        let remove = |path: &str| {
+            removed.borrow_mut().push(path.to_lowercase());
+            true
+        };
+        let (loaded, released) = restore_kept_fonts(&kept, &remove);
+        assert_eq!(released, 1);
+        assert_eq!(loaded.len(), 2);
+
+        let state = Arc::new(Mutex::new(AppState {
+            loaded,
+            ..AppState::default()
+        }));
+        let result = unload_fonts_with(state.clone(), None, false, &remove).expect("卸载");
+        assert_eq!(result.count, 2);
+        assert!(state.lock().expect("状态锁").loaded.is_empty());
+        // 每次注册都恰好释放一次：a.ttf 两次，b.ttf 一次
+        let mut removed = removed.into_inner();
+        removed.sort();
+        assert_eq!(removed, [r"d:\a.ttf", r"d:\a.ttf", r"d:\b.ttf"]);
+    }
+
+    #[test]
+    fn excluded_fonts_are_left_out_of_the_index() {
+        let dir = scratch_dir("excluded");
+        let kept = dir.join("kept.ttf");
+        let dropped = dir.join("dropped.ttf");
+        fs::copy(fixture("dual_language_names.ttf"), &kept).expect("复制字体");
+        fs::copy(fixture("ucs4_names.ttf"), &dropped).expect("复制字体");
+        // 排除列表中的路径大小写与实际不同也应生效
+        let options = LoadOptions {
+            excluded: excluded_font_keys(&[dropped.to_string_lossy().to_uppercase()]),
+            ..LoadOptions::default()
+        };
+        let paths = vec![dir.to_string_lossy().to_string()];
+        let scan =
+            scan_inputs(&paths, &options, &|_, _| {}, &AtomicBool::new(false)).expect("扫描");
+        assert_eq!(scan.font_files, vec![kept]);
+        assert_eq!(scan.excluded, 1);
+    }
+
+    #[test]
+    fn name_rules_come_from_each_scans_options() {
+        let dir = scratch_dir("name_rules");
+        fs::copy(fixture("dual_language_names.ttf"), dir.join("dual.ttf")).expect("复制字体");
+        let script = "[V4+ Styles]\n\
+            Format: Name, Fontname, Fontsize\n\
+            Style: A,GRP Dual Sans,20\n";
+        fs::write(dir.join("ep01.ass"), script).expect("写入字幕");
+        let paths = vec![dir.to_string_lossy().to_string()];
+        let scan = |rules: &[(&str, &str)]| {
+            let rules: Vec<NameRule> = rules
+                .iter()
+                .map(|(pattern, replace)| NameRule {
+                    pattern: pattern.to_string(),
+                    replace: replace.to_string(),
+                })
+                .collect();
+            let options = LoadOptions {
+                name_rules: NameRules::new(&rules),
+                ..LoadOptions::default()
+            };
+            scan_inputs(&paths, &options, &|_, _| {}, &AtomicBool::new(false)).expect("扫描")
+        };
+        let hit = |from: &str, to: &str| (from.to_string(), to.to_string());
+
+        // 只改写字幕一侧：去掉字幕组前缀后与字体文件的名称一致
+        let stripped = scan(&[("^GRP ", "")]);
+        assert!(stripped.required_fonts.contains("Dual Sans"));
+        assert!(stripped
+            .index
+            .names
+            .contains_key(&NameKey::new("Dual Sans")));
+        assert_eq!(
+            stripped.rule_hits.into_iter().collect::<Vec<_>>(),
+            vec![hit("GRP Dual Sans", "Dual Sans")]
+        );
+
+        // 同时进行的另一次扫描使用自己的规则，两侧都被改写
+        let renamed = scan(&[("Sans", "Serif")]);
+        assert!(renamed.required_fonts.contains("GRP Dual Serif"));
+        assert!(renamed
+            .index
+            .names
+            .contains_key(&NameKey::new("Dual Serif")));
+        assert!(!renamed.index.names.contains_key(&NameKey::new("Dual Sans")));
+        assert!(renamed
+            .rule_hits
+            .contains(&hit("GRP Dual Sans", "GRP Dual Serif")));
+        assert!(renamed.rule_hits.contains(&hit("Dual Sans", "Dual Serif")));
+
+        let plain = scan(&[]);
+        assert!(plain.required_fonts.contains("GRP Dual Sans"));
+        assert!(plain.rule_hits.is_empty());
+    }
+
+    #[test]
+    fn config_import_reports_every_missing_path() {
+        let dir = scratch_dir("config_paths");
+        let font = dir.join("kept.ttf");
+        fs::copy(fixture("dual_language_names.ttf"), &font).expect("复制字体");
+        let gone = dir.join("gone");
+        let config = Config {
+            player_path: gone.join("mpv.exe").to_string_lossy().to_string(),
+            excluded_fonts: vec![
+                font.to_string_lossy().to_string(),
+                gone.join("dropped.ttf").to_string_lossy().to_string(),
+            ],
+            structured_log_path: Some(gone.join("log.jsonl")),
+            ..Config::default()
+        };
+        let expected: Vec<String> = [gone.join("mpv.exe"), gone.join("dropped.ttf"), gone.clone()]
+            .iter()
+            .map(|path| path.to_string_lossy().to_string())
+            .collect();
+        assert_eq!(config_missing_paths(&config), expected);
+        // 日志文件尚不存在但目录存在时不算缺失
+        let config = Config {
+            structured_log_path: Some(dir.join("log.jsonl")),
+            ..Config::default()
+        };
+        assert!(config_missing_paths(&config).is_empty());
+    }
+
+    #[test]
+    fn imported_cache_ttl_must_be_in_range() {
+        let mut config = Config {
+            cache_ttl_days: MAX_CACHE_TTL_DAYS,
+            ..Config::default()
+        };
+        assert!(validate_config(&config).is_ok());
+        // 手写的配置文件中过大的天数换算成秒会溢出
+        config.cache_ttl_days = u64::MAX;
+        assert!(validate_config(&config).is_err());
+    }
+
+    #[test]
+    fn font_aliases_round_trip_through_settings_text() {
+        let aliases = parse_font_aliases(
+            "方正兰亭圆_GBK => 方正兰亭圆简体\n无效行\n => 空\nArial=>Helvetica",
+        );
+        assert_eq!(aliases.len(), 2);
+        assert_eq!(parse_font_aliases(&format_font_aliases(&aliases)), aliases);
+        let keys = alias_key_map(&aliases);
+        assert_eq!(
+            keys.get(&NameKey::new("ARIAL")).map(String::as_str),
+            Some("Helvetica")
+        );
+    }
+
+    #[test]
+    fn path_keys_ignore_case_separators_and_trailing_slashes() {
+        assert_eq!(normalize_path_key("C:/Fonts/A.TTF"), "c:\\fonts\\a.ttf");
+        assert_eq!(normalize_path_key("C:\\Fonts/Sub\\"), "c:\\fonts\\sub");
+        assert_eq!(normalize_path_key("c:\\fonts\\\\"), "c:\\fonts");
+        assert_eq!(normalize_path_key("C:\\"), "c:\\");
+        assert_eq!(normalize_path_key("C:/"), "c:\\");
+        assert_eq!(normalize_path_key("C:"), "c:");
+    }
+
+    #[test]
+    fn descendant_paths_respect_component_boundaries() {
+        assert!(is_descendant_path("C:\\Proj\\Sub\\a.ass", "c:/proj"));
+        assert!(is_descendant_path("c:/proj/", "C:\\PROJ"));
+        assert!(is_descendant_path("C:\\proj", "C:\\proj\\"));
+        assert!(is_descendant_path("C:\\proj\\x", "C:\\"));
+        assert!(is_descendant_path("C:/proj/x", "c:/"));
+        assert!(!is_descendant_path("C:\\proj2", "C:\\proj"));
+        assert!(!is_descendant_path("C:\\proj2\\a.ass", "C:\\proj\\"));
+        assert!(!is_descendant_path("C:\\proj", "C:\\proj\\sub"));
+        assert!(!is_descendant_path("D:\\proj", "C:\\"));
+    }
+
+    #[test]
+    fn subtitle_and_index_names_share_one_key() {
+        let dir = scratch_dir("name_key");
+        let font = dir.join("dual.ttf");
+        fs::copy(fixture("dual_language_names.ttf"), &font).expect("复制字体");
+        let index = build_font_index(
+            &[font],
+            false,
+            &mut CacheFile::default(),
+            &NameRules::default(),
+            &|_, _| {},
+            &AtomicBool::new(false),
+        );
+        let script = "[V4+ Styles]\n\
+            Format: Name, Fontname, Fontsize\n\
+            Style: A, ＤＵＡＬ　Ｓａｎｓ\u{200B},20\n\
+            Style: B,\u{FEFF}双语黑体 ,20\n\
+            [Events]\n\
+            Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n\
+            Dialogue: 0,0:00:00.00,0:00:01.00,A,,0,0,0,,{\\fndual sans\u{2060}}x";
+        let fonts = parse_ass_fonts(script).fonts;
+        let required: HashSet<NameKey> = fonts.iter().map(|f| NameKey::new(f)).collect();
+        let expected: HashSet<NameKey> = [NameKey::new("Dual Sans"), NameKey::new("双语黑体")]
+            .into_iter()
+            .collect();
+        assert_eq!(required, expected);
+        for key in &required {
+            assert!(index.names.contains_key(key), "{key:?} 不在索引中");
+        }
+        assert_eq!(
+            filter_key(" ＤＵＡＬ\u{3000}\u{3000}Sans\u{200B}"),
+            "dual sans"
+        );
+    }
+
+    #[test]
+    fn dual_language_family_names_are_both_indexed() {
+        let dir = scratch_dir("dual_language");
+        let font = dir.join("dual.ttf");
+        fs::copy(fixture("dual_language_names.ttf"), &font).expect("复制字体");
+        let index = build_font_index(
+            std::slice::from_ref(&font),
+            false,
+            &mut CacheFile::default(),
+            &NameRules::default(),
+            &|_, _| {},
+            &AtomicBool::new(false),
+        );
+        // 0x0409 与 0x0804 的家族名 (ID 1) 和全名 (ID 4) 都指向同一文件
+        for name in [
+            "Dual Sans",
+            "Dual Sans Regular",
+            "双语黑体",
+            "双语黑体 常规",
+        ] {
+            assert_eq!(
+                index.names.get(&NameKey::new(name)),
+                Some(&vec![font.clone()]),
+                "{name}"
+            );
+        }
+        let families = &index.families[&font];
+        assert!(families.iter().any(|f| f == "Dual Sans"));
+        assert!(families.iter().any(|f| f == "双语黑体"));
+    }
+
+    #[test]
+    fn supplementary_plane_names_match_between_font_and_subtitle() {
+        let data = fs::read(fixture("ucs4_names.ttf")).expect("读取字体");
+        let names = parse_font_names_from_bytes(&data);
+        assert_eq!(names.invalid, None);
+        // 平台 3 编码 10 的记录保留代理对，Shift-JIS 记录被跳过
+        assert!(names.names.iter().any(|n| n == "𠀀𪚥体 😀"));
+        assert!(!names.names.iter().any(|n| n.contains("SJIS")));
+        let indexed: HashSet<NameKey> = names.names.iter().map(|n| NameKey::new(n)).collect();
+        let fonts = fixture_font_variants("supplementary_plane_names.ass");
+        assert_eq!(fonts, owned(&[("𠀀𪚥体", 1), ("𠀀𪚥体 😀", 1)]));
+        for (font, _) in &fonts {
+            assert!(indexed.contains(&NameKey::new(font)), "{font}");
+        }
+    }
+
+    #[test]
+    fn embedded_attachments_do_not_produce_phantom_fonts() {
+        assert_eq!(
+            fixture_font_variants("embedded_fonts.ass"),
+            owned(&[("FOT-Rodin Pro DB", 1), ("思源黑体 CN Medium", 1)])
+        );
+    }
+
+    #[test]
+    fn events_after_a_broken_header_still_parse() {
+        // 残缺节头下的 Style 行不沿用 [V4+ Styles] 的解析状态
+        assert_eq!(
+            fixture_font_variants("broken_header.ass"),
+            owned(&[("Arial", 1), ("思源黑体", 1), ("方正楷体_GBK", 1)])
+        );
+    }
+
+    #[test]
+    fn charset_hint_decodes_gbk_subtitles() {
+        let data = fs::read(fixture("charset_hint_gbk.ass")).expect("读取字幕");
+        assert!(std::str::from_utf8(&data).is_err());
+        let text = decode_text(data).expect("按 GBK 解码");
+        assert!(text.contains("; charset: GBK"));
+        let fonts = parse_ass_fonts(&text).fonts;
+        let expected: HashSet<String> = ["方正兰亭圆_GBK", "华文楷体"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        assert_eq!(fonts, expected);
+    }
+
+    #[test]
+    fn karaoke_transforms_yield_whole_font_names() {
+        // 模板注释行不计入，`\t(...)` 内的 `\fn` 截止于下一个 `\` 或匹配的右括号
+        assert_eq!(
+            fixture_font_variants("karaoke_template.ass"),
+            owned(&[
+                ("Arial Rounded MT Bold", 1),
+                ("HGMaruGothicMPRO", 1),
+                ("MS Gothic", 1),
+                ("Source Han Sans JP", 1),
+                ("Yu Gothic UI Semibold", 1),
+                ("华康圆体W7", 1),
+                ("方正兰亭圆_GBK", 1),
+            ])
+        );
+    }
+
+    #[test]
+    fn bold_and_italic_overrides_are_recorded_per_font() {
+        let regular = variant_bit(false, false);
+        let bold = variant_bit(true, false);
+        let italic = variant_bit(false, true);
+        let bold_italic = variant_bit(true, true);
+        // `\b700` 算粗体，`\iclip` 不算斜体，`\r样式` 换用该样式的字体与变体
+        assert_eq!(
+            fixture_font_variants("bold_italic_overrides.ass"),
+            owned(&[
+                ("Arial", bold),
+                ("思源黑体", regular | bold | italic | bold_italic),
+                ("方正楷体_GBK", italic),
+            ])
+        );
+    }
+
+    #[test]
+    fn inflated_num_tables_is_rejected() {
+        let data = fs::read(fixture("inflated_num_tables.ttf")).expect("读取字体");
+        let names = parse_font_names_from_bytes(&data);
+        // 声明的 6 条记录超出了表目录，不能把表数据当作记录读出 name 表
+        assert!(names.names.is_empty());
+        let reason = names.invalid.expect("应判为损坏");
+        assert!(reason.contains("numTables"), "{reason}");
+    }
+
+    #[test]
+    fn escapes_terminate_fn_names() {
+        // `\h`、`\N` 不属于字体名；`\fn\h` 视为恢复样式字体，其后的 SimHei 是正文
+        assert_eq!(
+            fixture_font_variants("fn_escape_terminators.ass"),
+            owned(&[
+                ("Arial", 1),
+                ("Times New Roman", 1),
+                ("思源黑体", 1),
+                ("方正楷体_GBK", 1),
+            ])
+        );
+    }
+
+    #[test]
+    fn log_lines_keep_the_time_they_were_written() {
+        // 模拟工作线程先产生日志，界面稍后才合并并写入结构化日志
+        let mut worker = LogLines::default();
+        worker.push("[+] 早的一行".to_string());
+        thread::sleep(Duration::from_millis(30));
+        worker.push("[X] 晚的一行".to_string());
+        thread::sleep(Duration::from_millis(30));
+        let mut ui = LogLines::default();
+        ui.push("[i] 界面的一行".to_string());
+        ui.append(&mut worker);
+        assert!(worker.is_empty());
+        let times = ui.times().to_vec();
+        assert!(times[2] >= times[1] + 30, "{times:?}");
+        assert!(times[0] >= times[2] + 30, "{times:?}");
+
+        let path = scratch_dir("structured_log").join("log.jsonl");
+        append_structured_log(&path, &ui, ui.times()).expect("写入结构化日志");
+        let written: Vec<(String, u64)> = fs::read_to_string(&path)
+            .expect("读取结构化日志")
+            .lines()
+            .map(|line| {
+                let entry: serde_json::Value = serde_json::from_str(line).expect("JSON");
+                (
+                    entry["level"].as_str().unwrap_or_default().to_string(),
+                    entry["elapsed_ms"].as_u64().unwrap_or_default(),
+                )
+            })
+            .collect();
+        let levels: Vec<&str> = written.iter().map(|(level, _)| level.as_str()).collect();
+        assert_eq!(levels, ["info", "+", "error"]);
+        let written_times: Vec<u64> = written.iter().map(|&(_, ms)| ms).collect();
+        assert_eq!(written_times, times);
+        assert_eq!(
+            serde_json::to_string(&ui).expect("序列化"),
+            r#"["[i] 界面的一行","[+] 早的一行","[X] 晚的一行"]"#
+        );
+    }
+
+    #[test]
+    fn timed_out_registrations_are_unloaded_at_next_start() {
+        let font = |path: &str| KeptFont {
+            path: path.to_string(),
+            faces: 2,
+        };
+        let keep: HashSet<PathKey> = [PathKey::new("C:\\Subs\\Player.ttf")].into_iter().collect();
+        let session = outstanding_session(
+            vec![
+                font("c:/subs/player.ttf"),
+                font("C:\\Subs\\A.ttf"),
+                font("C:\\Subs\\B.ttf"),
+            ],
+            &keep,
+        );
+        assert_eq!(session.registered_faces, 6);
+        let kept: Vec<&str> = session.kept_fonts.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(kept, ["c:/subs/player.ttf"]);
+
+        // 经过会话文件往返后，只卸载不为播放器保留的字体
+        let data = serde_json::to_vec(&session).expect("序列化");
+        let session: SessionState = serde_json::from_slice(&data).expect("反序列化");
+        let removed = Mutex::new(Vec::new());
+        let remove = |path: &str| {
+            removed.lock().expect("锁").push(path.to_string());
+            !path.ends_with("B.ttf")
+        };
+        let (unloaded, failed) = unload_orphaned_fonts(&session.orphaned_fonts, &remove);
+        assert_eq!(unloaded, 1);
+        assert_eq!(failed, ["C:\\Subs\\B.ttf"]);
+        assert_eq!(
+            *removed.lock().expect("锁"),
+            ["C:\\Subs\\A.ttf", "C:\\Subs\\B.ttf"]
+        );
+        // 旧版会话文件没有该字段
+        let old: SessionState =
+            serde_json::from_str(r#"{"registered_faces":1,"kept_fonts":[]}"#).expect("旧版会话");
+        assert!(old.orphaned_fonts.is_empty());
+    }
+
+    #[test]
+    fn charset_scanner_concludes_at_first_other_section() {
+        let mut scanner = CharsetHintScanner::default();
+        assert_eq!(scanner.feed(b"; charset: GBK"), None);
+        assert_eq!(scanner.feed(b"[V4+ Styles]"), Some(None));
+
+        let mut scanner = CharsetHintScanner::default();
+        assert_eq!(scanner.feed(b"[Script Info]"), None);
+        assert_eq!(scanner.feed(b"Title: x"), None);
+        assert_eq!(scanner.feed(b"[Events]"), Some(None));
+    }
+}